@@ -0,0 +1,60 @@
+// Measures minify and parse throughput on a generated log export.
+//
+//     cargo run --release --example throughput -- [size in MiB]
+use std::time::Instant;
+
+fn sample(size: usize) -> String {
+	let mut json = String::from("[\n");
+	let mut i = 0u64;
+	while json.len() < size {
+		if i > 0 {
+			json.push_str(",\n");
+		}
+		json.push_str(&format!(
+			"  {{\n    \"id\": {},\n    \"level\": \"{}\",\n    \"message\": \"request handled in {} ms by worker-{} \\\"ok\\\"\",\n    \"tags\": [\"api\", \"v2\", \"région\"],\n    \"latency\": {}.{}\n  }}",
+			i, ["info", "warn", "error"][i as usize % 3], i % 997, i % 16, i % 300, i % 100,
+		));
+		i += 1;
+	}
+	json.push_str("\n]\n");
+	json
+}
+
+fn measure<F: FnMut() -> usize>(name: &str, bytes: usize, mut f: F) {
+	// One warm-up run, then the best of three
+	let mut check = f();
+	let mut best = f64::MAX;
+	for _ in 0..3 {
+		let start = Instant::now();
+		check = check.wrapping_add(f());
+		best = best.min(start.elapsed().as_secs_f64());
+	}
+	println!("{:<24} {:>8.3} GB/s  ({})", name, bytes as f64 / best / 1e9, check % 10);
+}
+
+fn main() {
+	let mib = std::env::args().nth(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(64);
+	let pretty = sample(mib << 20);
+	let mut compact = Vec::new();
+	minjson::JsonMinimizer::new_from_str(&pretty).write_to(&mut compact).unwrap();
+	let compact = String::from_utf8(compact).unwrap();
+
+	for (label, json) in [("pretty", &pretty), ("compact", &compact)] {
+		println!("{} input: {} bytes", label, json.len());
+		measure("minify (char iterator)", json.len(), || minjson::JsonMinimizer::new_from_str(json).collect::<String>().len());
+		measure("minify (write_to)", json.len(), || {
+			let mut out = Vec::with_capacity(json.len());
+			minjson::JsonMinimizer::new_from_str(json).write_to(&mut out).unwrap();
+			out.len()
+		});
+		measure("parse (JsonElement)", json.len(), || match minjson::build_json_graph(json).unwrap() {
+			minjson::JsonElement::JsonArray(ref v) => v.len(),
+			_ => 0,
+		});
+		measure("parse (JsonValueRef)", json.len(), || match minjson::build_json_value_ref(json).unwrap() {
+			minjson::JsonValueRef::JsonArray(ref v) => v.len(),
+			_ => 0,
+		});
+		measure("parse (JsonArena)", json.len(), || minjson::build_json_arena(json).unwrap().root().len());
+	}
+}
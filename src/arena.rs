@@ -0,0 +1,458 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::{parse_document, write_json_string, JsonElement, JsonNum, ParseError, ParseSetting, ParseTree};
+
+// A parsed document kept in a few flat buffers rather than one allocation per node: nodes in
+// a vector, container contents as index ranges into two more, and every string, key and
+// decimal lexeme in one shared text buffer. Building and freeing it takes a handful of
+// allocations however large the document is. Object members are stored sorted by key.
+#[derive(Debug, Clone, Default)]
+pub struct JsonArena {
+	nodes: Vec<Node>,
+	// Node indices of array elements; each array owns a contiguous range.
+	items: Vec<usize>,
+	// Keys and node indices of object members; each object owns a contiguous range.
+	members: Vec<(Span, usize)>,
+	text: String,
+	root: usize,
+}
+
+// A range of `JsonArena::text`, or of `items` or `members` for containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+	start: usize,
+	len: usize,
+}
+
+impl Span {
+	fn range(self) -> Range<usize> {
+		self.start..self.start + self.len
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Node {
+	Null,
+	Bool(bool),
+	Integer(i64),
+	Double(f64),
+	Decimal(Span),
+	String(Span),
+	Array(Span),
+	Object(Span),
+}
+
+// A value in a `JsonArena`. Copying it is free; it borrows the arena it points into.
+#[derive(Clone, Copy)]
+pub struct JsonNode<'a> {
+	arena: &'a JsonArena,
+	index: usize,
+}
+
+pub fn build_json_arena(json: &str) -> Result<JsonArena, String> {
+	build_json_arena_with_setting(json, ParseSetting::default())
+}
+
+pub fn build_json_arena_with_setting(json: &str, settings: ParseSetting) -> Result<JsonArena, String> {
+	try_build_json_arena(json, settings).map_err(String::from)
+}
+
+pub fn try_build_json_arena(json: &str, settings: ParseSetting) -> Result<JsonArena, ParseError> {
+	let mut tree = ArenaTree {
+		arena: JsonArena::default(),
+		open_items: Vec::new(),
+		open_members: Vec::new(),
+		open_keys: settings.limits.max_members.map(|_| Vec::new()),
+	};
+	let root = parse_document(json, settings, false, &mut tree)?;
+	tree.arena.root = root;
+	Ok(tree.arena)
+}
+
+impl JsonArena {
+	pub fn root(&self) -> JsonNode<'_> {
+		JsonNode { arena: self, index: self.root }
+	}
+
+	// Looks up a value by JSON Pointer from the root, as `JsonElement::pointer`.
+	pub fn pointer(&self, pointer: &str) -> Option<JsonNode<'_>> {
+		self.root().pointer(pointer)
+	}
+
+	// The number of values stored, including members replaced by a later duplicate key.
+	pub fn node_count(&self) -> usize {
+		self.nodes.len()
+	}
+
+	fn str(&self, span: Span) -> &str {
+		&self.text[span.range()]
+	}
+
+	fn push_str(&mut self, s: &str) -> Span {
+		let start = self.text.len();
+		self.text.push_str(s);
+		Span { start, len: s.len() }
+	}
+
+	fn push_node(&mut self, node: Node) -> usize {
+		self.nodes.push(node);
+		self.nodes.len() - 1
+	}
+}
+
+impl<'a> JsonNode<'a> {
+	// The JSON Schema name of the value's type, as `JsonElement::type_name`.
+	pub fn type_name(&self) -> &'static str {
+		match self.node() {
+			Node::Null => "null",
+			Node::Bool(_) => "boolean",
+			Node::String(_) => "string",
+			Node::Array(_) => "array",
+			Node::Object(_) => "object",
+			Node::Integer(_) => "integer",
+			Node::Double(_) => "number",
+			Node::Decimal(span) if !self.arena.str(span).contains(['.', 'e', 'E']) => "integer",
+			Node::Decimal(_) => "number",
+		}
+	}
+
+	// Looks up a value by RFC 6901 JSON Pointer, as `JsonElement::pointer`.
+	pub fn pointer(&self, pointer: &str) -> Option<JsonNode<'a>> {
+		if pointer.is_empty() {
+			return Some(*self);
+		}
+		if !pointer.starts_with('/') {
+			return None;
+		}
+
+		let mut target = *self;
+		for token in pointer[1..].split('/') {
+			let token = token.replace("~1", "/").replace("~0", "~");
+			target = match target.node() {
+				Node::Object(_) => target.get(&token)?,
+				Node::Array(_) => target.element(token.parse::<usize>().ok()?)?,
+				_ => return None,
+			};
+		}
+		Some(target)
+	}
+
+	pub fn is_null(&self) -> bool {
+		self.node() == Node::Null
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self.node() {
+			Node::Bool(b) => Some(b),
+			_ => None,
+		}
+	}
+
+	pub fn as_number(&self) -> Option<JsonNum> {
+		match self.node() {
+			Node::Integer(i) => Some(JsonNum::Integer(i)),
+			Node::Double(d) => Some(JsonNum::Double(d)),
+			Node::Decimal(span) => Some(JsonNum::Decimal(self.arena.str(span).to_string())),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&'a str> {
+		match self.node() {
+			Node::String(span) => Some(self.arena.str(span)),
+			_ => None,
+		}
+	}
+
+	// The number of array elements or object members; scalars have none.
+	pub fn len(&self) -> usize {
+		match self.node() {
+			Node::Array(span) | Node::Object(span) => span.len,
+			_ => 0,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// The array element at `index`.
+	pub fn element(&self, index: usize) -> Option<JsonNode<'a>> {
+		match self.node() {
+			Node::Array(span) => self.arena.items[span.range()].get(index).map(|&i| self.at(i)),
+			_ => None,
+		}
+	}
+
+	// The object member named `key`. Members are sorted, so this is a binary search.
+	pub fn get(&self, key: &str) -> Option<JsonNode<'a>> {
+		let members = self.member_slice();
+		let found = members.binary_search_by(|&(k, _)| self.arena.str(k).cmp(key)).ok()?;
+		Some(self.at(members[found].1))
+	}
+
+	// Array elements in order. Other values have none.
+	pub fn elements(&self) -> impl Iterator<Item = JsonNode<'a>> + 'a {
+		let node = *self;
+		let items = match self.node() {
+			Node::Array(span) => &self.arena.items[span.range()],
+			_ => &[],
+		};
+		items.iter().map(move |&i| node.at(i))
+	}
+
+	// Object members sorted by key. Other values have none.
+	pub fn members(&self) -> impl Iterator<Item = (&'a str, JsonNode<'a>)> + 'a {
+		let node = *self;
+		self.member_slice().iter().map(move |&(k, i)| (node.arena.str(k), node.at(i)))
+	}
+
+	// Copies the value out into an owned tree, walking it with an explicit stack.
+	pub fn to_element(&self) -> JsonElement {
+		enum Frame<'a> {
+			Array(std::slice::Iter<'a, usize>, Vec<JsonElement>),
+			Object(std::slice::Iter<'a, (Span, usize)>, HashMap<String, JsonElement>, &'a str),
+		}
+
+		let arena = self.arena;
+		let mut stack = Vec::new();
+		let mut next = self.index;
+		loop {
+			let mut value = match arena.nodes[next] {
+				Node::Array(span) => {
+					stack.push(Frame::Array(arena.items[span.range()].iter(), Vec::with_capacity(span.len)));
+					None
+				},
+				Node::Object(span) => {
+					stack.push(Frame::Object(arena.members[span.range()].iter(), HashMap::with_capacity(span.len), ""));
+					None
+				},
+				Node::String(span) => Some(JsonElement::JsonString(arena.str(span).to_string())),
+				Node::Null => Some(JsonElement::JsonNull),
+				Node::Bool(b) => Some(JsonElement::JsonBool(b)),
+				_ => self.at(next).as_number().map(JsonElement::JsonNumber),
+			};
+
+			// Hand the finished value to its parent until some parent has a child left to copy.
+			loop {
+				match stack.last_mut() {
+					None => return value.unwrap(),
+					Some(Frame::Array(iter, out)) => {
+						out.extend(value.take());
+						if let Some(&child) = iter.next() {
+							next = child;
+							break;
+						}
+					},
+					Some(Frame::Object(iter, out, key)) => {
+						if let Some(v) = value.take() {
+							out.insert(key.to_string(), v);
+						}
+						if let Some(&(k, child)) = iter.next() {
+							*key = arena.str(k);
+							next = child;
+							break;
+						}
+					},
+				}
+				value = match stack.pop() {
+					Some(Frame::Array(_, out)) => Some(JsonElement::JsonArray(out)),
+					Some(Frame::Object(_, out, _)) => Some(JsonElement::JsonObject(out)),
+					None => None,
+				};
+			}
+		}
+	}
+
+	fn node(&self) -> Node {
+		self.arena.nodes[self.index]
+	}
+
+	fn at(&self, index: usize) -> JsonNode<'a> {
+		JsonNode { arena: self.arena, index }
+	}
+
+	fn member_slice(&self) -> &'a [(Span, usize)] {
+		match self.node() {
+			Node::Object(span) => &self.arena.members[span.range()],
+			_ => &[],
+		}
+	}
+}
+
+impl<'a> From<JsonNode<'a>> for JsonElement {
+	fn from(node: JsonNode<'a>) -> JsonElement {
+		node.to_element()
+	}
+}
+
+impl From<&JsonArena> for JsonElement {
+	fn from(arena: &JsonArena) -> JsonElement {
+		arena.root().to_element()
+	}
+}
+
+// Writes the value as minified JSON, exactly as `JsonElement` would.
+impl<'a> std::fmt::Display for JsonNode<'a> {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		enum Step {
+			Value(usize),
+			Key(Span),
+			Text(&'static str),
+		}
+
+		let arena = self.arena;
+		let mut stack = vec![Step::Value(self.index)];
+		while let Some(step) = stack.pop() {
+			match step {
+				Step::Text(t) => formatter.write_str(t)?,
+				Step::Key(k) => {
+					write_json_string(formatter, arena.str(k))?;
+					formatter.write_str(":")?;
+				},
+				Step::Value(i) => match arena.nodes[i] {
+					Node::Array(span) => {
+						formatter.write_str("[")?;
+						stack.push(Step::Text("]"));
+						for (n, &child) in arena.items[span.range()].iter().enumerate().rev() {
+							stack.push(Step::Value(child));
+							if n != 0 {
+								stack.push(Step::Text(","));
+							}
+						}
+					},
+					Node::Object(span) => {
+						formatter.write_str("{")?;
+						stack.push(Step::Text("}"));
+						for (n, &(k, child)) in arena.members[span.range()].iter().enumerate().rev() {
+							stack.push(Step::Value(child));
+							stack.push(Step::Key(k));
+							if n != 0 {
+								stack.push(Step::Text(","));
+							}
+						}
+					},
+					Node::Null => formatter.write_str("null")?,
+					Node::Bool(b) => write!(formatter, "{}", b)?,
+					Node::Integer(n) => write!(formatter, "{}", n)?,
+					// JSON has no representation for Infinity and NaN
+					Node::Double(d) if !d.is_finite() => formatter.write_str("null")?,
+					Node::Double(d) => write!(formatter, "{}", d)?,
+					Node::Decimal(span) => formatter.write_str(arena.str(span))?,
+					Node::String(span) => write_json_string(formatter, arena.str(span))?,
+				},
+			}
+		}
+		Ok(())
+	}
+}
+
+impl<'a> std::fmt::Debug for JsonNode<'a> {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "JsonNode({})", self)
+	}
+}
+
+impl std::fmt::Display for JsonArena {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "{}", self.root())
+	}
+}
+
+struct ArenaTree {
+	arena: JsonArena,
+	// Elements and members of the containers still open, innermost last. Containers start
+	// as an offset into these and move their contents to the arena when they close.
+	open_items: Vec<usize>,
+	open_members: Vec<(Span, usize)>,
+	// With a member limit, the distinct keys of each open object, innermost last. Duplicate
+	// keys count once, as they do for the other trees.
+	open_keys: Option<Vec<HashSet<String>>>,
+}
+
+impl<'a> ParseTree<'a> for ArenaTree {
+	type Value = usize;
+	type Array = usize;
+	type Object = usize;
+
+	fn string(&mut self, s: Cow<'a, str>) -> usize {
+		let span = self.arena.push_str(&s);
+		self.arena.push_node(Node::String(span))
+	}
+
+	fn number(&mut self, n: JsonNum) -> usize {
+		let node = match n {
+			JsonNum::Integer(i) => Node::Integer(i),
+			JsonNum::Double(d) => Node::Double(d),
+			JsonNum::Decimal(s) => Node::Decimal(self.arena.push_str(&s)),
+		};
+		self.arena.push_node(node)
+	}
+
+	fn null(&mut self) -> usize {
+		self.arena.push_node(Node::Null)
+	}
+
+	fn bool(&mut self, b: bool) -> usize {
+		self.arena.push_node(Node::Bool(b))
+	}
+
+	fn array(&mut self) -> usize {
+		self.open_items.len()
+	}
+
+	fn push(&mut self, array: &mut usize, value: usize) -> usize {
+		self.open_items.push(value);
+		self.open_items.len() - *array
+	}
+
+	fn close_array(&mut self, array: usize) -> usize {
+		let start = self.arena.items.len();
+		self.arena.items.extend(self.open_items.drain(array..));
+		let len = self.arena.items.len() - start;
+		self.arena.push_node(Node::Array(Span { start, len }))
+	}
+
+	fn object(&mut self) -> usize {
+		if let Some(keys) = &mut self.open_keys {
+			keys.push(HashSet::new());
+		}
+		self.open_members.len()
+	}
+
+	// Members are inserted into the innermost open object.
+	fn insert(&mut self, object: &mut usize, key: Cow<'a, str>, value: usize) -> usize {
+		let span = self.arena.push_str(&key);
+		self.open_members.push((span, value));
+		match self.open_keys.as_mut().and_then(|keys| keys.last_mut()) {
+			Some(keys) => {
+				keys.insert(key.into_owned());
+				keys.len()
+			},
+			None => self.open_members.len() - *object,
+		}
+	}
+
+	fn close_object(&mut self, object: usize) -> usize {
+		if let Some(keys) = &mut self.open_keys {
+			keys.pop();
+		}
+		let arena = &mut self.arena;
+		let text = &arena.text;
+		let open = &mut self.open_members[object..];
+		// The sort is stable, so of several members with one key the last one read comes last.
+		open.sort_by(|a, b| text[a.0.range()].cmp(&text[b.0.range()]));
+
+		let start = arena.members.len();
+		let mut iter = self.open_members.drain(object..).peekable();
+		while let Some(member) = iter.next() {
+			if !matches!(iter.peek(), Some(next) if text[next.0.range()] == text[member.0.range()]) {
+				arena.members.push(member);
+			}
+		}
+		let len = arena.members.len() - start;
+		arena.push_node(Node::Object(Span { start, len }))
+	}
+}
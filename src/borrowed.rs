@@ -0,0 +1,189 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::{parse_document, JsonElement, JsonNum, ParseError, ParseSetting, ParseTree};
+
+// A parsed document that borrows its strings and keys from the input wherever they contain
+// no escape sequences, so parsing allocates only for containers and escaped strings.
+#[derive(Debug, PartialEq)]
+pub enum JsonValueRef<'a> {
+	JsonNumber(JsonNum),
+	JsonString(Cow<'a, str>),
+	JsonArray(Vec<JsonValueRef<'a>>),
+	JsonObject(HashMap<Cow<'a, str>, JsonValueRef<'a>>),
+	JsonNull,
+	JsonBool(bool),
+}
+
+pub fn build_json_value_ref(json: &str) -> Result<JsonValueRef<'_>, String> {
+	build_json_value_ref_with_setting(json, ParseSetting::default())
+}
+
+pub fn build_json_value_ref_with_setting(json: &str, settings: ParseSetting) -> Result<JsonValueRef<'_>, String> {
+	try_build_json_value_ref(json, settings).map_err(String::from)
+}
+
+pub fn try_build_json_value_ref(json: &str, settings: ParseSetting) -> Result<JsonValueRef<'_>, ParseError> {
+	parse_document(json, settings, false, &mut ValueRefTree)
+}
+
+impl<'a> JsonValueRef<'a> {
+	// The JSON Schema name of the value's type, as `JsonElement::type_name`.
+	pub fn type_name(&self) -> &'static str {
+		use JsonValueRef::*;
+
+		match self {
+			JsonNull => "null",
+			JsonBool(_) => "boolean",
+			JsonString(_) => "string",
+			JsonArray(_) => "array",
+			JsonObject(_) => "object",
+			JsonNumber(n) if n.is_integer() => "integer",
+			JsonNumber(_) => "number",
+		}
+	}
+
+	// Copies the borrowed strings, walking the tree with an explicit stack like `JsonElement::clone`.
+	pub fn into_owned(self) -> JsonElement {
+		use JsonValueRef::*;
+
+		enum Frame<'a> {
+			Array(std::vec::IntoIter<JsonValueRef<'a>>, Vec<JsonElement>),
+			Object(std::collections::hash_map::IntoIter<Cow<'a, str>, JsonValueRef<'a>>, HashMap<String, JsonElement>, String),
+		}
+
+		let mut stack = Vec::new();
+		let mut next = self;
+		loop {
+			// JsonValueRef implements Drop, so contents are taken out through a mutable borrow.
+			let mut value = match &mut next {
+				JsonArray(v) => {
+					let v = std::mem::take(v);
+					let len = v.len();
+					stack.push(Frame::Array(v.into_iter(), Vec::with_capacity(len)));
+					None
+				},
+				JsonObject(obj) => {
+					let obj = std::mem::take(obj);
+					let len = obj.len();
+					stack.push(Frame::Object(obj.into_iter(), HashMap::with_capacity(len), String::new()));
+					None
+				},
+				JsonNumber(n) => Some(JsonElement::JsonNumber(std::mem::replace(n, JsonNum::Integer(0)))),
+				JsonString(s) => Some(JsonElement::JsonString(std::mem::take(s).into_owned())),
+				JsonNull => Some(JsonElement::JsonNull),
+				JsonBool(b) => Some(JsonElement::JsonBool(*b)),
+			};
+
+			// Hand the finished value to its parent until some parent has a child left to convert.
+			loop {
+				match stack.last_mut() {
+					None => return value.unwrap(),
+					Some(Frame::Array(iter, out)) => {
+						out.extend(value.take());
+						if let Some(child) = iter.next() {
+							next = child;
+							break;
+						}
+					},
+					Some(Frame::Object(iter, out, key)) => {
+						if let Some(v) = value.take() {
+							out.insert(std::mem::take(key), v);
+						}
+						if let Some((k, child)) = iter.next() {
+							*key = k.into_owned();
+							next = child;
+							break;
+						}
+					},
+				}
+				value = match stack.pop() {
+					Some(Frame::Array(_, out)) => Some(JsonElement::JsonArray(out)),
+					Some(Frame::Object(_, out, _)) => Some(JsonElement::JsonObject(out)),
+					None => None,
+				};
+			}
+		}
+	}
+}
+
+impl<'a> From<JsonValueRef<'a>> for JsonElement {
+	fn from(value: JsonValueRef<'a>) -> JsonElement {
+		value.into_owned()
+	}
+}
+
+// Drops deep trees without recursing, as `JsonElement` does.
+impl<'a> Drop for JsonValueRef<'a> {
+	fn drop(&mut self) {
+		use JsonValueRef::*;
+
+		let is_nested = |c: &JsonValueRef| match c {
+			JsonArray(v) => !v.is_empty(),
+			JsonObject(obj) => !obj.is_empty(),
+			_ => false,
+		};
+		let mut stack: Vec<JsonValueRef> = match self {
+			JsonArray(v) if v.iter().any(is_nested) => std::mem::take(v),
+			JsonObject(obj) if obj.values().any(is_nested) => obj.drain().map(|(_, v)| v).collect(),
+			_ => return,
+		};
+		while let Some(mut value) = stack.pop() {
+			match &mut value {
+				JsonArray(v) => stack.append(v),
+				JsonObject(obj) => stack.extend(obj.drain().map(|(_, v)| v)),
+				_ => {},
+			}
+		}
+	}
+}
+
+struct ValueRefTree;
+
+impl<'a> ParseTree<'a> for ValueRefTree {
+	type Value = JsonValueRef<'a>;
+	type Array = Vec<JsonValueRef<'a>>;
+	type Object = HashMap<Cow<'a, str>, JsonValueRef<'a>>;
+
+	fn string(&mut self, s: Cow<'a, str>) -> JsonValueRef<'a> {
+		JsonValueRef::JsonString(s)
+	}
+
+	fn number(&mut self, n: JsonNum) -> JsonValueRef<'a> {
+		JsonValueRef::JsonNumber(n)
+	}
+
+	fn null(&mut self) -> JsonValueRef<'a> {
+		JsonValueRef::JsonNull
+	}
+
+	fn bool(&mut self, b: bool) -> JsonValueRef<'a> {
+		JsonValueRef::JsonBool(b)
+	}
+
+	fn array(&mut self) -> Vec<JsonValueRef<'a>> {
+		Vec::new()
+	}
+
+	fn push(&mut self, array: &mut Vec<JsonValueRef<'a>>, value: JsonValueRef<'a>) -> usize {
+		array.push(value);
+		array.len()
+	}
+
+	fn close_array(&mut self, array: Vec<JsonValueRef<'a>>) -> JsonValueRef<'a> {
+		JsonValueRef::JsonArray(array)
+	}
+
+	fn object(&mut self) -> HashMap<Cow<'a, str>, JsonValueRef<'a>> {
+		HashMap::new()
+	}
+
+	fn insert(&mut self, object: &mut HashMap<Cow<'a, str>, JsonValueRef<'a>>, key: Cow<'a, str>, value: JsonValueRef<'a>) -> usize {
+		object.insert(key, value);
+		object.len()
+	}
+
+	fn close_object(&mut self, object: HashMap<Cow<'a, str>, JsonValueRef<'a>>) -> JsonValueRef<'a> {
+		JsonValueRef::JsonObject(object)
+	}
+}
@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+
+use crate::infer::{InferSetting, Shape};
+use crate::JsonElement;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodegenLang {
+	Rust,
+	TypeScript,
+}
+
+// Generates type definitions that can hold every sample. Object shapes seen at the same
+// position are unified; keys missing from some samples or holding `null` become optional.
+pub fn generate_types(samples: &[JsonElement], root_name: &str, lang: CodegenLang) -> String {
+	let shape = Shape::from_samples(samples, InferSetting::default());
+	let reserved = match lang {
+		CodegenLang::Rust => RUST_TYPE_NAMES,
+		CodegenLang::TypeScript => TS_TYPE_NAMES,
+	};
+	let mut gen = Generator {
+		lang,
+		definitions: Vec::new(),
+		names: reserved.iter().map(|n| n.to_string()).collect(),
+	};
+
+	let root_name = pascal_case(root_name);
+	if kinds(&shape) == ["object"] && shape.nulls == 0 {
+		gen.define_struct(&shape, &root_name);
+	} else {
+		let idx = gen.reserve();
+		let name = gen.unique_name(&root_name);
+		let ty = gen.field_type(&shape, &format!("{}Item", root_name), false);
+		gen.definitions[idx] = match lang {
+			CodegenLang::Rust => format!("pub type {} = {};\n", name, ty),
+			CodegenLang::TypeScript => format!("export type {} = {};\n", name, ty),
+		};
+	}
+
+	let mut out = String::new();
+	if lang == CodegenLang::Rust {
+		out.push_str("use serde::{Deserialize, Serialize};\n\n");
+	}
+	out.push_str(&gen.definitions.join("\n"));
+	out
+}
+
+struct Generator {
+	lang: CodegenLang,
+	// Definitions are reserved before their fields are generated so that parents precede children.
+	definitions: Vec<String>,
+	names: HashSet<String>,
+}
+
+impl Generator {
+	fn reserve(&mut self) -> usize {
+		self.definitions.push(String::new());
+		self.definitions.len() - 1
+	}
+
+	fn unique_name(&mut self, base: &str) -> String {
+		let mut name = base.to_string();
+		let mut n = 2;
+		while self.names.contains(&name) {
+			name = format!("{}{}", base, n);
+			n += 1;
+		}
+		self.names.insert(name.clone());
+		name
+	}
+
+	fn unknown_type(&self) -> &'static str {
+		match self.lang {
+			CodegenLang::Rust => "serde_json::Value",
+			CodegenLang::TypeScript => "unknown",
+		}
+	}
+
+	fn field_type(&mut self, shape: &Shape, hint: &str, optional: bool) -> String {
+		let ty = self.value_type(shape, hint);
+		if shape.nulls == 0 && !optional {
+			return ty;
+		}
+		match self.lang {
+			CodegenLang::Rust => format!("Option<{}>", ty),
+			CodegenLang::TypeScript if shape.nulls > 0 => format!("{} | null", ty),
+			CodegenLang::TypeScript => ty,
+		}
+	}
+
+	// The type of the non-null values at this position.
+	fn value_type(&mut self, shape: &Shape, hint: &str) -> String {
+		let kinds = kinds(shape);
+		match kinds.as_slice() {
+			[] => self.unknown_type().to_string(),
+			[kind] => self.kind_type(shape, kind, hint),
+			_ => match self.lang {
+				CodegenLang::Rust => self.define_enum(shape, &kinds, hint),
+				CodegenLang::TypeScript => {
+					let variants: Vec<String> = kinds.iter().map(|k| self.kind_type(shape, k, hint)).collect();
+					variants.join(" | ")
+				},
+			},
+		}
+	}
+
+	fn kind_type(&mut self, shape: &Shape, kind: &str, hint: &str) -> String {
+		use CodegenLang::*;
+
+		match (self.lang, kind) {
+			(Rust, "boolean") => "bool".to_string(),
+			(Rust, "integer") => "i64".to_string(),
+			(Rust, "number") => "f64".to_string(),
+			(Rust, "string") => "String".to_string(),
+			(TypeScript, "boolean") => "boolean".to_string(),
+			(TypeScript, "integer") | (TypeScript, "number") => "number".to_string(),
+			(TypeScript, "string") => "string".to_string(),
+			(_, "array") => {
+				let item = match &shape.items {
+					Some(items) => self.field_type(items, &singular(hint), false),
+					None => self.unknown_type().to_string(),
+				};
+				match self.lang {
+					Rust => format!("Vec<{}>", item),
+					TypeScript if item.contains(' ') => format!("({})[]", item),
+					TypeScript => format!("{}[]", item),
+				}
+			},
+			(_, "object") => self.define_struct(shape, hint),
+			_ => unreachable!(),
+		}
+	}
+
+	fn define_struct(&mut self, shape: &Shape, hint: &str) -> String {
+		let idx = self.reserve();
+		let name = self.unique_name(hint);
+
+		let mut body = String::new();
+		let mut fields = HashSet::new();
+		for (key, (seen, field_shape)) in shape.properties.iter() {
+			let optional = *seen < shape.objects;
+			let ty = self.field_type(field_shape, &pascal_case(key), optional);
+			match self.lang {
+				CodegenLang::Rust => {
+					// Keys like "fooBar" and "foo_bar" map to the same field name
+					let base = rust_field_name(key);
+					let mut field = base.clone();
+					let mut n = 2;
+					while !fields.insert(field.clone()) {
+						field = format!("{}_{}", base.trim_start_matches("r#"), n);
+						n += 1;
+					}
+					if field.trim_start_matches("r#") != key {
+						body.push_str(&format!("    #[serde(rename = {})]\n", JsonElement::JsonString(key.clone())));
+					}
+					body.push_str(&format!("    pub {}: {},\n", field, ty));
+				},
+				CodegenLang::TypeScript => {
+					let field = if is_ts_identifier(key) { key.clone() } else { JsonElement::JsonString(key.clone()).to_string() };
+					body.push_str(&format!("    {}{}: {};\n", field, if optional { "?" } else { "" }, ty));
+				},
+			}
+		}
+
+		self.definitions[idx] = match self.lang {
+			CodegenLang::Rust => format!("#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]\npub struct {} {{\n{}}}\n", name, body),
+			CodegenLang::TypeScript => format!("export interface {} {{\n{}}}\n", name, body),
+		};
+		name
+	}
+
+	fn define_enum(&mut self, shape: &Shape, kinds: &[&str], hint: &str) -> String {
+		let idx = self.reserve();
+		let name = self.unique_name(hint);
+
+		let mut body = String::new();
+		for kind in kinds {
+			let variant = pascal_case(kind);
+			let ty = self.kind_type(shape, kind, &format!("{}{}", name, variant));
+			body.push_str(&format!("    {}({}),\n", variant, ty));
+		}
+
+		self.definitions[idx] = format!("#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]\n#[serde(untagged)]\npub enum {} {{\n{}}}\n", name, body);
+		name
+	}
+}
+
+fn kinds(shape: &Shape) -> Vec<&'static str> {
+	let mut kinds = Vec::new();
+	if shape.bools > 0 {
+		kinds.push("boolean");
+	}
+	if shape.doubles > 0 {
+		kinds.push("number");
+	} else if shape.integers > 0 {
+		kinds.push("integer");
+	}
+	if shape.strings > 0 {
+		kinds.push("string");
+	}
+	if shape.arrays > 0 {
+		kinds.push("array");
+	}
+	if shape.objects > 0 {
+		kinds.push("object");
+	}
+	kinds
+}
+
+fn words(s: &str) -> Vec<String> {
+	let mut words = Vec::new();
+	let mut current = String::new();
+	let mut prev_lower = false;
+	for ch in s.chars() {
+		if !ch.is_alphanumeric() {
+			if !current.is_empty() {
+				words.push(current.clone());
+				current.clear();
+			}
+			prev_lower = false;
+			continue;
+		}
+		if ch.is_uppercase() && prev_lower && !current.is_empty() {
+			words.push(current.clone());
+			current.clear();
+		}
+		prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+		current.push(ch);
+	}
+	if !current.is_empty() {
+		words.push(current);
+	}
+	words
+}
+
+fn pascal_case(s: &str) -> String {
+	let mut out = String::new();
+	for word in words(s) {
+		let mut chars = word.chars();
+		if let Some(first) = chars.next() {
+			out.extend(first.to_uppercase());
+			out.push_str(&chars.as_str().to_lowercase());
+		}
+	}
+	if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+		out.insert(0, 'T');
+	}
+	out
+}
+
+fn singular(name: &str) -> String {
+	if let Some(stem) = name.strip_suffix("ies") {
+		format!("{}y", stem)
+	} else if name.len() > 1 && name.ends_with('s') && !name.ends_with("ss") {
+		name[..name.len() - 1].to_string()
+	} else if name.ends_with("Item") {
+		name.to_string()
+	} else {
+		format!("{}Item", name)
+	}
+}
+
+// Names generated types must not shadow: the prelude and serde imports the output relies on,
+// and the built-in types of TypeScript.
+const RUST_TYPE_NAMES: &[&str] = &[
+	"Self", "String", "Vec", "Option", "Some", "None", "Result", "Ok", "Err", "Box", "ToString", "ToOwned",
+	"Clone", "Copy", "Debug", "Default", "Eq", "PartialEq", "Ord", "PartialOrd", "Hash", "Send", "Sync", "Sized",
+	"Drop", "Fn", "FnMut", "FnOnce", "From", "Into", "Iterator", "IntoIterator", "Extend", "AsRef", "AsMut",
+	"Serialize", "Deserialize",
+];
+
+const TS_TYPE_NAMES: &[&str] = &[
+	"Object", "Array", "String", "Number", "Boolean", "Symbol", "BigInt", "Function", "Date", "RegExp", "Error",
+	"Map", "Set", "Promise", "Record", "Partial", "Required", "Readonly", "Pick", "Omit",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+	"as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for", "if",
+	"impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait",
+	"true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro", "override",
+	"priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+fn rust_field_name(key: &str) -> String {
+	let mut name = words(key).iter().map(|w| w.to_lowercase()).collect::<Vec<_>>().join("_");
+	// Keys without letters or digits, like "" and "_", would give `_`, which can't name a field
+	if name.is_empty() {
+		name.push_str("field");
+	} else if name.starts_with(|c: char| c.is_ascii_digit()) {
+		name.insert(0, '_');
+	}
+	if ["self", "super", "crate"].contains(&name.as_str()) {
+		name.push('_');
+	} else if RUST_KEYWORDS.contains(&name.as_str()) {
+		name.insert_str(0, "r#");
+	}
+	name
+}
+
+fn is_ts_identifier(key: &str) -> bool {
+	let mut chars = key.chars();
+	match chars.next() {
+		Some(c) if c.is_alphabetic() || c == '_' || c == '$' => chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$'),
+		_ => false,
+	}
+}
@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+use minjson::{JsonElement, JsonNum, ParseLimits};
+use regex::Regex;
+
+use crate::CliError;
+
+const FILE_NAMES: [&str; 2] = [".minjson.toml", "minjson.json"];
+
+// Defaults for command-line options, from the nearest `.minjson.toml` or `minjson.json` in the
+// working directory or one of its parents. Both files have the same layout:
+//
+//     [parse]
+//     json5 = true
+//     max_depth = 64
+//
+//     [pretty]
+//     indent = 4
+//
+//     [diff]
+//     threshold = 1e-3
+//     ignore = ["::meta::generated_at"]
+//
+//     [[overrides]]
+//     files = ["legacy/**/*.json"]
+//     pretty = { indent = 2 }
+#[derive(Debug, Default)]
+pub struct Config {
+	// Override globs containing '/' are matched against paths relative to this directory
+	dir: PathBuf,
+	base: Section,
+	overrides: Vec<(Vec<Glob>, Section)>,
+}
+
+#[derive(Debug)]
+pub struct Glob {
+	regex: Regex,
+	// Like .gitignore, a pattern without '/' matches the file name in any directory
+	by_path: bool,
+}
+
+// The options one file or override sets. Anything left as None falls through to the next layer.
+#[derive(Debug, Default, Clone)]
+pub struct Section {
+	pub json5: Option<bool>,
+	pub jsonc: Option<bool>,
+	pub arbitrary_precision: Option<bool>,
+	pub limits: ParseLimits,
+	pub indent: Option<u32>,
+	pub threshold: Option<f64>,
+	pub ignore: Option<Vec<String>>,
+}
+
+impl Config {
+	// Loads `path`, or looks for a config file if it is None. No config file means no defaults.
+	pub fn load(path: Option<&str>) -> Result<Config, CliError> {
+		let path = match path {
+			Some(path) => PathBuf::from(path),
+			None => match std::env::current_dir().ok().and_then(|cwd| Config::discover(&cwd)) {
+				Some(path) => path,
+				None => return Ok(Config::default()),
+			},
+		};
+		let text = std::fs::read_to_string(&path).map_err(|e| CliError::Io(format!("{}: {}", path.display(), e)))?;
+		let dir = path.canonicalize().ok().and_then(|p| p.parent().map(Path::to_path_buf)).unwrap_or_default();
+		Config::parse(&text, &path, dir).map_err(|e| CliError::Invalid(format!("{}: {}", path.display(), e)))
+	}
+
+	// The config file in `dir` or the nearest of its parents that has one.
+	fn discover(dir: &Path) -> Option<PathBuf> {
+		dir.ancestors()
+			.flat_map(|dir| FILE_NAMES.iter().map(move |name| dir.join(name)))
+			.find(|path| path.is_file())
+	}
+
+	fn parse(text: &str, path: &Path, dir: PathBuf) -> Result<Config, String> {
+		let root = if path.extension() == Some("toml".as_ref()) {
+			let value = text.parse::<toml::Value>().map_err(|e| e.to_string())?;
+			toml_to_element(value)
+		} else {
+			minjson::build_json_graph(text)?
+		};
+		let root = match &root {
+			JsonElement::JsonObject(obj) => obj,
+			_ => return Err(String::from("expected a table of settings")),
+		};
+
+		let mut config = Config {
+			dir,
+			base: parse_section(root, "", &["overrides"])?,
+			overrides: Vec::new(),
+		};
+		let overrides = match root.get("overrides") {
+			None => &[][..],
+			Some(JsonElement::JsonArray(v)) => &v[..],
+			Some(_) => return Err(String::from("'overrides' must be an array of tables")),
+		};
+		for (i, o) in overrides.iter().enumerate() {
+			let context = format!("overrides[{}].", i);
+			let o = match o {
+				JsonElement::JsonObject(obj) => obj,
+				_ => return Err(format!("'{}' must be a table", &context[..context.len() - 1])),
+			};
+			let globs = match o.get("files").map(strings) {
+				Some(Some(globs)) => globs.iter().map(|g| Glob::new(g)).collect::<Result<Vec<_>, _>>()?,
+				_ => return Err(format!("'{}files' must be an array of glob patterns", context)),
+			};
+			config.overrides.push((globs, parse_section(o, &context, &["files"])?));
+		}
+		Ok(config)
+	}
+
+	// The options for an input: those of the config file, then of each override matching
+	// `path` in order. Standard input only gets the former.
+	pub fn section_for(&self, path: Option<&str>) -> Section {
+		let mut section = self.base.clone();
+		let path = match path {
+			Some(path) => Path::new(path),
+			None => return section,
+		};
+		let relative = path.canonicalize().ok()
+			.and_then(|p| p.strip_prefix(&self.dir).ok().map(Path::to_path_buf))
+			.unwrap_or_else(|| path.to_path_buf());
+		let relative = relative.to_string_lossy().replace('\\', "/");
+		let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+		for (globs, o) in &self.overrides {
+			if globs.iter().any(|g| g.regex.is_match(if g.by_path { &relative } else { &name })) {
+				section.merge(o);
+			}
+		}
+		section
+	}
+}
+
+impl Section {
+	fn merge(&mut self, other: &Section) {
+		fn set<T: Clone>(to: &mut Option<T>, from: &Option<T>) {
+			if from.is_some() {
+				*to = from.clone();
+			}
+		}
+
+		set(&mut self.json5, &other.json5);
+		set(&mut self.jsonc, &other.jsonc);
+		set(&mut self.arbitrary_precision, &other.arbitrary_precision);
+		set(&mut self.limits.max_depth, &other.limits.max_depth);
+		set(&mut self.limits.max_string_length, &other.limits.max_string_length);
+		set(&mut self.limits.max_members, &other.limits.max_members);
+		set(&mut self.limits.max_input_size, &other.limits.max_input_size);
+		set(&mut self.indent, &other.indent);
+		set(&mut self.threshold, &other.threshold);
+		set(&mut self.ignore, &other.ignore);
+	}
+}
+
+// Reads the [parse], [pretty] and [diff] tables. `context` prefixes key names in errors, and
+// `skip` lists other keys the caller handles.
+fn parse_section(root: &HashMap<String, JsonElement>, context: &str, skip: &[&str]) -> Result<Section, String> {
+	let mut section = Section::default();
+	for (group, members) in root {
+		let members = match (group.as_str(), members) {
+			(g, _) if skip.contains(&g) => continue,
+			("parse", JsonElement::JsonObject(m)) | ("pretty", JsonElement::JsonObject(m)) | ("diff", JsonElement::JsonObject(m)) => m,
+			("parse", _) | ("pretty", _) | ("diff", _) => return Err(format!("'{}{}' must be a table", context, group)),
+			_ => return Err(format!("unknown key '{}{}'", context, group)),
+		};
+		for (key, value) in members {
+			let name = format!("{}.{}", group, key);
+			let invalid = |expected: &str| format!("'{}{}' must be {}", context, name, expected);
+			let count = || match value {
+				JsonElement::JsonNumber(JsonNum::Integer(n)) if *n >= 0 => Ok(*n as usize),
+				_ => Err(invalid("a non-negative integer")),
+			};
+			let flag = || match value {
+				JsonElement::JsonBool(b) => Ok(*b),
+				_ => Err(invalid("true or false")),
+			};
+			match name.as_str() {
+				"parse.json5" => section.json5 = Some(flag()?),
+				"parse.jsonc" => section.jsonc = Some(flag()?),
+				"parse.arbitrary_precision" => section.arbitrary_precision = Some(flag()?),
+				"parse.max_depth" => section.limits.max_depth = Some(count()?),
+				"parse.max_string_length" => section.limits.max_string_length = Some(count()?),
+				"parse.max_members" => section.limits.max_members = Some(count()?),
+				"parse.max_input_size" => section.limits.max_input_size = Some(count()?),
+				"pretty.indent" => section.indent = Some(count().and_then(|n| u32::try_from(n).map_err(|_| invalid("a smaller number")))?),
+				"diff.threshold" => section.threshold = match value {
+					JsonElement::JsonNumber(n) => Some(n.as_f64()),
+					_ => return Err(invalid("a number")),
+				},
+				"diff.ignore" => section.ignore = Some(strings(value).ok_or_else(|| invalid("an array of paths"))?),
+				_ => return Err(format!("unknown key '{}{}'", context, name)),
+			}
+		}
+	}
+	Ok(section)
+}
+
+fn strings(value: &JsonElement) -> Option<Vec<String>> {
+	match value {
+		JsonElement::JsonArray(v) => v.iter().map(|s| match s {
+			JsonElement::JsonString(s) => Some(s.clone()),
+			_ => None,
+		}).collect(),
+		_ => None,
+	}
+}
+
+impl Glob {
+	// '*' and '?' match within a path segment, and '**' across segments.
+	pub fn new(glob: &str) -> Result<Glob, String> {
+		let mut pattern = String::from("^");
+		let mut rest = glob;
+		while let Some(ch) = rest.chars().next() {
+			let (piece, len) = if rest.starts_with("**/") {
+				("(?:.*/)?", 3)
+			} else if rest.starts_with("**") {
+				(".*", 2)
+			} else if ch == '*' {
+				("[^/]*", 1)
+			} else if ch == '?' {
+				("[^/]", 1)
+			} else {
+				pattern.push_str(&regex::escape(&rest[..ch.len_utf8()]));
+				rest = &rest[ch.len_utf8()..];
+				continue;
+			};
+			pattern.push_str(piece);
+			rest = &rest[len..];
+		}
+		pattern.push('$');
+		let regex = Regex::new(&pattern).map_err(|e| format!("invalid glob '{}': {}", glob, e))?;
+		Ok(Glob { regex, by_path: glob.contains('/') })
+	}
+
+	// Matches the whole of `path`, as a shell would, whether or not the pattern contains '/'.
+	pub fn matches_path(&self, path: &str) -> bool {
+		self.regex.is_match(path)
+	}
+}
+
+fn toml_to_element(value: toml::Value) -> JsonElement {
+	match value {
+		toml::Value::String(s) => JsonElement::JsonString(s),
+		toml::Value::Integer(i) => JsonElement::JsonNumber(JsonNum::Integer(i)),
+		toml::Value::Float(f) => JsonElement::JsonNumber(JsonNum::Double(f)),
+		toml::Value::Boolean(b) => JsonElement::JsonBool(b),
+		toml::Value::Datetime(d) => JsonElement::JsonString(d.to_string()),
+		toml::Value::Array(v) => JsonElement::JsonArray(v.into_iter().map(toml_to_element).collect()),
+		toml::Value::Table(t) => JsonElement::JsonObject(t.into_iter().map(|(k, v)| (k, toml_to_element(v))).collect()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A fresh directory per test, removed when dropped.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new(name: &str) -> TempDir {
+			let dir = std::env::temp_dir().join(format!("minjson-config-{}-{}", name, std::process::id()));
+			let _ = std::fs::remove_dir_all(&dir);
+			std::fs::create_dir_all(&dir).unwrap();
+			TempDir(dir.canonicalize().unwrap())
+		}
+
+		fn write(&self, path: &str, text: &str) -> PathBuf {
+			let path = self.0.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(&path, text).unwrap();
+			path
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn matches(glob: &str, path: &str) -> bool {
+		Glob::new(glob).unwrap().matches_path(path)
+	}
+
+	fn config(toml: &str, dir: &Path) -> Config {
+		Config::parse(toml, Path::new(".minjson.toml"), dir.to_path_buf()).unwrap()
+	}
+
+	fn parse_error(text: &str) -> String {
+		Config::parse(text, Path::new(".minjson.toml"), PathBuf::new()).unwrap_err()
+	}
+
+	#[test]
+	fn glob_segments() {
+		assert!(matches("*.json", "a.json"));
+		assert!(!matches("*.json", "dir/a.json"));
+		assert!(!matches("*.json", "a.json5"));
+		assert!(matches("a?.json", "ab.json"));
+		assert!(!matches("a?.json", "a/.json"));
+		assert!(matches("data/*.json", "data/x.json"));
+		assert!(!matches("data/*.json", "data/sub/x.json"));
+		// Everything else is literal, including regex syntax
+		assert!(matches("a+(b).json", "a+(b).json"));
+		assert!(!matches("a+(b).json", "aa(b).json"));
+		assert!(!matches("a.json", "abjson"));
+		assert!(matches("caf\u{e9}/*.json", "caf\u{e9}/x.json"));
+	}
+
+	#[test]
+	fn glob_double_star() {
+		// `**/` matches any number of directories, including none
+		assert!(matches("**/*.json", "a.json"));
+		assert!(matches("**/*.json", "a/b/c.json"));
+		assert!(matches("legacy/**/*.json", "legacy/a.json"));
+		assert!(matches("legacy/**/*.json", "legacy/x/y/a.json"));
+		assert!(!matches("legacy/**/*.json", "other/legacy/a.json"));
+		assert!(!matches("legacy/**/*.json", "legacyx/a.json"));
+		// Elsewhere `**` matches anything, '/' included
+		assert!(matches("legacy/**", "legacy/x/y/a.json"));
+		assert!(matches("a**z", "a/b/z"));
+	}
+
+	#[test]
+	fn override_order() {
+		let dir = TempDir::new("order");
+		let config = config(r#"
+			[parse]
+			max_depth = 10
+			[pretty]
+			indent = 4
+
+			[[overrides]]
+			files = ["*.json"]
+			pretty = { indent = 2 }
+			parse = { json5 = false }
+
+			[[overrides]]
+			files = ["legacy/**/*.json"]
+			pretty = { indent = 8 }
+
+			[[overrides]]
+			files = ["legacy/keep/*.json"]
+			parse = { json5 = true }
+		"#, &dir.0);
+
+		let section = config.section_for(None);
+		assert_eq!((section.indent, section.json5, section.limits.max_depth), (Some(4), None, Some(10)));
+
+		// Later overrides win, and keys they don't set fall through to earlier ones
+		let path = dir.write("legacy/keep/a.json", "{}");
+		let section = config.section_for(Some(path.to_str().unwrap()));
+		assert_eq!((section.indent, section.json5, section.limits.max_depth), (Some(8), Some(true), Some(10)));
+
+		let path = dir.write("legacy/other/a.json", "{}");
+		let section = config.section_for(Some(path.to_str().unwrap()));
+		assert_eq!((section.indent, section.json5), (Some(8), Some(false)));
+
+		let path = dir.write("a.txt", "{}");
+		let section = config.section_for(Some(path.to_str().unwrap()));
+		assert_eq!((section.indent, section.json5), (Some(4), None));
+	}
+
+	// Patterns with '/' match the path from the config file's directory, others only the name.
+	#[test]
+	fn override_paths() {
+		let dir = TempDir::new("paths");
+		let config = config(r#"
+			[[overrides]]
+			files = ["data/*.json"]
+			pretty = { indent = 1 }
+
+			[[overrides]]
+			files = ["special.json"]
+			pretty = { indent = 3 }
+		"#, &dir.0);
+		let indent = |path: &Path| config.section_for(Some(path.to_str().unwrap())).indent;
+
+		assert_eq!(indent(&dir.write("data/a.json", "")), Some(1));
+		assert_eq!(indent(&dir.write("nested/data/a.json", "")), None);
+		assert_eq!(indent(&dir.write("special.json", "")), Some(3));
+		assert_eq!(indent(&dir.write("deep/er/special.json", "")), Some(3));
+		assert_eq!(indent(&dir.write("data/special.json", "")), Some(3));
+		// Paths are resolved before matching
+		assert_eq!(indent(&dir.0.join("nested/../data/a.json")), Some(1));
+	}
+
+	#[test]
+	fn discovery() {
+		let dir = TempDir::new("discovery");
+		let nested = dir.0.join("a/b/c");
+		std::fs::create_dir_all(&nested).unwrap();
+		assert!(Config::discover(&nested).is_none_or(|path| !path.starts_with(&dir.0)));
+
+		let json = dir.write("a/minjson.json", "{}");
+		assert_eq!(Config::discover(&nested), Some(json.clone()));
+		// The nearest directory wins, and in it `.minjson.toml` over `minjson.json`
+		let toml = dir.write("a/b/.minjson.toml", "");
+		assert_eq!(Config::discover(&nested), Some(toml.clone()));
+		dir.write("a/b/minjson.json", "{}");
+		assert_eq!(Config::discover(&nested), Some(toml));
+		assert_eq!(Config::discover(&dir.0.join("a")), Some(json));
+	}
+
+	#[test]
+	fn load() {
+		let dir = TempDir::new("load");
+		let path = dir.write("sub/minjson.json", r#"{"pretty": {"indent": 3}, "overrides": [{"files": ["x/*.json"], "parse": {"jsonc": true}}]}"#);
+		let config = Config::load(path.to_str()).unwrap_or_else(|_| panic!("failed to load"));
+		assert_eq!(config.section_for(None).indent, Some(3));
+		let file = dir.write("sub/x/a.json", "{}");
+		assert_eq!(config.section_for(file.to_str()).jsonc, Some(true));
+
+		let bad = dir.write("bad.toml", "[pretty]\nindent = -1\n");
+		match Config::load(bad.to_str()) {
+			Err(CliError::Invalid(e)) => assert_eq!(e, format!("{}: 'pretty.indent' must be a non-negative integer", bad.display())),
+			_ => panic!("expected an invalid config"),
+		}
+		match Config::load(dir.0.join("missing.toml").to_str()) {
+			Err(CliError::Io(e)) => assert!(e.contains("missing.toml"), "{}", e),
+			_ => panic!("expected an I/O error"),
+		}
+	}
+
+	#[test]
+	fn errors() {
+		assert_eq!(parse_error("[parse]\njson5 = 1"), "'parse.json5' must be true or false");
+		assert_eq!(parse_error("[parse]\nmax_depth = -1"), "'parse.max_depth' must be a non-negative integer");
+		assert_eq!(parse_error("[pretty]\nindent = 5000000000"), "'pretty.indent' must be a smaller number");
+		assert_eq!(parse_error("[diff]\nthreshold = \"x\""), "'diff.threshold' must be a number");
+		assert_eq!(parse_error("[diff]\nignore = [1]"), "'diff.ignore' must be an array of paths");
+		assert_eq!(parse_error("parse = 1"), "'parse' must be a table");
+		assert_eq!(parse_error("[format]"), "unknown key 'format'");
+		assert_eq!(parse_error("[parse]\nindent = 2"), "unknown key 'parse.indent'");
+		assert_eq!(parse_error("overrides = 1"), "'overrides' must be an array of tables");
+		assert_eq!(parse_error("overrides = [1]"), "'overrides[0]' must be a table");
+		assert_eq!(parse_error("[[overrides]]\npretty = { indent = 2 }"), "'overrides[0].files' must be an array of glob patterns");
+		assert_eq!(parse_error("[[overrides]]\nfiles = [\"*\"]\nother = 1"), "unknown key 'overrides[0].other'");
+		assert_eq!(parse_error("[[overrides]]\nfiles = [\"*\"]\npretty = { indent = \"2\" }"), "'overrides[0].pretty.indent' must be a non-negative integer");
+		assert!(parse_error("[parse").contains("expected"));
+		let json_error = Config::parse("[1]", Path::new("minjson.json"), PathBuf::new()).unwrap_err();
+		assert_eq!(json_error, "expected a table of settings");
+	}
+}
@@ -0,0 +1,126 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::iter::FromIterator;
+
+use crate::{JsonElement, JsonNum};
+
+// Integers that do not fit in i64 are kept exactly as decimal text.
+pub(crate) fn wide_integer<T: TryInto<i64> + ToString + Copy>(n: T) -> JsonNum {
+	match n.try_into() {
+		Ok(i) => JsonNum::Integer(i),
+		Err(_) => JsonNum::Decimal(n.to_string()),
+	}
+}
+
+macro_rules! from_integer {
+	($($ty:ty),*) => {
+		$(
+			impl From<$ty> for JsonElement {
+				fn from(n: $ty) -> JsonElement {
+					JsonElement::JsonNumber(wide_integer(n))
+				}
+			}
+		)*
+	};
+}
+
+from_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl From<f32> for JsonElement {
+	fn from(n: f32) -> JsonElement {
+		JsonElement::JsonNumber(JsonNum::Double(n as f64))
+	}
+}
+
+impl From<f64> for JsonElement {
+	fn from(n: f64) -> JsonElement {
+		JsonElement::JsonNumber(JsonNum::Double(n))
+	}
+}
+
+impl From<JsonNum> for JsonElement {
+	fn from(n: JsonNum) -> JsonElement {
+		JsonElement::JsonNumber(n)
+	}
+}
+
+impl From<bool> for JsonElement {
+	fn from(b: bool) -> JsonElement {
+		JsonElement::JsonBool(b)
+	}
+}
+
+impl From<&str> for JsonElement {
+	fn from(s: &str) -> JsonElement {
+		JsonElement::JsonString(s.to_string())
+	}
+}
+
+impl From<String> for JsonElement {
+	fn from(s: String) -> JsonElement {
+		JsonElement::JsonString(s)
+	}
+}
+
+impl From<&String> for JsonElement {
+	fn from(s: &String) -> JsonElement {
+		JsonElement::JsonString(s.clone())
+	}
+}
+
+impl From<char> for JsonElement {
+	fn from(c: char) -> JsonElement {
+		JsonElement::JsonString(c.to_string())
+	}
+}
+
+impl From<()> for JsonElement {
+	fn from(_: ()) -> JsonElement {
+		JsonElement::JsonNull
+	}
+}
+
+impl<T: Into<JsonElement>> From<Option<T>> for JsonElement {
+	fn from(v: Option<T>) -> JsonElement {
+		match v {
+			Some(v) => v.into(),
+			None => JsonElement::JsonNull,
+		}
+	}
+}
+
+impl<T: Into<JsonElement>> From<Vec<T>> for JsonElement {
+	fn from(v: Vec<T>) -> JsonElement {
+		JsonElement::JsonArray(v.into_iter().map(Into::into).collect())
+	}
+}
+
+impl<T: Clone + Into<JsonElement>> From<&[T]> for JsonElement {
+	fn from(v: &[T]) -> JsonElement {
+		JsonElement::JsonArray(v.iter().cloned().map(Into::into).collect())
+	}
+}
+
+impl<K: Into<String>, V: Into<JsonElement>> From<HashMap<K, V>> for JsonElement {
+	fn from(m: HashMap<K, V>) -> JsonElement {
+		JsonElement::JsonObject(m.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+	}
+}
+
+impl<K: Into<String>, V: Into<JsonElement>> From<BTreeMap<K, V>> for JsonElement {
+	fn from(m: BTreeMap<K, V>) -> JsonElement {
+		JsonElement::JsonObject(m.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+	}
+}
+
+impl<T: Into<JsonElement>> FromIterator<T> for JsonElement {
+	fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> JsonElement {
+		JsonElement::JsonArray(iter.into_iter().map(Into::into).collect())
+	}
+}
+
+impl<K: Into<String>, V: Into<JsonElement>> FromIterator<(K, V)> for JsonElement {
+	fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> JsonElement {
+		JsonElement::JsonObject(iter.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+	}
+}
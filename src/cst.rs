@@ -0,0 +1,457 @@
+use crate::{build_json_graph, parse_document, ElementTree, JsonElement, JsonFormatter, ParseSetting, PrettySetting};
+
+// A lossless concrete syntax tree of a JSON (or JSONC) document. Whitespace, comments, key order
+// and the original spelling of every scalar are kept, so `to_string()` reproduces the input byte
+// for byte. Edits only rewrite the touched values; everything else is emitted unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonDocument {
+	leading: String,
+	root: CstValue,
+	trailing: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CstValue {
+	// The source text of a string, number, bool or null
+	Scalar(String),
+	Array(CstContainer),
+	Object(CstContainer),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CstContainer {
+	entries: Vec<CstEntry>,
+	// Trivia between the last entry (or its trailing comma) and the closing bracket
+	trailing: String,
+}
+
+// `leading` key `after_key` : `after_colon` value `after_value` ,
+#[derive(Debug, Clone, PartialEq)]
+struct CstEntry {
+	leading: String,
+	key: Option<CstKey>,
+	value: CstValue,
+	after_value: String,
+	comma: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CstKey {
+	raw: String,
+	name: String,
+	after_key: String,
+	after_colon: String,
+}
+
+impl JsonDocument {
+	pub fn parse(text: &str) -> Result<JsonDocument, String> {
+		let mut parser = CstParser { text, pos: 0 };
+		let leading = parser.trivia()?;
+		let root = parser.value()?;
+		let trailing = parser.trivia()?;
+		if parser.pos < text.len() {
+			return Err(format!("Unexpected trailing characters at byte {}", parser.pos));
+		}
+		Ok(JsonDocument { leading, root, trailing })
+	}
+
+	pub fn to_element(&self) -> Result<JsonElement, String> {
+		self.root.to_element()
+	}
+
+	// Sets the value at a JSON Pointer. A missing last key is inserted; "-" or the length
+	// of an array appends to it.
+	pub fn set(&mut self, pointer: &str, value: &JsonElement) -> Result<(), String> {
+		let indent_unit = self.indent_unit();
+		if pointer.is_empty() {
+			self.root = render(value, Some(""), &indent_unit)?;
+			return Ok(());
+		}
+
+		let (parent, token) = split_pointer(pointer)?;
+		match self.lookup_mut(parent)? {
+			CstValue::Object(obj) => {
+				match obj.entries.iter_mut().find(|e| e.key.as_ref().map(|k| k.name.as_str()) == Some(token.as_str())) {
+					Some(entry) => {
+						entry.value = render(value, entry.indentation().as_deref(), &indent_unit)?;
+						Ok(())
+					},
+					None => obj.push(Some(&token), value, &indent_unit),
+				}
+			},
+			CstValue::Array(arr) => {
+				if token == "-" || token == arr.entries.len().to_string() {
+					return arr.push(None, value, &indent_unit);
+				}
+				let entry = token.parse::<usize>().ok().and_then(|i| arr.entries.get_mut(i)).ok_or_else(|| format!("Index out of range: {}", pointer))?;
+				entry.value = render(value, entry.indentation().as_deref(), &indent_unit)?;
+				Ok(())
+			},
+			CstValue::Scalar(_) => Err(format!("Cannot set {}: parent is not a container", pointer)),
+		}
+	}
+
+	// Appends a new key to the object at `object_pointer`, copying the layout of its last member.
+	pub fn insert(&mut self, object_pointer: &str, key: &str, value: &JsonElement) -> Result<(), String> {
+		let indent_unit = self.indent_unit();
+		match self.lookup_mut(object_pointer)? {
+			CstValue::Object(obj) => {
+				if obj.entries.iter().any(|e| e.key.as_ref().map(|k| k.name.as_str()) == Some(key)) {
+					return Err(format!("Key already exists: {}", key));
+				}
+				obj.push(Some(key), value, &indent_unit)
+			},
+			_ => Err(format!("Not an object: {}", object_pointer)),
+		}
+	}
+
+	pub fn remove(&mut self, pointer: &str) -> Result<(), String> {
+		let (parent, token) = split_pointer(pointer)?;
+		let container = match self.lookup_mut(parent)? {
+			CstValue::Object(obj) => obj,
+			CstValue::Array(arr) => arr,
+			CstValue::Scalar(_) => return Err(format!("Path not found: {}", pointer)),
+		};
+		let idx = container.position(&token).ok_or_else(|| format!("Path not found: {}", pointer))?;
+		let removed = container.entries.remove(idx);
+
+		// A comment on the line of the previous entry belongs to that entry
+		let keep = match removed.leading.find('\n') {
+			Some(i) if !removed.leading[..i].trim().is_empty() => &removed.leading[..i],
+			_ => "",
+		};
+
+		if idx < container.entries.len() {
+			let next = &mut container.entries[idx];
+			if idx == 0 {
+				// The new first entry takes over the layout after the opening bracket
+				next.leading = format!("{}{}", trailing_whitespace(&removed.leading), next.leading.trim_start());
+			}
+			next.leading.insert_str(0, keep);
+		} else {
+			// Keep the layout before the closing bracket when the last entry goes away
+			match container.entries.last_mut() {
+				Some(prev) => {
+					prev.comma = removed.comma;
+					if prev.comma {
+						container.trailing.insert_str(0, keep);
+					} else {
+						prev.after_value = format!("{}{}{}", prev.after_value, keep, removed.after_value);
+					}
+				},
+				None => {
+					container.trailing = format!("{}{}{}", keep, trailing_whitespace(&removed.after_value), container.trailing);
+				},
+			}
+		}
+		Ok(())
+	}
+
+	fn lookup_mut(&mut self, pointer: &str) -> Result<&mut CstValue, String> {
+		let mut target = &mut self.root;
+		if pointer.is_empty() {
+			return Ok(target);
+		}
+		if !pointer.starts_with('/') {
+			return Err(format!("Invalid JSON Pointer: {}", pointer));
+		}
+		for token in pointer[1..].split('/') {
+			let token = token.replace("~1", "/").replace("~0", "~");
+			target = match target {
+				CstValue::Object(c) | CstValue::Array(c) => {
+					let idx = c.position(&token).ok_or_else(|| format!("Path not found: {}", pointer))?;
+					&mut c.entries[idx].value
+				},
+				CstValue::Scalar(_) => return Err(format!("Path not found: {}", pointer)),
+			};
+		}
+		Ok(target)
+	}
+
+	// The indentation step of the document, guessed from the first nested entry. It is kept as
+	// text, so that documents indented with tabs get tabs. Empty means everything is on one line.
+	fn indent_unit(&self) -> String {
+		if let CstValue::Object(c) | CstValue::Array(c) = &self.root {
+			if let Some(entry) = c.entries.first() {
+				return entry.indentation().unwrap_or_default();
+			}
+		}
+		"  ".to_string()
+	}
+}
+
+impl std::fmt::Display for JsonDocument {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "{}{}{}", self.leading, self.root, self.trailing)
+	}
+}
+
+impl std::fmt::Display for CstValue {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let (open, close, c) = match self {
+			CstValue::Scalar(s) => return write!(formatter, "{}", s),
+			CstValue::Array(c) => ('[', ']', c),
+			CstValue::Object(c) => ('{', '}', c),
+		};
+		write!(formatter, "{}", open)?;
+		for entry in &c.entries {
+			write!(formatter, "{}", entry.leading)?;
+			if let Some(key) = &entry.key {
+				write!(formatter, "{}{}:{}", key.raw, key.after_key, key.after_colon)?;
+			}
+			write!(formatter, "{}{}", entry.value, entry.after_value)?;
+			if entry.comma {
+				write!(formatter, ",")?;
+			}
+		}
+		write!(formatter, "{}{}", c.trailing, close)
+	}
+}
+
+impl CstValue {
+	fn to_element(&self) -> Result<JsonElement, String> {
+		match self {
+			CstValue::Scalar(s) => build_json_graph(s),
+			CstValue::Array(c) => c.entries.iter().map(|e| e.value.to_element()).collect::<Result<_, _>>().map(JsonElement::JsonArray),
+			CstValue::Object(c) => {
+				let mut obj = std::collections::HashMap::new();
+				for e in &c.entries {
+					if let Some(key) = &e.key {
+						obj.insert(key.name.clone(), e.value.to_element()?);
+					}
+				}
+				Ok(JsonElement::JsonObject(obj))
+			},
+		}
+	}
+}
+
+impl CstEntry {
+	fn indentation(&self) -> Option<String> {
+		if self.leading.contains('\n') {
+			Some(indentation(&self.leading))
+		} else {
+			None
+		}
+	}
+}
+
+impl CstContainer {
+	fn position(&self, token: &str) -> Option<usize> {
+		match self.entries.first().map(|e| e.key.is_some()) {
+			Some(true) => self.entries.iter().position(|e| e.key.as_ref().map(|k| k.name.as_str()) == Some(token)),
+			_ => token.parse::<usize>().ok().filter(|i| *i < self.entries.len()),
+		}
+	}
+
+	fn push(&mut self, key: Option<&str>, value: &JsonElement, indent_unit: &str) -> Result<(), String> {
+		let mut entry = CstEntry {
+			leading: String::new(),
+			key: key.map(|k| CstKey {
+				raw: JsonElement::JsonString(k.to_string()).to_string(),
+				name: k.to_string(),
+				after_key: String::new(),
+				after_colon: " ".to_string(),
+			}),
+			value: CstValue::Scalar(String::new()),
+			after_value: String::new(),
+			comma: false,
+		};
+
+		match self.entries.last_mut() {
+			Some(last) => {
+				let last_indent = indentation(&last.leading);
+				let separator = if last.leading.contains('\n') { format!("\n{}", last_indent) } else { last.leading.clone() };
+				if let (Some(new_key), Some(last_key)) = (entry.key.as_mut(), last.key.as_ref()) {
+					new_key.after_colon = last_key.after_colon.clone();
+				}
+				if last.comma {
+					// Trailing comma style
+					entry.comma = true;
+					entry.leading = separator;
+				} else {
+					// Whatever followed the last value (e.g. a trailing comment) stays with it
+					let tail = trailing_whitespace(&last.after_value).to_string();
+					let content = last.after_value[..last.after_value.len() - tail.len()].to_string();
+					last.comma = true;
+					last.after_value.clear();
+					entry.leading = format!("{}{}", content, separator);
+					entry.after_value = tail;
+				}
+				entry.value = render(value, last.indentation().as_deref(), indent_unit)?;
+			},
+			None => {
+				entry.value = render(value, None, indent_unit)?;
+			},
+		}
+		self.entries.push(entry);
+		Ok(())
+	}
+}
+
+fn split_pointer(pointer: &str) -> Result<(&str, String), String> {
+	match pointer.rfind('/') {
+		Some(i) => Ok((&pointer[..i], pointer[i + 1..].replace("~1", "/").replace("~0", "~"))),
+		None => Err(format!("Invalid JSON Pointer: {}", pointer)),
+	}
+}
+
+// The spaces and tabs after the last line break
+fn indentation(leading: &str) -> String {
+	match leading.rfind('\n') {
+		Some(i) => leading[i + 1..].chars().take_while(|c| *c == ' ' || *c == '\t').collect(),
+		None => String::new(),
+	}
+}
+
+fn trailing_whitespace(s: &str) -> &str {
+	&s[s.trim_end().len()..]
+}
+
+// Renders a new value so that it lines up with an entry indented by `indent`.
+// Entries that do not start on their own line get a value without line breaks.
+fn render(value: &JsonElement, indent: Option<&str>, indent_unit: &str) -> Result<CstValue, String> {
+	let text = match (value, indent) {
+		(JsonElement::JsonArray(_), Some(indent)) | (JsonElement::JsonObject(_), Some(indent)) if !indent_unit.is_empty() => {
+			// Pretty printed with one space per level, which is then swapped for the unit
+			let pretty: String = JsonFormatter::new_from_str(&value.to_string(), PrettySetting { indent_width: 1 }).collect();
+			let lines: Vec<String> = pretty.split('\n').map(|line| {
+				let token = line.trim_start_matches(' ');
+				format!("{}{}", indent_unit.repeat(line.len() - token.len()), token)
+			}).collect();
+			lines.join(&format!("\n{}", indent))
+		},
+		_ => value.to_string(),
+	};
+	CstParser { text: &text, pos: 0 }.value()
+}
+
+struct CstParser<'a> {
+	text: &'a str,
+	pos: usize,
+}
+
+impl CstParser<'_> {
+	fn peek(&self) -> Option<char> {
+		self.text[self.pos..].chars().next()
+	}
+
+	fn expect(&mut self, ch: char) -> Result<(), String> {
+		match self.peek() {
+			Some(c) if c == ch => {
+				self.pos += c.len_utf8();
+				Ok(())
+			},
+			Some(c) => Err(format!("Expected '{}', got {} at byte {}", ch, c, self.pos)),
+			None => Err(format!("Expected '{}', reached EOF", ch)),
+		}
+	}
+
+	// Whitespace and comments
+	fn trivia(&mut self) -> Result<String, String> {
+		let start = self.pos;
+		loop {
+			let rest = &self.text[self.pos..];
+			if rest.starts_with("//") {
+				self.pos += rest.find('\n').unwrap_or(rest.len());
+			} else if let Some(body) = rest.strip_prefix("/*") {
+				match body.find("*/") {
+					Some(end) => self.pos += end + 4,
+					None => return Err("Reached EOF while parsing comment".to_string()),
+				}
+			} else {
+				match self.peek() {
+					Some(c) if c.is_whitespace() => self.pos += c.len_utf8(),
+					_ => break,
+				}
+			}
+		}
+		Ok(self.text[start..self.pos].to_string())
+	}
+
+	fn value(&mut self) -> Result<CstValue, String> {
+		match self.peek() {
+			Some('{') => self.container(true).map(CstValue::Object),
+			Some('[') => self.container(false).map(CstValue::Array),
+			Some('"') => self.string().map(CstValue::Scalar),
+			Some(_) => {
+				let start = self.pos;
+				while let Some(c) = self.peek() {
+					if c.is_whitespace() || ",:]}/".contains(c) {
+						break;
+					}
+					self.pos += c.len_utf8();
+				}
+				let raw = &self.text[start..self.pos];
+				// The whole token must be one value, so that e.g. `nullx` is rejected
+				parse_document(raw, ParseSetting::default(), true, &mut ElementTree)?;
+				Ok(CstValue::Scalar(raw.to_string()))
+			},
+			None => Err("Reached EOF while parsing JSON value".to_string()),
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		let start = self.pos;
+		self.expect('"')?;
+		let mut escaped = false;
+		while let Some(c) = self.peek() {
+			self.pos += c.len_utf8();
+			if escaped {
+				escaped = false;
+			} else if c == '\\' {
+				escaped = true;
+			} else if c == '"' {
+				let raw = &self.text[start..self.pos];
+				// Escapes and control characters are checked by the real parser
+				parse_document(raw, ParseSetting::default(), true, &mut ElementTree)?;
+				return Ok(raw.to_string());
+			}
+		}
+		Err("Reached EOF while parsing JSON string".to_string())
+	}
+
+	fn container(&mut self, is_object: bool) -> Result<CstContainer, String> {
+		let close = if is_object { '}' } else { ']' };
+		self.pos += 1;
+
+		let mut entries = Vec::new();
+		loop {
+			let leading = self.trivia()?;
+			if self.peek() == Some(close) {
+				self.pos += 1;
+				return Ok(CstContainer { entries, trailing: leading });
+			}
+
+			let key = if is_object {
+				let raw = self.string()?;
+				let name = match &mut build_json_graph(&raw)? {
+					JsonElement::JsonString(s) => std::mem::take(s),
+					_ => unreachable!(),
+				};
+				let after_key = self.trivia()?;
+				self.expect(':')?;
+				let after_colon = self.trivia()?;
+				Some(CstKey { raw, name, after_key, after_colon })
+			} else {
+				None
+			};
+			let value = self.value()?;
+			let after_value = self.trivia()?;
+
+			match self.peek() {
+				Some(',') => {
+					self.pos += 1;
+					entries.push(CstEntry { leading, key, value, after_value, comma: true });
+				},
+				Some(c) if c == close => {
+					self.pos += 1;
+					entries.push(CstEntry { leading, key, value, after_value, comma: false });
+					return Ok(CstContainer { entries, trailing: String::new() });
+				},
+				Some(c) => return Err(format!("Expected ',' or '{}', got {} at byte {}", close, c, self.pos)),
+				None => return Err(format!("Reached EOF while parsing JSON {}", if is_object { "object" } else { "array" })),
+			}
+		}
+	}
+}
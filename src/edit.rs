@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use crate::{escape_pointer_token, JsonElement};
+
+// In-place editing by JSON Pointer. Paths passed to callbacks are JSON Pointers as well.
+impl JsonElement {
+	pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JsonElement> {
+		let tokens = parse_pointer(pointer).ok()?;
+		self.lookup_mut(&tokens, pointer).ok()
+	}
+
+	// Stores `value` at the pointer and returns the value it replaced. Missing objects along
+	// the way are created; "-" or the length of an array appends to it.
+	pub fn set(&mut self, pointer: &str, value: JsonElement) -> Result<Option<JsonElement>, String> {
+		let tokens = parse_pointer(pointer)?;
+		self.set_tokens(&tokens, pointer, value).map_err(|(e, _)| e)
+	}
+
+	pub fn remove(&mut self, pointer: &str) -> Result<JsonElement, String> {
+		let tokens = parse_pointer(pointer)?;
+		let (last, parents) = tokens.split_last().ok_or_else(|| String::from("Cannot remove the root"))?;
+		match self.lookup_mut(parents, pointer)? {
+			JsonElement::JsonObject(obj) => obj.remove(last).ok_or_else(|| format!("Path not found: {}", pointer)),
+			JsonElement::JsonArray(v) => {
+				let i = array_index(last, v.len(), false, pointer)?;
+				Ok(v.remove(i))
+			},
+			other => Err(not_container(other, parents, pointer)),
+		}
+	}
+
+	// Renames the object member at the pointer, keeping its value.
+	pub fn rename(&mut self, pointer: &str, new_key: &str) -> Result<(), String> {
+		let tokens = parse_pointer(pointer)?;
+		let (last, parents) = tokens.split_last().ok_or_else(|| String::from("Cannot rename the root"))?;
+		match self.lookup_mut(parents, pointer)? {
+			JsonElement::JsonObject(obj) => {
+				if last == new_key {
+					return if obj.contains_key(last) { Ok(()) } else { Err(format!("Path not found: {}", pointer)) };
+				}
+				if obj.contains_key(new_key) {
+					return Err(format!("Key already exists: {}", new_key));
+				}
+				let value = obj.remove(last).ok_or_else(|| format!("Path not found: {}", pointer))?;
+				obj.insert(new_key.to_string(), value);
+				Ok(())
+			},
+			other => Err(format!("Cannot rename {}: parent is {} {}, not an object", pointer, article(other), other.type_name())),
+		}
+	}
+
+	// Moves the value at `from` to `to` as by `remove` followed by `set`, so array indices
+	// in `to` refer to the array after the removal. The document is unchanged on error.
+	pub fn move_to(&mut self, from: &str, to: &str) -> Result<(), String> {
+		let from_tokens = parse_pointer(from)?;
+		let to_tokens = parse_pointer(to)?;
+		if from_tokens == to_tokens {
+			return self.lookup_mut(&from_tokens, from).map(|_| ());
+		}
+		if to_tokens.starts_with(&from_tokens) {
+			return Err(format!("Cannot move {} into itself: {}", from, to));
+		}
+
+		let value = self.remove(from)?;
+		match self.set_tokens(&to_tokens, to, value) {
+			Ok(_) => Ok(()),
+			Err((e, value)) => {
+				self.restore(&from_tokens, value);
+				Err(e)
+			},
+		}
+	}
+
+	// Calls `f` on every element, parents before children. Children are visited after `f`
+	// returns, so replacing a container with `f` walks the new children.
+	pub fn walk_mut<F: FnMut(&str, &mut JsonElement)>(&mut self, mut f: F) {
+		walk_mut_at(self, &mut String::new(), &mut f);
+	}
+
+	// Replaces every element with the result of `f`, children before parents, so `f` sees
+	// containers whose children have already been mapped.
+	pub fn map<F: FnMut(&str, JsonElement) -> JsonElement>(&mut self, mut f: F) {
+		map_at(self, &mut String::new(), &mut f);
+	}
+
+	// Removes every array element and object member for which `f` returns false, along with
+	// its subtree. Paths are those of the document before anything was removed.
+	pub fn retain<F: FnMut(&str, &JsonElement) -> bool>(&mut self, mut f: F) {
+		retain_at(self, &mut String::new(), &mut f);
+	}
+
+	fn lookup_mut(&mut self, tokens: &[String], pointer: &str) -> Result<&mut JsonElement, String> {
+		let mut target = self;
+		for (depth, token) in tokens.iter().enumerate() {
+			target = match target {
+				JsonElement::JsonObject(obj) => obj.get_mut(token).ok_or_else(|| format!("Path not found: {}", pointer))?,
+				JsonElement::JsonArray(v) => {
+					let i = array_index(token, v.len(), false, pointer)?;
+					&mut v[i]
+				},
+				other => return Err(not_container(other, &tokens[..depth], pointer)),
+			};
+		}
+		Ok(target)
+	}
+
+	// Hands `value` back on error. Nothing is created before an error is detected: new
+	// intermediates are empty objects, which accept any further key.
+	fn set_tokens(&mut self, tokens: &[String], pointer: &str, value: JsonElement) -> Result<Option<JsonElement>, (String, JsonElement)> {
+		let (last, parents) = match tokens.split_last() {
+			Some(split) => split,
+			None => return Ok(Some(std::mem::replace(self, value))),
+		};
+
+		let mut target = self;
+		for (depth, token) in parents.iter().enumerate() {
+			target = match target {
+				JsonElement::JsonObject(obj) => obj.entry(token.clone()).or_insert_with(|| JsonElement::JsonObject(HashMap::new())),
+				JsonElement::JsonArray(v) => {
+					let i = match array_index(token, v.len(), true, pointer) {
+						Ok(i) => i,
+						Err(e) => return Err((e, value)),
+					};
+					if i == v.len() {
+						v.push(JsonElement::JsonObject(HashMap::new()));
+					}
+					&mut v[i]
+				},
+				other => return Err((not_container(other, &parents[..depth], pointer), value)),
+			};
+		}
+
+		match target {
+			JsonElement::JsonObject(obj) => Ok(obj.insert(last.clone(), value)),
+			JsonElement::JsonArray(v) => {
+				let i = match array_index(last, v.len(), true, pointer) {
+					Ok(i) => i,
+					Err(e) => return Err((e, value)),
+				};
+				if i == v.len() {
+					v.push(value);
+					Ok(None)
+				} else {
+					Ok(Some(std::mem::replace(&mut v[i], value)))
+				}
+			},
+			other => Err((not_container(other, parents, pointer), value)),
+		}
+	}
+
+	// Puts a value taken out by `remove` back where it was.
+	fn restore(&mut self, tokens: &[String], value: JsonElement) {
+		if let Some((last, parents)) = tokens.split_last() {
+			match self.lookup_mut(parents, "") {
+				Ok(JsonElement::JsonObject(obj)) => {
+					obj.insert(last.clone(), value);
+				},
+				Ok(JsonElement::JsonArray(v)) => {
+					if let Ok(i) = last.parse::<usize>() {
+						v.insert(i.min(v.len()), value);
+					}
+				},
+				_ => {},
+			}
+		}
+	}
+}
+
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+	if pointer.is_empty() {
+		return Ok(Vec::new());
+	}
+	if !pointer.starts_with('/') {
+		return Err(format!("Invalid JSON Pointer: {}", pointer));
+	}
+	Ok(pointer[1..].split('/').map(|t| t.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+// `allow_end` accepts "-" and the array length, which refer to the slot after the last element.
+fn array_index(token: &str, len: usize, allow_end: bool, pointer: &str) -> Result<usize, String> {
+	if token == "-" {
+		return if allow_end { Ok(len) } else { Err(format!("Index out of range: {}", pointer)) };
+	}
+	if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) || (token.len() > 1 && token.starts_with('0')) {
+		return Err(format!("Invalid array index {}: {}", token, pointer));
+	}
+	match token.parse::<usize>() {
+		Ok(i) if i < len || (allow_end && i == len) => Ok(i),
+		_ => Err(format!("Index out of range: {}", pointer)),
+	}
+}
+
+fn article(elm: &JsonElement) -> &'static str {
+	match elm.type_name() {
+		"array" | "object" | "integer" => "an",
+		_ => "a",
+	}
+}
+
+fn not_container(elm: &JsonElement, prefix: &[String], pointer: &str) -> String {
+	let prefix: String = prefix.iter().map(|t| format!("/{}", escape_pointer_token(t))).collect();
+	format!("Cannot traverse into {} {} at \"{}\": {}", article(elm), elm.type_name(), prefix, pointer)
+}
+
+// Like the other traversals, these keep their work on an explicit stack so that deep documents
+// don't overflow the call stack. A task holds the length of its parent's path and its own
+// token, which is pushed onto `path` when the task is popped; since tasks are handled
+// depth-first, the parent's path is always a prefix of `path` by then.
+fn set_path(path: &mut String, parent_len: usize, token: &Option<String>) {
+	path.truncate(parent_len);
+	if let Some(token) = token {
+		path.push('/');
+		path.push_str(&escape_pointer_token(token));
+	}
+}
+
+fn walk_mut_at<F: FnMut(&str, &mut JsonElement)>(elm: &mut JsonElement, path: &mut String, f: &mut F) {
+	let mut stack = vec![(elm, path.len(), None)];
+	while let Some((elm, parent_len, token)) = stack.pop() {
+		set_path(path, parent_len, &token);
+		f(path, elm);
+		// Pushed in reverse, so that children are visited in order
+		let len = path.len();
+		match elm {
+			JsonElement::JsonArray(v) => stack.extend(v.iter_mut().enumerate().rev().map(|(i, child)| (child, len, Some(i.to_string())))),
+			JsonElement::JsonObject(obj) => {
+				let children: Vec<_> = obj.iter_mut().map(|(k, child)| (child, len, Some(k.clone()))).collect();
+				stack.extend(children.into_iter().rev());
+			},
+			_ => {},
+		}
+	}
+}
+
+// Containers are taken apart so that their children can be handled on their own, and put back
+// together by a `Build` task once the children are done. `Visit` leaves each finished value
+// in `results`, where `None` is a child that `retain` dropped.
+enum Task {
+	// `check` is set for children that `retain` has yet to decide on
+	Visit(JsonElement, usize, Option<String>, bool),
+	BuildArray(usize, usize, Option<String>),
+	BuildObject(Vec<String>, usize, Option<String>),
+}
+
+// Pushes the tasks that take `elm` apart, or returns it if it has no children.
+fn take_apart(mut elm: JsonElement, parent_len: usize, token: Option<String>, path_len: usize, tasks: &mut Vec<Task>, check: bool) -> Option<JsonElement> {
+	match &mut elm {
+		JsonElement::JsonArray(v) => {
+			let children = std::mem::take(v);
+			tasks.push(Task::BuildArray(children.len(), parent_len, token));
+			for (i, child) in children.into_iter().enumerate().rev() {
+				tasks.push(Task::Visit(child, path_len, Some(i.to_string()), check));
+			}
+			None
+		},
+		JsonElement::JsonObject(obj) => {
+			let (keys, children): (Vec<String>, Vec<JsonElement>) = std::mem::take(obj).into_iter().unzip();
+			let visits: Vec<Task> = children.into_iter().zip(&keys).rev().map(|(child, key)| Task::Visit(child, path_len, Some(key.clone()), check)).collect();
+			tasks.push(Task::BuildObject(keys, parent_len, token));
+			tasks.extend(visits);
+			None
+		},
+		_ => Some(elm),
+	}
+}
+
+fn map_at<F: FnMut(&str, JsonElement) -> JsonElement>(elm: &mut JsonElement, path: &mut String, f: &mut F) {
+	let mut results = Vec::new();
+	let mut tasks = vec![Task::Visit(std::mem::replace(elm, JsonElement::JsonNull), path.len(), None, false)];
+	while let Some(task) = tasks.pop() {
+		let container = match task {
+			Task::Visit(value, parent_len, token, _) => {
+				set_path(path, parent_len, &token);
+				if let Some(value) = take_apart(value, parent_len, token, path.len(), &mut tasks, false) {
+					results.push(f(path, value));
+				}
+				continue;
+			},
+			Task::BuildArray(len, parent_len, token) => {
+				set_path(path, parent_len, &token);
+				JsonElement::JsonArray(results.split_off(results.len() - len))
+			},
+			Task::BuildObject(keys, parent_len, token) => {
+				set_path(path, parent_len, &token);
+				let values = results.split_off(results.len() - keys.len());
+				JsonElement::JsonObject(keys.into_iter().zip(values).collect())
+			},
+		};
+		results.push(f(path, container));
+	}
+	*elm = results.pop().unwrap_or(JsonElement::JsonNull);
+}
+
+fn retain_at<F: FnMut(&str, &JsonElement) -> bool>(elm: &mut JsonElement, path: &mut String, f: &mut F) {
+	let mut results: Vec<Option<JsonElement>> = Vec::new();
+	let mut tasks = vec![Task::Visit(std::mem::replace(elm, JsonElement::JsonNull), path.len(), None, false)];
+	while let Some(task) = tasks.pop() {
+		let container = match task {
+			Task::Visit(value, parent_len, token, check) => {
+				set_path(path, parent_len, &token);
+				if check && !f(path, &value) {
+					results.push(None);
+				} else if let Some(value) = take_apart(value, parent_len, token, path.len(), &mut tasks, true) {
+					results.push(Some(value));
+				}
+				continue;
+			},
+			Task::BuildArray(len, _, _) => JsonElement::JsonArray(results.split_off(results.len() - len).into_iter().flatten().collect()),
+			Task::BuildObject(keys, _, _) => {
+				let values = results.split_off(results.len() - keys.len());
+				JsonElement::JsonObject(keys.into_iter().zip(values).filter_map(|(k, v)| Some((k, v?))).collect())
+			},
+		};
+		results.push(Some(container));
+	}
+	*elm = results.pop().flatten().unwrap_or(JsonElement::JsonNull);
+}
@@ -0,0 +1,173 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{JsonElement, JsonNum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InferSetting {
+	// A string-only position becomes an `enum` when it has at most this many distinct values
+	// and at least one of them was seen more than once.
+	pub max_enum_values: usize,
+}
+
+impl Default for InferSetting {
+	fn default() -> InferSetting {
+		InferSetting {
+			max_enum_values: 8,
+		}
+	}
+}
+
+// Everything observed at one position of the sample documents.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shape {
+	pub(crate) nulls: usize,
+	pub(crate) bools: usize,
+	pub(crate) integers: usize,
+	pub(crate) doubles: usize,
+	min: Option<JsonNum>,
+	max: Option<JsonNum>,
+	pub(crate) strings: usize,
+	string_values: BTreeSet<String>,
+	too_many_strings: bool,
+	pub(crate) arrays: usize,
+	pub(crate) items: Option<Box<Shape>>,
+	pub(crate) objects: usize,
+	// Key -> (number of objects that had the key, shape of its values)
+	pub(crate) properties: BTreeMap<String, (usize, Shape)>,
+}
+
+impl Shape {
+	pub(crate) fn from_samples(samples: &[JsonElement], settings: InferSetting) -> Shape {
+		let mut shape = Shape::default();
+		for sample in samples {
+			shape.observe(sample, settings);
+		}
+		shape
+	}
+
+	fn observe(&mut self, elm: &JsonElement, settings: InferSetting) {
+		use JsonElement::*;
+
+		match elm {
+			JsonNull => self.nulls += 1,
+			JsonBool(_) => self.bools += 1,
+			JsonNumber(n) => {
+				match n {
+					JsonNum::Integer(_) => self.integers += 1,
+					JsonNum::Decimal(_) if n.is_integer() => self.integers += 1,
+					JsonNum::Double(_) | JsonNum::Decimal(_) => self.doubles += 1,
+				}
+				// Bounds keep the number as written, so that large integers and decimals stay exact
+				if self.min.as_ref().is_none_or(|m| n.compare(m) == Some(Ordering::Less)) {
+					self.min = Some(n.clone());
+				}
+				if self.max.as_ref().is_none_or(|m| n.compare(m) == Some(Ordering::Greater)) {
+					self.max = Some(n.clone());
+				}
+			},
+			JsonString(s) => {
+				self.strings += 1;
+				if !self.too_many_strings {
+					self.string_values.insert(s.clone());
+					if self.string_values.len() > settings.max_enum_values {
+						self.too_many_strings = true;
+						self.string_values.clear();
+					}
+				}
+			},
+			JsonArray(v) => {
+				self.arrays += 1;
+				for item in v {
+					self.items.get_or_insert_with(Default::default).observe(item, settings);
+				}
+			},
+			JsonObject(obj) => {
+				self.objects += 1;
+				for (k, v) in obj.iter() {
+					let (seen, shape) = self.properties.entry(k.clone()).or_default();
+					*seen += 1;
+					shape.observe(v, settings);
+				}
+			},
+		}
+	}
+
+	fn to_schema(&self) -> HashMap<String, JsonElement> {
+		use JsonElement::*;
+
+		let mut schema = HashMap::new();
+
+		let mut types = Vec::new();
+		if self.nulls > 0 {
+			types.push("null");
+		}
+		if self.bools > 0 {
+			types.push("boolean");
+		}
+		if self.doubles > 0 {
+			types.push("number");
+		} else if self.integers > 0 {
+			types.push("integer");
+		}
+		if self.strings > 0 {
+			types.push("string");
+		}
+		if self.arrays > 0 {
+			types.push("array");
+		}
+		if self.objects > 0 {
+			types.push("object");
+		}
+
+		match types.len() {
+			0 => {},
+			1 => {
+				schema.insert("type".to_string(), JsonString(types[0].to_string()));
+			},
+			_ => {
+				let types = types.iter().map(|t| JsonString(t.to_string())).collect();
+				schema.insert("type".to_string(), JsonArray(types));
+			},
+		}
+
+		if let (Some(min), Some(max)) = (&self.min, &self.max) {
+			schema.insert("minimum".to_string(), JsonNumber(min.clone()));
+			schema.insert("maximum".to_string(), JsonNumber(max.clone()));
+		}
+
+		let only_strings = self.strings > 0 && types.len() == 1;
+		if only_strings && !self.too_many_strings && self.string_values.len() < self.strings {
+			let values = self.string_values.iter().map(|s| JsonString(s.clone())).collect();
+			schema.insert("enum".to_string(), JsonArray(values));
+		}
+
+		if let Some(items) = &self.items {
+			schema.insert("items".to_string(), JsonObject(items.to_schema()));
+		}
+
+		if self.objects > 0 {
+			let mut properties = HashMap::new();
+			let mut required = Vec::new();
+			for (k, (seen, shape)) in self.properties.iter() {
+				properties.insert(k.clone(), JsonObject(shape.to_schema()));
+				if *seen == self.objects {
+					required.push(JsonString(k.clone()));
+				}
+			}
+			schema.insert("properties".to_string(), JsonObject(properties));
+			if !required.is_empty() {
+				schema.insert("required".to_string(), JsonArray(required));
+			}
+		}
+
+		schema
+	}
+}
+
+// Infers a JSON Schema (draft 2020-12) that accepts every sample.
+pub fn infer_schema(samples: &[JsonElement], settings: InferSetting) -> JsonElement {
+	let mut schema = Shape::from_samples(samples, settings).to_schema();
+	schema.insert("$schema".to_string(), JsonElement::JsonString("https://json-schema.org/draft/2020-12/schema".to_string()));
+	JsonElement::JsonObject(schema)
+}
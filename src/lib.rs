@@ -1,19 +1,84 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+#[macro_use]
+mod macros;
+
+mod arena;
+mod borrowed;
+mod codegen;
+mod convert;
+mod cst;
+mod edit;
+mod infer;
+mod merge;
+mod parallel;
+mod query;
+mod scan;
+mod schema;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod stats;
+mod tree;
+
+pub use arena::{build_json_arena, build_json_arena_with_setting, try_build_json_arena, JsonArena, JsonNode};
+pub use borrowed::{build_json_value_ref, build_json_value_ref_with_setting, try_build_json_value_ref, JsonValueRef};
+pub use codegen::{generate_types, CodegenLang};
+pub use cst::JsonDocument;
+pub use infer::{infer_schema, InferSetting};
+pub use merge::{MergeConflict, MergeResult};
+pub use parallel::{build_json_graph_parallel, build_json_graph_parallel_bytes, parse_ndjson, parse_ndjson_bytes, ParallelSetting};
+pub use query::JsonQuery;
+pub use schema::{JsonSchema, SchemaViolation};
+#[cfg(feature = "serde")]
+pub use serde_impl::{from_element, to_element};
+pub use stats::{JsonStats, StatsSetting};
+pub use tree::{render_tree, TreeSetting};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommentKind {
+	// The opening '/' has been read
+	Start,
+	Line,
+	Block,
+	// Inside a block comment right after a '*'
+	BlockStar,
+}
+
+impl CommentKind {
+	fn advance(self, ch: char) -> Option<CommentKind> {
+		use CommentKind::*;
+
+		match (self, ch) {
+			(Start, '/') => Some(Line),
+			(Start, _) => Some(Block),
+			(Line, '\n') => None,
+			(Line, _) => Some(Line),
+			(Block, '*') | (BlockStar, '*') => Some(BlockStar),
+			(BlockStar, '/') => None,
+			(Block, _) | (BlockStar, _) => Some(Block),
+		}
+	}
+}
+
+// Strips whitespace outside string literals. `//` and `/* */` comments are stripped as well,
+// unless `preserve_comments` is used, in which case they are passed through verbatim (line
+// comments with their terminating '\n'), preceded by a '\n' if the comment started on a new line.
+#[derive(Debug, Clone)]
 pub struct JsonMinimizer<'a> {
 	in_str_literal: bool,
 	skip_char: bool,
 	buffer: std::str::Chars<'a>,
+	keep_comments: bool,
+	comment: Option<CommentKind>,
+	saw_newline: bool,
+	pending: Option<char>,
 }
 
 impl<'a> JsonMinimizer<'a> {
 	pub fn new_from_str(chars: &'a str) -> Self {
-		JsonMinimizer {
-			in_str_literal: false,
-			skip_char: false,
-			buffer: chars.chars(),
-		}
+		Self::new_from_chars(chars.chars())
 	}
 
 	pub fn new_from_chars(chars: std::str::Chars<'a>) -> Self {
@@ -21,6 +86,76 @@ impl<'a> JsonMinimizer<'a> {
 			in_str_literal: false,
 			skip_char: false,
 			buffer: chars,
+			keep_comments: false,
+			comment: None,
+			saw_newline: false,
+			pending: None,
+		}
+	}
+
+	pub fn preserve_comments(mut self) -> Self {
+		self.keep_comments = true;
+		self
+	}
+
+	// Writes the rest of the output, the same as the iterator would yield. Runs of string
+	// contents and of other characters that are kept as-is are found a block of bytes at a
+	// time and copied whole; only comments and non-ASCII characters outside strings go
+	// through `next`.
+	pub fn write_to<W: std::io::Write + ?Sized>(mut self, out: &mut W) -> std::io::Result<()> {
+		let mut buf = [0; 4];
+		loop {
+			if self.pending.is_none() && !self.skip_char && self.comment.is_none() {
+				let rest = self.buffer.as_str();
+				let bytes = rest.as_bytes();
+				let mut i = 0;
+				loop {
+					if self.in_str_literal {
+						let end = i + scan::find_string_end(&bytes[i..], b'"');
+						match bytes.get(end) {
+							Some(b'"') => {
+								out.write_all(&bytes[i..=end])?;
+								self.in_str_literal = false;
+								i = end + 1;
+							},
+							// A backslash and the ASCII character it escapes
+							Some(_) if bytes.get(end + 1).is_some_and(|b| b.is_ascii()) => {
+								out.write_all(&bytes[i..end + 2])?;
+								i = end + 2;
+							},
+							_ => {
+								out.write_all(&bytes[i..end])?;
+								i = end;
+								break;
+							},
+						}
+					} else {
+						while let Some(&b) = bytes.get(i).filter(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r')) {
+							if b == b'\n' {
+								self.saw_newline = true;
+							}
+							i += 1;
+						}
+						let end = i + scan::find_layout(&bytes[i..]);
+						let opens_string = bytes.get(end) == Some(&b'"');
+						if opens_string || end > i {
+							let end = if opens_string { end + 1 } else { end };
+							out.write_all(&bytes[i..end])?;
+							self.saw_newline = false;
+							self.in_str_literal = opens_string;
+							i = end;
+						} else if !matches!(bytes.get(i), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+							break;
+						}
+					}
+				}
+				self.buffer = rest[i..].chars();
+			}
+
+			match self.next() {
+				Some(ch) => out.write_all(ch.encode_utf8(&mut buf).as_bytes())?,
+				None => return Ok(()),
+			}
 		}
 	}
 }
@@ -29,6 +164,10 @@ impl Iterator for JsonMinimizer<'_> {
 	type Item = char;
 
 	fn next(&mut self) -> Option<Self::Item> {
+		if let c@Some(_) = self.pending.take() {
+			return c;
+		}
+
 		while let Some(ch) = self.buffer.next() {
 			if self.skip_char {
 				self.skip_char = false;
@@ -45,9 +184,34 @@ impl Iterator for JsonMinimizer<'_> {
 				}
 				return Some(ch);
 			}
+			if let Some(kind) = self.comment {
+				self.comment = kind.advance(ch);
+				// A line comment ends with the newline
+				self.saw_newline = self.comment.is_none() && kind == CommentKind::Line;
+				if self.keep_comments {
+					return Some(ch);
+				}
+				continue;
+			}
+			if ch == '/' && matches!(self.buffer.clone().next(), Some('/') | Some('*')) {
+				self.comment = Some(CommentKind::Start);
+				if !self.keep_comments {
+					continue;
+				}
+				if self.saw_newline {
+					self.saw_newline = false;
+					self.pending = Some(ch);
+					return Some('\n');
+				}
+				return Some(ch);
+			}
 			if ch.is_whitespace() {
+				if ch == '\n' {
+					self.saw_newline = true;
+				}
 				continue;
 			}
+			self.saw_newline = false;
 			if ch == '\"' {
 				self.in_str_literal = true;
 				return Some(ch);
@@ -109,19 +273,25 @@ pub struct JsonFormatter<'a> {
 	curr_indentlv: u32,
 	ctx: Vec<JsonContext>,
 	skip_char: bool,
-	last_comma: bool,
 	buffer: JsonMinimizer<'a>,
 	settings: PrettySetting,
-	next_chars: std::collections::VecDeque<char>
+	next_chars: std::collections::VecDeque<char>,
+	// Line breaks after ',' and openers are deferred so that a trailing comment can stay on the same line.
+	pending_newline: bool,
+	last_opener: bool,
+	at_start: bool,
+	comment: Option<CommentKind>,
+	own_line_comment: bool,
+	space_after_comment: bool,
 }
 
 impl<'a> JsonFormatter<'a> {
 	pub fn new_from_str(s: &'a str, settings: PrettySetting) -> Self {
-		Self::new_from_minimizer(JsonMinimizer::new_from_str(s), settings)
+		Self::new_from_minimizer(JsonMinimizer::new_from_str(s).preserve_comments(), settings)
 	}
 
 	pub fn new_from_chars(chars: std::str::Chars<'a>, settings: PrettySetting) -> Self {
-		Self::new_from_minimizer(JsonMinimizer::new_from_chars(chars), settings)
+		Self::new_from_minimizer(JsonMinimizer::new_from_chars(chars).preserve_comments(), settings)
 	}
 
 	pub fn new_from_minimizer(minimizer: JsonMinimizer<'a>, settings: PrettySetting) -> Self {
@@ -129,99 +299,163 @@ impl<'a> JsonFormatter<'a> {
 			curr_indentlv: 0,
 			ctx: vec![JsonContext::Object],
 			skip_char: false,
-			last_comma: false,
 			buffer: minimizer,
 			settings,
 			next_chars: std::collections::VecDeque::new(),
+			pending_newline: false,
+			last_opener: false,
+			at_start: true,
+			comment: None,
+			own_line_comment: false,
+			space_after_comment: false,
 		}
 	}
-}
-
-impl Iterator for JsonFormatter<'_> {
-	type Item = char;
 
-	fn next(&mut self) -> Option<Self::Item> {
-		let repeat_chars = |ch, n| std::iter::repeat(ch).take(n);
+	fn push_newline(&mut self) {
+		self.next_chars.push_back('\n');
+		self.next_chars.extend(std::iter::repeat_n(' ', (self.curr_indentlv * self.settings.indent_width) as usize));
+	}
 
-		if let c@Some(_) = self.next_chars.pop_front() {
-			return c;
+	fn push_char(&mut self, ch: char) {
+		if self.skip_char {
+			self.skip_char = false;
+			self.next_chars.push_back(ch);
+			return;
 		}
 
-		while let Some(ch) = self.buffer.next() {
-			if self.skip_char {
-				self.skip_char = false;
-				self.last_comma = false;
-				self.next_chars.push_back(ch);
-				break;
+		if self.ctx.last() == Some(&JsonContext::String) {
+			if ch == '\\' {
+				self.skip_char = true;
+			} else if ch == '"' {
+				self.ctx.pop();
 			}
+			self.next_chars.push_back(ch);
+			return;
+		}
 
-			else if let Some(c) = self.ctx.last() {
-				if *c == JsonContext::String {
-					if ch == '\\' {
-						self.skip_char = true;
-					} else if ch == '"' {
-						self.ctx.pop();
-					}
-					self.last_comma = false;
+		if let Some(kind) = self.comment {
+			self.comment = kind.advance(ch);
+			match self.comment {
+				// End of line comment
+				None if kind == CommentKind::Line => self.pending_newline = true,
+				None => {
 					self.next_chars.push_back(ch);
-					break;
-				}
-			}
-
-			if ch == '"' {
-				self.last_comma = false;
-				self.ctx.push(JsonContext::String);
-				self.next_chars.push_back(ch);
+					if self.own_line_comment {
+						self.pending_newline = true;
+					} else {
+						self.space_after_comment = true;
+					}
+				},
+				Some(_) if ch == '\r' => {},
+				Some(_) => self.next_chars.push_back(ch),
 			}
+			return;
+		}
 
-			else if ch == ',' {
-				self.last_comma = true;
-				self.next_chars.push_back(ch);
-				self.next_chars.push_back('\n');
-				self.next_chars.extend(repeat_chars(' ', (self.curr_indentlv * self.settings.indent_width) as usize));
-			}
+		// The minimizer marks comments that start on their own line with '\n'
+		if ch == '\n' {
+			self.own_line_comment = true;
+			return;
+		}
 
-			else if ch == '[' || ch == '{' {
-				self.ctx.push(match ch {
-					'[' => JsonContext::Array,
-					'{' => JsonContext::Object,
-					_ => unreachable!(),
-				});
-				self.last_comma = false;
-				self.curr_indentlv += 1;
-				self.next_chars.push_back(ch);
-				self.next_chars.push_back('\n');
-				self.next_chars.extend(repeat_chars(' ', (self.curr_indentlv * self.settings.indent_width) as usize));
-			}
-	
-			else if ch == ']' || ch == '}' {
-				self.curr_indentlv -= 1;
-				self.ctx.pop();
-				
-				self.next_chars.push_back('\n');
-				self.next_chars.extend(repeat_chars(' ', (self.curr_indentlv * self.settings.indent_width) as usize));
-				self.next_chars.push_back(ch);
-			}
-	
-			else if ch == ':' {
-				self.next_chars.push_back(ch);
+		if ch == '/' {
+			if self.at_start {
+				self.own_line_comment = true;
+			} else if self.own_line_comment {
+				self.push_newline();
+				self.pending_newline = false;
+			} else {
+				// A comment after ',' or an opener stays on that line, and the pending line break follows it
 				self.next_chars.push_back(' ');
 			}
-	
-			else {
-				self.next_chars.push_back(ch);
+			self.comment = Some(CommentKind::Start);
+			self.space_after_comment = false;
+			self.last_opener = false;
+			self.at_start = false;
+			self.next_chars.push_back(ch);
+			return;
+		}
+		self.own_line_comment = false;
+		self.at_start = false;
+
+		if ch == ']' || ch == '}' {
+			self.curr_indentlv -= 1;
+			self.ctx.pop();
+			if !self.last_opener {
+				self.push_newline();
 			}
+			self.pending_newline = false;
+			self.space_after_comment = false;
+			self.last_opener = false;
+			self.next_chars.push_back(ch);
+			return;
+		}
+
+		if self.pending_newline {
+			self.pending_newline = false;
+			self.push_newline();
+		} else if self.space_after_comment && ch != ',' && ch != ':' {
+			self.next_chars.push_back(' ');
+		}
+		self.space_after_comment = false;
+		self.last_opener = false;
+
+		if ch == '"' {
+			self.ctx.push(JsonContext::String);
+			self.next_chars.push_back(ch);
+		}
+
+		else if ch == ',' {
+			self.next_chars.push_back(ch);
+			self.pending_newline = true;
+		}
+
+		else if ch == '[' || ch == '{' {
+			self.ctx.push(match ch {
+				'[' => JsonContext::Array,
+				'{' => JsonContext::Object,
+				_ => unreachable!(),
+			});
+			self.curr_indentlv += 1;
+			self.next_chars.push_back(ch);
+			self.pending_newline = true;
+			self.last_opener = true;
+		}
+
+		else if ch == ':' {
+			self.next_chars.push_back(ch);
+			self.next_chars.push_back(' ');
+		}
+
+		else {
+			self.next_chars.push_back(ch);
+		}
+	}
+}
+
+impl Iterator for JsonFormatter<'_> {
+	type Item = char;
 
-			return self.next_chars.pop_front();
+	fn next(&mut self) -> Option<Self::Item> {
+		if let c@Some(_) = self.next_chars.pop_front() {
+			return c;
+		}
+
+		while let Some(ch) = self.buffer.next() {
+			self.push_char(ch);
+			if let c@Some(_) = self.next_chars.pop_front() {
+				return c;
+			}
 		}
 
-		self.next_chars.pop_front()
+		None
 	}
 }
 
 impl std::iter::FusedIterator for JsonFormatter<'_> {}
 
 #[deprecated(note = "use JsonFormatter instead.")]
+#[allow(deprecated)]
 pub fn pretty_json(json: &str, setting: &PrettySetting) -> String {
 	let compressed = minimize_json(json);
 	let dirty = &compressed;
@@ -315,17 +549,100 @@ pub struct PrettySetting {
 	pub indent_width: u32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+// New kinds of numbers may be added, so matches need a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum JsonNum {
 	Integer(i64),
 	Double(f64),
+	// The source text of a number, kept as-is when parsing with `ParseSetting::arbitrary_precision`.
+	Decimal(String),
 }
 
 impl JsonNum {
+	pub fn as_f64(&self) -> f64 {
+		match self {
+			JsonNum::Integer(i) => *i as f64,
+			JsonNum::Double(d) => *d,
+			JsonNum::Decimal(s) => s.parse().unwrap_or(f64::NAN),
+		}
+	}
+
+	// Whether the number was written without a fraction or exponent.
+	pub fn is_integer(&self) -> bool {
+		match self {
+			JsonNum::Integer(_) => true,
+			JsonNum::Double(_) => false,
+			JsonNum::Decimal(s) => !s.contains(['.', 'e', 'E']),
+		}
+	}
+
+	// The exact value as (negative, significant digits, exponent), meaning digits × 10^exponent.
+	// Digits carry no leading or trailing zeros, so equal values have equal parts. Doubles
+	// have no exact decimal form and return None.
+	fn decimal_parts(&self) -> Option<(bool, String, i64)> {
+		let text = match self {
+			JsonNum::Integer(i) => i.to_string(),
+			JsonNum::Decimal(s) => s.clone(),
+			JsonNum::Double(_) => return None,
+		};
+		let (negative, body) = match text.strip_prefix('-') {
+			Some(rest) => (true, rest),
+			None => (false, text.as_str()),
+		};
+		let (mantissa, exp) = match body.find(['e', 'E']) {
+			Some(i) => (&body[..i], body[i + 1..].trim_start_matches('+').parse::<i64>().ok()?),
+			None => (body, 0),
+		};
+		let (int, frac) = match mantissa.find('.') {
+			Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+			None => (mantissa, ""),
+		};
+
+		let digits = format!("{}{}", int, frac);
+		let trimmed = digits.trim_end_matches('0');
+		let exp = exp.checked_sub(frac.len() as i64)?.checked_add((digits.len() - trimmed.len()) as i64)?;
+		let trimmed = trimmed.trim_start_matches('0');
+		if trimmed.is_empty() {
+			return Some((false, String::new(), 0));
+		}
+		Some((negative, trimmed.to_string(), exp))
+	}
+
+	// Orders integers and decimals exactly, and anything involving a double as f64.
+	pub(crate) fn compare(&self, other: &JsonNum) -> Option<std::cmp::Ordering> {
+		use std::cmp::Ordering::*;
+
+		let (a, b) = match (self.decimal_parts(), other.decimal_parts()) {
+			(Some(a), Some(b)) => (a, b),
+			_ => return self.as_f64().partial_cmp(&other.as_f64()),
+		};
+		let sign = |(negative, digits, _): &(bool, String, i64)| match (digits.is_empty(), negative) {
+			(true, _) => 0,
+			(false, true) => -1,
+			(false, false) => 1,
+		};
+		// 0.digits × 10^(exponent + length): a larger point position is larger, and then the
+		// digit strings, which have no trailing zeros, compare as text
+		let magnitude = |(_, digits, exp): &(bool, String, i64)| (exp.saturating_add(digits.len() as i64), digits.clone());
+		Some(match sign(&a).cmp(&sign(&b)) {
+			Equal if sign(&a) == 0 => Equal,
+			Equal if sign(&a) < 0 => magnitude(&b).cmp(&magnitude(&a)),
+			Equal => magnitude(&a).cmp(&magnitude(&b)),
+			ordering => ordering,
+		})
+	}
+
+	// Integers and decimals are compared exactly; a double on either side is compared within `threshold`.
 	pub fn is_equal(&self, other: &JsonNum, threshold: f64) -> bool {
 		use JsonNum::*;
 
+		if let (Some(a), Some(b)) = (self.decimal_parts(), other.decimal_parts()) {
+			return a == b;
+		}
+
 		match self {
+			Decimal(_) => (self.as_f64() - other.as_f64()).abs() < threshold,
 			Integer(si) => {
 				match other {
 					Integer(oi) => {
@@ -333,7 +650,8 @@ impl JsonNum {
 					},
 					Double(od) => {
 						(*si as f64 - od).abs() < threshold
-					}
+					},
+					Decimal(_) => (*si as f64 - other.as_f64()).abs() < threshold,
 				}
 			},
 
@@ -344,7 +662,8 @@ impl JsonNum {
 					},
 					Double(od) => {
 						(sd - od).abs() < threshold
-					}
+					},
+					Decimal(_) => (sd - other.as_f64()).abs() < threshold,
 				}
 			}
 		}
@@ -356,11 +675,12 @@ impl std::fmt::Display for JsonNum {
 		match self {
 			JsonNum::Integer(i) => write!(formatter, "{}", i),
 			JsonNum::Double(d) => write!(formatter, "{}", d),
+			JsonNum::Decimal(s) => write!(formatter, "{}", s),
 		}
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug)]
 pub enum JsonElement {
 	JsonNumber(JsonNum),
 	JsonString(String),
@@ -370,331 +690,1002 @@ pub enum JsonElement {
 	JsonBool(bool),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct JsonLexer<'a> {
-	chars: &'a str,
-	ptr: usize,
-}
+// Clone, Drop and PartialEq walk the tree with an explicit stack so that deeply nested
+// documents don't overflow the call stack.
+impl Clone for JsonElement {
+	fn clone(&self) -> JsonElement {
+		use JsonElement::*;
 
-impl<'a> JsonLexer<'a> {
-	fn new(json: &'a str) -> JsonLexer<'a> {
-		JsonLexer {chars: json, ptr: 0}
-	}
+		enum Frame<'a> {
+			Array(std::slice::Iter<'a, JsonElement>, Vec<JsonElement>),
+			Object(std::collections::hash_map::Iter<'a, String, JsonElement>, HashMap<String, JsonElement>, Option<&'a String>),
+		}
 
-	#[allow(dead_code)]
-	fn ptr(&mut self) -> usize {
-		self.ptr
-	}
+		let mut stack = Vec::new();
+		let mut next = self;
+		loop {
+			let mut value = match next {
+				JsonArray(v) => {
+					stack.push(Frame::Array(v.iter(), Vec::with_capacity(v.len())));
+					None
+				},
+				JsonObject(obj) => {
+					stack.push(Frame::Object(obj.iter(), HashMap::with_capacity(obj.len()), None));
+					None
+				},
+				JsonNumber(n) => Some(JsonNumber(n.clone())),
+				JsonString(s) => Some(JsonString(s.clone())),
+				JsonNull => Some(JsonNull),
+				JsonBool(b) => Some(JsonBool(*b)),
+			};
 
-	fn next(&mut self) -> Option<char> {
-		if self.ptr < self.chars.len() {
-			let ch = self.chars.chars().nth(self.ptr);
-			self.ptr += 1;
-			ch
-		} else {
-			None
+			// Hand the finished value to its parent until some parent has a child left to clone.
+			loop {
+				match stack.last_mut() {
+					None => return value.unwrap(),
+					Some(Frame::Array(iter, out)) => {
+						out.extend(value.take());
+						if let Some(child) = iter.next() {
+							next = child;
+							break;
+						}
+					},
+					Some(Frame::Object(iter, out, key)) => {
+						if let (Some(k), Some(v)) = (key.take(), value.take()) {
+							out.insert(k.clone(), v);
+						}
+						if let Some((k, child)) = iter.next() {
+							*key = Some(k);
+							next = child;
+							break;
+						}
+					},
+				}
+				value = match stack.pop() {
+					Some(Frame::Array(_, out)) => Some(JsonArray(out)),
+					Some(Frame::Object(_, out, _)) => Some(JsonObject(out)),
+					None => None,
+				};
+			}
 		}
 	}
+}
 
-	fn back(&mut self) {
-		self.ptr -= 1;
+impl Drop for JsonElement {
+	fn drop(&mut self) {
+		use JsonElement::*;
+
+		let is_nested = |c: &JsonElement| match c {
+			JsonArray(v) => !v.is_empty(),
+			JsonObject(obj) => !obj.is_empty(),
+			_ => false,
+		};
+		// Flat containers are left to the default drop glue.
+		let mut stack: Vec<JsonElement> = match self {
+			JsonArray(v) if v.iter().any(is_nested) => std::mem::take(v),
+			JsonObject(obj) if obj.values().any(is_nested) => obj.drain().map(|(_, v)| v).collect(),
+			_ => return,
+		};
+		// Every element is emptied before it is dropped, so this drop never recurses deeper.
+		while let Some(mut elm) = stack.pop() {
+			match &mut elm {
+				JsonArray(v) => stack.append(v),
+				JsonObject(obj) => stack.extend(obj.drain().map(|(_, v)| v)),
+				_ => {},
+			}
+		}
 	}
+}
 
-	#[allow(dead_code)]
-	fn slice(&self, start: usize, end: Option<usize>) -> Option<&'a str> {
-		let end = end.unwrap_or(self.chars.len());
-		if end <= self.chars.len() && start <= end {
-			Some(&self.chars[start..end])
-		} else {
-			None
+// The same as the derived equality: numbers compare as `JsonNum`, so 1 and 1.0 differ.
+impl PartialEq for JsonElement {
+	fn eq(&self, other: &JsonElement) -> bool {
+		use JsonElement::*;
+
+		let mut stack = vec![(self, other)];
+		while let Some(pair) = stack.pop() {
+			match pair {
+				(JsonNumber(a), JsonNumber(b)) if a == b => {},
+				(JsonString(a), JsonString(b)) if a == b => {},
+				(JsonBool(a), JsonBool(b)) if a == b => {},
+				(JsonNull, JsonNull) => {},
+				(JsonArray(a), JsonArray(b)) if a.len() == b.len() => stack.extend(a.iter().zip(b)),
+				(JsonObject(a), JsonObject(b)) if a.len() == b.len() => {
+					for (k, av) in a {
+						match b.get(k) {
+							Some(bv) => stack.push((av, bv)),
+							None => return false,
+						}
+					}
+				},
+				_ => return false,
+			}
 		}
+		true
 	}
+}
 
-	#[allow(dead_code)]
-	fn reset(&mut self) {
-		self.ptr = 0;
+fn write_json_string(formatter: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+	write!(formatter, "\"")?;
+	for ch in s.chars() {
+		match ch {
+			'"' => write!(formatter, "\\\"")?,
+			'\\' => write!(formatter, "\\\\")?,
+			'\n' => write!(formatter, "\\n")?,
+			'\r' => write!(formatter, "\\r")?,
+			'\t' => write!(formatter, "\\t")?,
+			'\u{08}' => write!(formatter, "\\b")?,
+			'\u{0c}' => write!(formatter, "\\f")?,
+			c if (c as u32) < 0x20 => write!(formatter, "\\u{:04x}", c as u32)?,
+			c => write!(formatter, "{}", c)?,
+		}
 	}
+	write!(formatter, "\"")
 }
 
-fn parse_json(json: &mut JsonLexer) -> Result<JsonElement, String> {
-	let mut json_elem: Option<Result<JsonElement, String>> = None;
+impl JsonElement {
+	// The JSON Schema name of the element's type. Integral numbers are "integer", others "number".
+	pub fn type_name(&self) -> &'static str {
+		use JsonElement::*;
 
-	while let Some(ch) = json.next() {
-		if ch.is_whitespace() {
-			continue;
+		match self {
+			JsonNull => "null",
+			JsonBool(_) => "boolean",
+			JsonString(_) => "string",
+			JsonArray(_) => "array",
+			JsonObject(_) => "object",
+			JsonNumber(n) if n.is_integer() => "integer",
+			JsonNumber(_) => "number",
 		}
+	}
 
-		//println!("{}", ch);
-		if ch == '{' {
-			//println!("OBJECT");
-			json_elem = Some(parse_json_object(json));
-			break;
-		} else if ch == '"' {
-			//println!("STRING");
-			json_elem = Some(parse_json_string(json));
-			break;
-		} else if ch == '[' {
-			//println!("ARRAY");
-			json_elem = Some(parse_json_array(json));
-			break;
-		} else if ch == 'n' {
-			json.back();
-			json_elem = Some(parse_json_null(json));
-			break;
-		} else if ch == 't' || ch == 'f' {
-			json.back();
-			json_elem = Some(parse_json_bool(json));
-			break;
-		} else {
-			//println!("NUMBER");
-			// TODO Number parsing
-			json.back();
-			json_elem = Some(parse_json_number(json));
-			break;
+	// Looks up a value by RFC 6901 JSON Pointer, e.g. "/a/0/b". The empty pointer refers to the element itself.
+	pub fn pointer(&self, pointer: &str) -> Option<&JsonElement> {
+		if pointer.is_empty() {
+			return Some(self);
+		}
+		if !pointer.starts_with('/') {
+			return None;
+		}
+
+		let mut target = self;
+		for token in pointer[1..].split('/') {
+			let token = token.replace("~1", "/").replace("~0", "~");
+			target = match target {
+				JsonElement::JsonObject(obj) => obj.get(&token)?,
+				JsonElement::JsonArray(v) => v.get(token.parse::<usize>().ok()?)?,
+				_ => return None,
+			};
 		}
+		Some(target)
 	}
 
-	match json_elem {
-		Some(j) => j,
-		None => Err("cannot parse JSON".to_string())
+	// Like `structure_diff`, for documents that have been parsed already.
+	pub fn diff(&self, compared: &JsonElement, settings: DiffSetting) -> Vec<JsonDiff> {
+		element_diff(self, compared, "", settings)
 	}
 }
 
-fn parse_json_object(json: &mut JsonLexer) -> Result<JsonElement, String> {
-	let mut pairs = HashMap::new();
+pub fn escape_pointer_token(token: &str) -> String {
+	token.replace('~', "~0").replace('/', "~1")
+}
 
-	let res;
-	'top: loop {
-		let (key, value) = parse_json_object_pair(json)?;
+// Writes the element as minified JSON. Object keys are sorted so the output is stable.
+// Like Clone and Drop, it walks the tree with an explicit stack.
+impl std::fmt::Display for JsonElement {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		use JsonElement::*;
 
-		//println!("objKEY: {:?}, objVALUE: {:?}", &key, &value);
-		pairs.insert(key, value);
+		enum Step<'a> {
+			Value(&'a JsonElement),
+			Key(&'a str),
+			Text(&'static str),
+		}
 
-		while let Some(ch) = json.next() {
-			//println!("kv ch {}", ch);
-			if ch.is_whitespace() {
-				continue;
-			} else if ch == ',' {
-				break; // Next kv pair
-			} else if ch == '}' {
-				// End of JSON object
-				let obj = JsonElement::JsonObject(pairs);
-				res = Some(Ok(obj));
-				break 'top;
-			} else {
-				res = Some(Err(format!("Expected ',' or '}}', got {}", ch)));
-				break 'top;
+		let mut stack = vec![Step::Value(self)];
+		while let Some(step) = stack.pop() {
+			let elm = match step {
+				Step::Text(t) => {
+					formatter.write_str(t)?;
+					continue;
+				},
+				Step::Key(k) => {
+					write_json_string(formatter, k)?;
+					formatter.write_str(":")?;
+					continue;
+				},
+				Step::Value(elm) => elm,
+			};
+			match elm {
+				// JSON has no representation for Infinity and NaN. Callers converting JSON5 should
+				// reject them first, as the CLI does.
+				JsonNumber(JsonNum::Double(d)) if !d.is_finite() => formatter.write_str("null")?,
+				JsonNumber(n) => write!(formatter, "{}", n)?,
+				JsonString(s) => write_json_string(formatter, s)?,
+				JsonArray(v) => {
+					formatter.write_str("[")?;
+					stack.push(Step::Text("]"));
+					for (i, child) in v.iter().enumerate().rev() {
+						stack.push(Step::Value(child));
+						if i != 0 {
+							stack.push(Step::Text(","));
+						}
+					}
+				},
+				JsonObject(obj) => {
+					let mut members: Vec<(&String, &JsonElement)> = obj.iter().collect();
+					members.sort_by(|a, b| a.0.cmp(b.0));
+					formatter.write_str("{")?;
+					stack.push(Step::Text("}"));
+					for (i, (k, child)) in members.into_iter().enumerate().rev() {
+						stack.push(Step::Value(child));
+						stack.push(Step::Key(k));
+						if i != 0 {
+							stack.push(Step::Text(","));
+						}
+					}
+				},
+				JsonNull => formatter.write_str("null")?,
+				JsonBool(b) => write!(formatter, "{}", b)?,
 			}
 		}
-	}
-	match res {
-		Some(s) => s,
-		None => Err(String::from("Reached EOF while parsing JSON object")),
+		Ok(())
 	}
 }
 
-fn parse_json_object_pair(json: &mut JsonLexer) -> Result<(String, JsonElement), String> {
-	let _ctx = vec![JsonContext::Object];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseSetting {
+	// Accept JSON5: comments, trailing commas, identifier keys, single-quoted strings,
+	// hexadecimal numbers, Infinity/NaN and a leading '+' or '.' in numbers.
+	pub json5: bool,
+	// Accept JSONC: `//` and `/* */` comments, as in editor settings files. JSON5 allows
+	// them as well.
+	pub jsonc: bool,
+	// Keep decimal numbers as their source text (`JsonNum::Decimal`) instead of converting
+	// them to i64/f64, so that they re-serialize unchanged and compare exactly.
+	pub arbitrary_precision: bool,
+	pub limits: ParseLimits,
+}
 
-	// Parse key (string)
-	let mut res = None;
-	while let Some(ch) = json.next() {
-		if ch.is_whitespace() {
-			continue;
-		} else if ch == '"' {
-			break;
-		} else {
-			res = Some(Err(String::from(format!("Expected '\"', got {}", ch))));
-			break;
-		}
-	}
-	if res.is_some() {
-		return res.unwrap();
-	}
-	let key = parse_json_string(json)?;
-	// expect colon
-	let mut res = None;
-	while let Some(ch) = json.next() {
-		if ch.is_whitespace() {
-			continue;
-		} else if ch == ':' {
-			break;
-		} else {
-			res = Some(Err(String::from(format!("Expected ':', got {}", ch))));
-			break;
-		}
-	}
-	if res.is_some() {
-		return res.unwrap();
-	}
-	// Parse value (any json element)
-	let value = parse_json(json)?;
+// Bounds on untrusted input. `None` means unlimited, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+	// Number of nested arrays and objects; a top-level container is depth 1.
+	pub max_depth: Option<usize>,
+	// Length of a decoded string or object key, in bytes.
+	pub max_string_length: Option<usize>,
+	// Number of elements in one array or members in one object.
+	pub max_members: Option<usize>,
+	// Length of the whole input, in bytes.
+	pub max_input_size: Option<usize>,
+}
 
-	let key = match key {
-		JsonElement::JsonString(s) => s,
-		_ => unreachable!()
-	};
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+	Depth, StringLength, Members, InputSize
+}
 
-	Ok((key, value))
+impl std::fmt::Display for LimitKind {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		use LimitKind::*;
+		let name = match self {
+			Depth => "nesting depth",
+			StringLength => "string length",
+			Members => "number of members",
+			InputSize => "input size",
+		};
+		write!(formatter, "{}", name)
+	}
 }
 
-fn parse_json_string(json: &mut JsonLexer) -> Result<JsonElement, String> {
-	let mut skip = false;
-	let mut buffer = String::new();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+	// Malformed input.
+	Syntax(String),
+	// Input exceeding one of the `ParseLimits`, with the limit that was hit.
+	LimitExceeded(LimitKind, usize),
+}
 
-	let mut res = None;
-	while let Some(ch) = json.next() {
-		if ch == '"' && !skip {
-			// End of JSON string
-			res = Some(Ok(JsonElement::JsonString(buffer.clone())));
-			break;
-		} else if ch == '\\' && !skip {
-			skip = true;
-			continue;
-		} else {
-			buffer.push(ch);
+impl std::fmt::Display for ParseError {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			ParseError::Syntax(msg) => write!(formatter, "{}", msg),
+			ParseError::LimitExceeded(kind, limit) => write!(formatter, "Limit exceeded: {} is over {}", kind, limit),
 		}
-	};
-
-	//println!("end string");
-	//println!("{}", json.ptr());
-	match res {
-		Some(s) => {
-			//println!("{}", buffer);
-			s
-		},
-		None => Err(format!("Reached EOF while parsing JSON string. string: {}", buffer)),
 	}
 }
 
-fn parse_json_number(json: &mut JsonLexer) -> Result<JsonElement, String> {
-	// TODO: support for frac exp, more precise parsing
+impl std::error::Error for ParseError {}
 
-	let mut first_char = None;
-	while let Some(ch) = json.next() {
-		if ch.is_whitespace() {
-			continue;
-		} else {
-			first_char = Some(ch);
-			break;
+impl From<ParseError> for String {
+	fn from(e: ParseError) -> String {
+		e.to_string()
+	}
+}
+
+// What the parser reads: text, or bytes that are checked to be UTF-8 as the parser reaches
+// them, so that a large input isn't read once just for that before it is parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input<'a> {
+	Text(&'a str),
+	Bytes(&'a [u8]),
+}
+
+impl<'a> Input<'a> {
+	fn as_bytes(&self) -> &'a [u8] {
+		match self {
+			Input::Text(s) => s.as_bytes(),
+			Input::Bytes(b) => b,
 		}
 	}
 
-	if first_char.is_none() {
-		return Err(String::from("Reached EOF while parsing JSON number"));
+	// Text is cut at char boundaries, or panics; callers cut at ASCII characters.
+	fn slice(&self, start: usize, end: usize) -> Input<'a> {
+		match self {
+			Input::Text(s) => Input::Text(&s[start..end]),
+			Input::Bytes(b) => Input::Bytes(&b[start..end]),
+		}
 	}
-	let first_char = first_char.unwrap();
 
-	let is_minus = first_char == '-';
+	// Whitespace-only, as `str::trim` sees it. Bytes are only decoded for the rare lines that
+	// start with a character that may be whitespace but isn't ASCII whitespace.
+	fn is_blank(&self) -> bool {
+		let bytes = self.as_bytes();
+		match bytes.iter().position(|b| !b.is_ascii_whitespace()) {
+			None => true,
+			Some(i) if bytes[i] == 0x0b || bytes[i] >= 0x80 => match self {
+				Input::Text(s) => s.trim().is_empty(),
+				Input::Bytes(b) => std::str::from_utf8(b).is_ok_and(|s| s.trim().is_empty()),
+			},
+			Some(_) => false,
+		}
+	}
+}
+
+impl<'a> From<&'a str> for Input<'a> {
+	fn from(json: &'a str) -> Input<'a> {
+		Input::Text(json)
+	}
+}
 
-	let mut int_nums = Vec::new();
-	let mut float_nums = Vec::new();
-	let mut fp = false;
+// Bytes are checked for UTF-8 this many at a time, a little ahead of the parser.
+const UTF8_BLOCK: usize = 1 << 16;
 
-	if first_char.is_ascii_digit() {
-		int_nums.push(first_char.to_digit(10).unwrap());
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JsonLexer<'a> {
+	input: Input<'a>,
+	bytes: &'a [u8],
+	ptr: usize,
+	settings: ParseSetting,
+	// Set when parsing stopped at a limit, so the caller can report it as such.
+	limit_error: Option<(LimitKind, usize)>,
+	// The input is known to be UTF-8 up to this offset, which text starts at its end.
+	checked: Cell<usize>,
+	// Where the input stopped being UTF-8, once the parser got there.
+	invalid_utf8: Cell<Option<usize>>,
+}
+
+impl<'a> JsonLexer<'a> {
+	fn new(json: impl Into<Input<'a>>, settings: ParseSetting) -> JsonLexer<'a> {
+		let input = json.into();
+		let checked = match input {
+			Input::Text(s) => s.len(),
+			Input::Bytes(_) => 0,
+		};
+		JsonLexer {input, bytes: input.as_bytes(), ptr: 0, settings, limit_error: None, checked: Cell::new(checked), invalid_utf8: Cell::new(None)}
 	}
 
-	let err = None;
-	while let Some(ch) = json.next() {
-		if ch.is_whitespace() {
-			// End of JSON number
-			break;
-		} else if ch.is_ascii_digit() {
-			let num = ch.to_digit(10).unwrap();
-			if !fp {
-				int_nums.push(num);
+	fn check_limit(&mut self, kind: LimitKind, limit: Option<usize>, value: usize) -> Result<(), String> {
+		match limit {
+			Some(limit) if value > limit => {
+				self.limit_error = Some((kind, limit));
+				Err(ParseError::LimitExceeded(kind, limit).to_string())
+			},
+			_ => Ok(()),
+		}
+	}
+
+	#[allow(dead_code)]
+	fn ptr(&mut self) -> usize {
+		self.ptr
+	}
+
+	// `ptr` is a byte offset that always sits on a char boundary.
+	fn next(&mut self) -> Option<char> {
+		let ch = self.peek()?;
+		self.ptr += ch.len_utf8();
+		Some(ch)
+	}
+
+	fn back(&mut self) {
+		self.ptr -= 1;
+		while !self.is_char_boundary(self.ptr) {
+			self.ptr -= 1;
+		}
+	}
+
+	// None at the end of the input, and where it stops being UTF-8.
+	fn peek(&self) -> Option<char> {
+		let &b = self.bytes.get(self.ptr)?;
+		if b < 0x80 {
+			return Some(b as char);
+		}
+		let len = match b {
+			0xc0..=0xdf => 2,
+			0xe0..=0xef => 3,
+			_ => 4,
+		};
+		self.text(self.ptr, (self.ptr + len).min(self.bytes.len())).ok()?.chars().next()
+	}
+
+	fn is_char_boundary(&self, i: usize) -> bool {
+		// Not a continuation byte, 0b10xx_xxxx
+		self.bytes.get(i).map_or(i == self.bytes.len(), |&b| (b as i8) >= -0x40)
+	}
+
+	// Extends the checked part of the input to at least `end`, a block at a time, and returns
+	// whether all of it up to `end` is UTF-8.
+	fn check_utf8(&self, end: usize) -> bool {
+		while self.checked.get() < end {
+			let checked = self.checked.get();
+			let stop = end.max(checked + UTF8_BLOCK).min(self.bytes.len());
+			match std::str::from_utf8(&self.bytes[checked..stop]) {
+				Ok(_) => self.checked.set(stop),
+				Err(e) => {
+					let valid = checked + e.valid_up_to();
+					self.checked.set(valid);
+					// A character cut at the end of a block is checked with the next one
+					if e.error_len().is_some() || stop == self.bytes.len() {
+						if valid < end {
+							self.invalid_utf8.set(Some(valid));
+						}
+						return valid >= end;
+					}
+				},
+			}
+		}
+		true
+	}
+
+	// The input from `start` to `end`, both on char boundaries.
+	fn text(&self, start: usize, end: usize) -> Result<&'a str, String> {
+		let bytes = match self.input {
+			Input::Text(s) => return Ok(&s[start..end]),
+			Input::Bytes(b) => b,
+		};
+		if !self.check_utf8(end) {
+			return Err(utf8_error(self.invalid_utf8.get().unwrap_or(end)));
+		}
+		// Safety: `check_utf8` only moves `checked` past input that is UTF-8. Slicing the text
+		// then checks that `start` and `end` are char boundaries.
+		let checked = unsafe { std::str::from_utf8_unchecked(&bytes[..self.checked.get()]) };
+		Ok(&checked[start..end])
+	}
+
+	// Skips whitespace, and comments in JSON5 and JSONC mode.
+	fn skip_whitespace(&mut self) -> Result<(), String> {
+		let comments = self.settings.json5 || self.settings.jsonc;
+		while let Some(ch) = self.peek() {
+			if ch.is_whitespace() {
+				self.next();
+			} else if ch == '/' && comments && self.bytes[self.ptr..].starts_with(b"//") {
+				let end = match self.bytes[self.ptr..].iter().position(|&b| b == b'\n') {
+					Some(end) => self.ptr + end,
+					None => self.bytes.len(),
+				};
+				self.text(self.ptr, end)?;
+				self.ptr = end;
+			} else if ch == '/' && comments && self.bytes[self.ptr..].starts_with(b"/*") {
+				let end = match self.bytes[self.ptr + 2..].windows(2).position(|w| w == b"*/") {
+					Some(end) => self.ptr + end + 4,
+					None => return Err("Reached EOF while parsing comment".to_string()),
+				};
+				self.text(self.ptr, end)?;
+				self.ptr = end;
 			} else {
-				float_nums.push(num);
+				break;
 			}
-		} else if ch == '.' {
-			fp = true;
+		}
+		Ok(())
+	}
+
+	fn next_token(&mut self) -> Result<Option<char>, String> {
+		self.skip_whitespace()?;
+		Ok(self.next())
+	}
+
+	fn peek_token(&mut self) -> Result<Option<char>, String> {
+		self.skip_whitespace()?;
+		Ok(self.peek())
+	}
+
+	#[allow(dead_code)]
+	fn slice(&self, start: usize, end: Option<usize>) -> Option<&'a str> {
+		let end = end.unwrap_or(self.bytes.len());
+		if end <= self.bytes.len() && start <= end {
+			self.text(start, end).ok()
 		} else {
-			json.back();
-			break;
+			None
+		}
+	}
+
+	#[allow(dead_code)]
+	fn reset(&mut self) {
+		self.ptr = 0;
+	}
+}
+
+// Builds the tree for a document as the parser reads it: the owned `JsonElement`, a
+// `JsonValueRef` borrowing from the input, or a `JsonArena`. Containers are opened, filled
+// one member at a time and then closed into a value.
+trait ParseTree<'a> {
+	type Value;
+	type Array;
+	type Object;
+
+	fn string(&mut self, s: Cow<'a, str>) -> Self::Value;
+	fn number(&mut self, n: JsonNum) -> Self::Value;
+	fn null(&mut self) -> Self::Value;
+	fn bool(&mut self, b: bool) -> Self::Value;
+	fn array(&mut self) -> Self::Array;
+	// Returns the number of elements so far.
+	fn push(&mut self, array: &mut Self::Array, value: Self::Value) -> usize;
+	fn close_array(&mut self, array: Self::Array) -> Self::Value;
+	fn object(&mut self) -> Self::Object;
+	// Returns the number of members so far. A repeated key replaces the earlier member.
+	fn insert(&mut self, object: &mut Self::Object, key: Cow<'a, str>, value: Self::Value) -> usize;
+	fn close_object(&mut self, object: Self::Object) -> Self::Value;
+}
+
+struct ElementTree;
+
+impl<'a> ParseTree<'a> for ElementTree {
+	type Value = JsonElement;
+	type Array = Vec<JsonElement>;
+	type Object = HashMap<String, JsonElement>;
+
+	fn string(&mut self, s: Cow<'a, str>) -> JsonElement {
+		JsonElement::JsonString(s.into_owned())
+	}
+
+	fn number(&mut self, n: JsonNum) -> JsonElement {
+		JsonElement::JsonNumber(n)
+	}
+
+	fn null(&mut self) -> JsonElement {
+		JsonElement::JsonNull
+	}
+
+	fn bool(&mut self, b: bool) -> JsonElement {
+		JsonElement::JsonBool(b)
+	}
+
+	fn array(&mut self) -> Vec<JsonElement> {
+		Vec::new()
+	}
+
+	fn push(&mut self, array: &mut Vec<JsonElement>, value: JsonElement) -> usize {
+		array.push(value);
+		array.len()
+	}
+
+	fn close_array(&mut self, array: Vec<JsonElement>) -> JsonElement {
+		JsonElement::JsonArray(array)
+	}
+
+	fn object(&mut self) -> HashMap<String, JsonElement> {
+		HashMap::new()
+	}
+
+	fn insert(&mut self, object: &mut HashMap<String, JsonElement>, key: Cow<'a, str>, value: JsonElement) -> usize {
+		object.insert(key.into_owned(), value);
+		object.len()
+	}
+
+	fn close_object(&mut self, object: HashMap<String, JsonElement>) -> JsonElement {
+		JsonElement::JsonObject(object)
+	}
+}
+
+// Builds nothing, for `validate_json`. The keys of open objects are only kept when members
+// are limited, so that a repeated key counts once as it does in the other trees.
+struct CheckTree {
+	count_keys: bool,
+}
+
+impl<'a> ParseTree<'a> for CheckTree {
+	type Value = ();
+	type Array = usize;
+	type Object = HashSet<Cow<'a, str>>;
+
+	fn string(&mut self, _: Cow<'a, str>) {}
+
+	fn number(&mut self, _: JsonNum) {}
+
+	fn null(&mut self) {}
+
+	fn bool(&mut self, _: bool) {}
+
+	fn array(&mut self) -> usize {
+		0
+	}
+
+	fn push(&mut self, array: &mut usize, _: ()) -> usize {
+		*array += 1;
+		*array
+	}
+
+	fn close_array(&mut self, _: usize) {}
+
+	fn object(&mut self) -> HashSet<Cow<'a, str>> {
+		HashSet::new()
+	}
+
+	fn insert(&mut self, object: &mut HashSet<Cow<'a, str>>, key: Cow<'a, str>, _: ()) -> usize {
+		if self.count_keys {
+			object.insert(key);
 		}
+		object.len()
 	}
 
-	match err {
-		Some(e) => e,
-		None => {
-			let jn = if !fp {
-				let mut intnum: i64 = 0;
-				for (i, n) in int_nums.iter().enumerate() {
-					if i != 0 { intnum *= 10; }
-					intnum += *n as i64;
+	fn close_object(&mut self, _: HashSet<Cow<'a, str>>) {}
+}
+
+// An array or object whose closing bracket hasn't been read yet.
+enum ParseFrame<'a, T: ParseTree<'a>> {
+	Array(T::Array),
+	// Members so far, and the key of the member whose value is being parsed.
+	Object(T::Object, Cow<'a, str>),
+}
+
+// Open containers are kept on an explicit stack rather than the call stack, so nesting
+// depth is bounded only by memory and `ParseLimits::max_depth`.
+fn parse_json<'a, T: ParseTree<'a>>(json: &mut JsonLexer<'a>, tree: &mut T) -> Result<T::Value, String> {
+	let mut stack: Vec<ParseFrame<T>> = Vec::new();
+
+	loop {
+		let ch = match json.next_token()? {
+			Some(ch) => ch,
+			None => return Err("cannot parse JSON".to_string()),
+		};
+
+		let mut value = match ch {
+			'{' | '[' => {
+				json.check_limit(LimitKind::Depth, json.settings.limits.max_depth, stack.len() + 1)?;
+				let close = if ch == '{' { '}' } else { ']' };
+				if json.peek_token()? == Some(close) {
+					json.next();
+					if ch == '{' {
+						let object = tree.object();
+						tree.close_object(object)
+					} else {
+						let array = tree.array();
+						tree.close_array(array)
+					}
+				} else {
+					stack.push(if ch == '{' {
+						ParseFrame::Object(tree.object(), parse_json_object_key(json)?)
+					} else {
+						ParseFrame::Array(tree.array())
+					});
+					continue;
 				}
-				if is_minus {
-					intnum = -intnum;
+			},
+			'"' => tree.string(parse_json_string(json, '"')?),
+			'\'' if json.settings.json5 => tree.string(parse_json_string(json, '\'')?),
+			'n' => {
+				json.back();
+				parse_json_null(json)?;
+				tree.null()
+			},
+			't' | 'f' => {
+				json.back();
+				let b = parse_json_bool(json)?;
+				tree.bool(b)
+			},
+			_ => {
+				json.back();
+				let n = parse_json_number(json)?;
+				tree.number(n)
+			},
+		};
+
+		// Add the finished value to its container, closing every container that ends here.
+		loop {
+			let (len, close) = match stack.last_mut() {
+				None => return Ok(value),
+				Some(ParseFrame::Array(elems)) => (tree.push(elems, value), ']'),
+				Some(ParseFrame::Object(pairs, key)) => (tree.insert(pairs, std::mem::take(key), value), '}'),
+			};
+			json.check_limit(LimitKind::Members, json.settings.limits.max_members, len)?;
+
+			if !parse_json_separator(json, close)? {
+				if let Some(ParseFrame::Object(_, key)) = stack.last_mut() {
+					*key = parse_json_object_key(json)?;
 				}
-				JsonNum::Integer(intnum)
+				break;
+			}
+			value = match stack.pop().unwrap() {
+				ParseFrame::Array(elems) => tree.close_array(elems),
+				ParseFrame::Object(pairs, _) => tree.close_object(pairs),
+			};
+		}
+	}
+}
+
+// Reads what follows an array element or object member. Returns true if it closed the container.
+fn parse_json_separator(json: &mut JsonLexer, close: char) -> Result<bool, String> {
+	match json.next_token()? {
+		Some(',') => {
+			// Trailing comma
+			if json.settings.json5 && json.peek_token()? == Some(close) {
+				json.next();
+				Ok(true)
 			} else {
-				let mut intnum = 0;
-				for (i, n) in int_nums.iter().enumerate() {
-					if i != 0 { intnum *= 10; }
-					intnum += *n as i64;
-				}
-				let mut floatnum = 0.0;
-				for n in &float_nums {
-					floatnum += *n as f64;
-					floatnum /= 10.0;
-				}
-				let mut total = (intnum as f64) + floatnum;
-				if is_minus {
-					total = -total;
+				Ok(false)
+			}
+		},
+		Some(ch) if ch == close => Ok(true),
+		Some(ch) => Err(format!("Expected ',' or '{}', got {}", close, ch)),
+		None if close == '}' => Err(String::from("Reached EOF while parsing JSON object")),
+		None => Err(String::from("Reached EOF while parsing JSON array")),
+	}
+}
+
+// Parses an object key and the colon after it.
+fn parse_json_object_key<'a>(json: &mut JsonLexer<'a>) -> Result<Cow<'a, str>, String> {
+	// Parse key (string)
+	let key = match json.next_token()? {
+		Some('"') => parse_json_string(json, '"')?,
+		Some('\'') if json.settings.json5 => parse_json_string(json, '\'')?,
+		Some(ch) if json.settings.json5 && is_identifier_start(ch) => {
+			json.back();
+			let key = parse_json_identifier(json)?;
+			json.check_limit(LimitKind::StringLength, json.settings.limits.max_string_length, key.len())?;
+			Cow::Borrowed(key)
+		},
+		Some(ch) => return Err(format!("Expected '\"', got {}", ch)),
+		None => return Err(String::from("Reached EOF while parsing JSON object")),
+	};
+
+	// expect colon
+	match json.next_token()? {
+		Some(':') => {},
+		Some(ch) => return Err(format!("Expected ':', got {}", ch)),
+		None => return Err(String::from("Reached EOF while parsing JSON object")),
+	}
+
+	Ok(key)
+}
+
+fn is_identifier_start(ch: char) -> bool {
+	ch.is_alphabetic() || ch == '_' || ch == '$'
+}
+
+fn parse_json_identifier<'a>(json: &mut JsonLexer<'a>) -> Result<&'a str, String> {
+	let start = json.ptr;
+	while let Some(ch) = json.next() {
+		if !(ch.is_alphanumeric() || ch == '_' || ch == '$') {
+			json.back();
+			break;
+		}
+	}
+	json.text(start, json.ptr)
+}
+
+// Parses the rest of a string literal whose opening quote has been consumed. Strings
+// without escapes are borrowed from the input.
+fn parse_json_string<'a>(json: &mut JsonLexer<'a>, quote: char) -> Result<Cow<'a, str>, String> {
+	let mut buffer: Option<String> = None;
+
+	loop {
+		// Copy the run up to the next quote or backslash in one go
+		let end = scan::find_string_end(&json.bytes[json.ptr..], quote as u8);
+		let len = buffer.as_ref().map_or(0, |b| b.len()) + end;
+		json.check_limit(LimitKind::StringLength, json.settings.limits.max_string_length, len)?;
+		let run = json.text(json.ptr, json.ptr + end)?;
+		json.ptr += end;
+
+		let ch = json.next();
+		if ch == Some(quote) {
+			// End of JSON string
+			return Ok(match buffer {
+				None => Cow::Borrowed(run),
+				Some(mut buffer) => {
+					buffer.push_str(run);
+					Cow::Owned(buffer)
+				},
+			});
+		}
+
+		let buffer = buffer.get_or_insert_with(String::new);
+		buffer.push_str(run);
+		let ch = match (ch, json.next()) {
+			(Some(_), Some(ch)) => ch,
+			_ => return Err(format!("Reached EOF while parsing JSON string. string: {}", buffer)),
+		};
+		match ch {
+			'"' | '\\' | '/' => buffer.push(ch),
+			'b' => buffer.push('\u{08}'),
+			'f' => buffer.push('\u{0c}'),
+			'n' => buffer.push('\n'),
+			'r' => buffer.push('\r'),
+			't' => buffer.push('\t'),
+			'u' => buffer.push(parse_json_unicode_escape(json)?),
+			_ if json.settings.json5 => parse_json5_escape(json, ch, buffer)?,
+			_ => return Err(format!("Invalid escape sequence \\{}", ch)),
+		}
+		json.check_limit(LimitKind::StringLength, json.settings.limits.max_string_length, buffer.len())?;
+	}
+}
+
+fn parse_json5_escape(json: &mut JsonLexer, ch: char, buffer: &mut String) -> Result<(), String> {
+	match ch {
+		'\'' => buffer.push('\''),
+		'v' => buffer.push('\u{0b}'),
+		'0' if !json.peek().is_some_and(|c| c.is_ascii_digit()) => buffer.push('\0'),
+		'x' => {
+			let mut code = 0;
+			for _ in 0..2 {
+				match json.next().and_then(|c| c.to_digit(16)) {
+					Some(d) => code = code * 16 + d,
+					None => return Err("Invalid \\x escape in JSON string".to_string()),
 				}
-				JsonNum::Double(total)
+			}
+			buffer.push(std::char::from_u32(code).unwrap());
+		},
+		// Line continuation
+		'\n' | '\u{2028}' | '\u{2029}' => {},
+		'\r' => {
+			if json.peek() == Some('\n') {
+				json.next();
+			}
+		},
+		c if c.is_ascii_digit() => return Err(format!("Invalid escape sequence \\{}", c)),
+		c => buffer.push(c),
+	}
+	Ok(())
+}
 
-			};
+fn parse_json_hex4(json: &mut JsonLexer) -> Result<u32, String> {
+	let mut code = 0;
+	for _ in 0..4 {
+		match json.next().and_then(|ch| ch.to_digit(16)) {
+			Some(d) => code = code * 16 + d,
+			None => return Err("Invalid \\u escape in JSON string".to_string()),
+		}
+	}
+	Ok(code)
+}
+
+fn parse_json_unicode_escape(json: &mut JsonLexer) -> Result<char, String> {
+	let high = parse_json_hex4(json)?;
+	let code = if (0xD800..0xDC00).contains(&high) {
+		// Surrogate pair: the low half must follow as another \u escape
+		if json.next() != Some('\\') || json.next() != Some('u') {
+			return Err("Unpaired surrogate in JSON string".to_string());
+		}
+		let low = parse_json_hex4(json)?;
+		if !(0xDC00..0xE000).contains(&low) {
+			return Err("Unpaired surrogate in JSON string".to_string());
+		}
+		0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+	} else {
+		high
+	};
+	std::char::from_u32(code).ok_or_else(|| "Unpaired surrogate in JSON string".to_string())
+}
 
-			Ok(JsonElement::JsonNumber(jn))
+fn parse_json_number(json: &mut JsonLexer) -> Result<JsonNum, String> {
+	let start = json.ptr;
+	while let Some(ch) = json.peek() {
+		if ch.is_ascii_alphanumeric() || ch == '+' || ch == '-' || ch == '.' {
+			json.next();
+		} else {
+			break;
 		}
 	}
+	let text = json.text(start, json.ptr)?;
+
+	if text.is_empty() {
+		return match json.peek() {
+			Some(ch) => Err(format!("Unexpected character {}", ch)),
+			None => Err(String::from("Reached EOF while parsing JSON number")),
+		};
+	}
 
+	parse_number_text(text, json.settings).ok_or_else(|| format!("Invalid JSON number {}", text))
 }
 
-fn parse_json_array(json: &mut JsonLexer) -> Result<JsonElement, String> {
-	let mut elems = Vec::new();
+fn parse_number_text(text: &str, settings: ParseSetting) -> Option<JsonNum> {
+	let (is_minus, body) = match text.as_bytes()[0] {
+		b'-' => (true, &text[1..]),
+		b'+' if settings.json5 => (false, &text[1..]),
+		_ => (false, text),
+	};
+	let sign = if is_minus { -1.0 } else { 1.0 };
 
-	// (elem ,)*
-	let mut res = None;
-	'top: loop {
-		while let Some(ch) = json.next() {
-			if ch.is_whitespace() {
-				continue;
-			} else if ch == ']' {
-				break 'top;
-			} else {
-				json.back();
-				break;
+	if settings.json5 {
+		if body == "Infinity" {
+			return Some(JsonNum::Double(sign * f64::INFINITY));
+		}
+		if body == "NaN" {
+			return Some(JsonNum::Double(f64::NAN));
+		}
+		if let Some(hex) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+			if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+				return None;
 			}
+			return Some(match i64::from_str_radix(hex, 16) {
+				Ok(n) if is_minus => JsonNum::Integer(-n),
+				Ok(n) => JsonNum::Integer(n),
+				Err(_) => JsonNum::Double(sign * u128::from_str_radix(hex, 16).ok()? as f64),
+			});
 		}
+	}
 
-		let elem = parse_json(json)?;
-		//println!("ARRAY ELM {:?}", &elem);
-		elems.push(elem);
+	// int frac? exp?
+	let exp_at = body.find(['e', 'E']);
+	let (mantissa, exp) = match exp_at {
+		Some(i) => (&body[..i], Some(&body[i + 1..])),
+		None => (body, None),
+	};
+	let (int, frac) = match mantissa.find('.') {
+		Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+		None => (mantissa, None),
+	};
 
-		while let Some(ch) = json.next() {
-			if ch.is_whitespace() {
-				continue;
-			} else if ch == ',' {
-				break;
-			} else if ch == ']' {
-				break 'top;
-			} else {
-				res = Some(Err(format!("Expected ',' or ']', got {}", ch)));
-				break 'top;
-			}
+	let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+	let int_ok = match frac {
+		// JSON5 allows ".5" and "5."
+		Some(f) if settings.json5 => is_digits(int) && (!int.is_empty() || !f.is_empty()),
+		_ => !int.is_empty() && is_digits(int),
+	};
+	let int_ok = int_ok && (int.len() <= 1 || !int.starts_with('0'));
+	let frac_ok = match frac {
+		Some(f) => is_digits(f) && (settings.json5 || !f.is_empty()),
+		None => true,
+	};
+	let exp_ok = match exp {
+		Some(e) => {
+			let digits = e.strip_prefix('+').or_else(|| e.strip_prefix('-')).unwrap_or(e);
+			!digits.is_empty() && is_digits(digits)
+		},
+		None => true,
+	};
+	if !(int_ok && frac_ok && exp_ok) {
+		return None;
+	}
+
+	if settings.arbitrary_precision {
+		// Spell JSON5-only forms like "+5", ".5" and "5." as strict JSON
+		let mut lexeme = String::from(if is_minus { "-" } else { "" });
+		lexeme.push_str(if int.is_empty() { "0" } else { int });
+		if let Some(f) = frac.filter(|f| !f.is_empty()) {
+			lexeme.push('.');
+			lexeme.push_str(f);
+		}
+		if let (Some(i), Some(e)) = (exp_at, exp) {
+			lexeme.push_str(&body[i..i + 1]);
+			lexeme.push_str(e);
 		}
+		return Some(JsonNum::Decimal(lexeme));
 	}
 
-	match res {
-		Some(r) => r,
-		None => Ok(JsonElement::JsonArray(elems)),
+	if frac.is_none() && exp.is_none() {
+		if let Ok(n) = text.trim_start_matches('+').parse::<i64>() {
+			return Some(JsonNum::Integer(n));
+		}
 	}
+	// Rust's float parser does not accept an empty integer or fraction part
+	let normalized = format!("{}{}.{}e{}", if is_minus { "-" } else { "" }, if int.is_empty() { "0" } else { int }, match frac {
+		Some(f) if !f.is_empty() => f,
+		_ => "0",
+	}, exp.unwrap_or("0"));
+	normalized.parse::<f64>().ok().map(JsonNum::Double)
 }
 
-fn parse_json_null(json: &mut JsonLexer) -> Result<JsonElement, String> {
+fn parse_json_null(json: &mut JsonLexer) -> Result<(), String> {
 	let errmsg = "Error while parsing JSON null.";
 	let eofmsg = "Reached EOF while parsing JSON null";
 
@@ -718,10 +1709,10 @@ fn parse_json_null(json: &mut JsonLexer) -> Result<JsonElement, String> {
 	check_char!('l');
 	check_char!('l');
 
-	Ok(JsonElement::JsonNull)
+	Ok(())
 }
 
-fn parse_json_bool(json: &mut JsonLexer) -> Result<JsonElement, String> {
+fn parse_json_bool(json: &mut JsonLexer) -> Result<bool, String> {
 	let first_char = match json.next() {
 		Some(ch) => ch,
 		None => return Err("Reached EOF while parsing JSON bool".to_string()),
@@ -746,21 +1737,78 @@ fn parse_json_bool(json: &mut JsonLexer) -> Result<JsonElement, String> {
 		check_char!('r');
 		check_char!('u');
 		check_char!('e');
-		Ok(JsonElement::JsonBool(true))
+		Ok(true)
 	} else if first_char == 'f' {
 		check_char!('a');
 		check_char!('l');
 		check_char!('s');
 		check_char!('e');
-		Ok(JsonElement::JsonBool(false))
+		Ok(false)
 	} else {
 		Err("Error while parsing JSON bool".to_string())
 	}
 }
 
 pub fn build_json_graph(json: &str) -> Result<JsonElement, String> {
-	let mut lexer = JsonLexer::new(json);
-	parse_json(&mut lexer)
+	build_json_graph_with_setting(json, ParseSetting::default())
+}
+
+pub fn build_json_graph_with_setting(json: &str, settings: ParseSetting) -> Result<JsonElement, String> {
+	try_build_json_graph(json, settings).map_err(String::from)
+}
+
+// Like `build_json_graph_with_setting`, but tells limit violations apart from syntax errors.
+pub fn try_build_json_graph(json: &str, settings: ParseSetting) -> Result<JsonElement, ParseError> {
+	parse_document(json, settings, false, &mut ElementTree)
+}
+
+// Checks that `json` is one document, followed by nothing but whitespace (and comments in
+// JSON5 and JSONC mode). Unlike the parse functions, which ignore text after the first value,
+// this is fit for checking input that is passed on as-is. Nothing is built from the input.
+pub fn validate_json(json: &str, settings: ParseSetting) -> Result<(), ParseError> {
+	let mut tree = CheckTree { count_keys: settings.limits.max_members.is_some() };
+	parse_document(json, settings, true, &mut tree)
+}
+
+// Like `validate_json`, for input that may not be UTF-8, such as the contents of a file. It is
+// checked for UTF-8 as the parser goes rather than in a pass of its own, and returned as text.
+pub fn validate_json_bytes(json: &[u8], settings: ParseSetting) -> Result<&str, ParseError> {
+	let mut tree = CheckTree { count_keys: settings.limits.max_members.is_some() };
+	let mut lexer = JsonLexer::new(Input::Bytes(json), settings);
+	parse_lexed(&mut lexer, true, &mut tree)?;
+	lexer.text(0, json.len()).map_err(ParseError::Syntax)
+}
+
+// Text after the first value is ignored unless `whole` is set, in which case only whitespace
+// (and comments in JSON5 and JSONC mode) may follow it.
+fn parse_document<'a, T: ParseTree<'a>>(json: impl Into<Input<'a>>, settings: ParseSetting, whole: bool, tree: &mut T) -> Result<T::Value, ParseError> {
+	parse_lexed(&mut JsonLexer::new(json, settings), whole, tree)
+}
+
+fn parse_lexed<'a, T: ParseTree<'a>>(lexer: &mut JsonLexer<'a>, whole: bool, tree: &mut T) -> Result<T::Value, ParseError> {
+	let (settings, len) = (lexer.settings, lexer.bytes.len());
+	let result = lexer.check_limit(LimitKind::InputSize, settings.limits.max_input_size, len)
+		.and_then(|_| parse_json(lexer, tree))
+		.and_then(|value| {
+			if !whole {
+				// What follows the value is not read, not even to skip comments, but must still be text
+				lexer.check_utf8(len);
+			} else if let Some(ch) = lexer.peek_token()? {
+				return Err(format!("Unexpected character {} after JSON value", ch));
+			}
+			Ok(value)
+		});
+	// Bytes that aren't UTF-8 read as the end of the input, so whatever the parser made of
+	// them is replaced by the real reason
+	match (result, lexer.limit_error, lexer.invalid_utf8.get()) {
+		(Err(_), Some((kind, limit)), _) => Err(ParseError::LimitExceeded(kind, limit)),
+		(_, _, Some(at)) => Err(ParseError::Syntax(utf8_error(at))),
+		(result, _, None) => result.map_err(ParseError::Syntax),
+	}
+}
+
+pub(crate) fn utf8_error(at: usize) -> String {
+	format!("Invalid UTF-8 at byte {}", at)
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -802,16 +1850,12 @@ impl Default for DiffSetting {
 }
 
 pub fn structure_diff(base_json: &str, compared_json: &str, settings: DiffSetting) -> Result<Vec<JsonDiff>, String> {
-	let base_g = build_json_graph(base_json);
-	if base_g.is_err() {
-		return Err(base_g.unwrap_err().to_string());
-	}
-	let base_g = base_g.unwrap();
-	let compared_g = build_json_graph(compared_json);
-	if compared_g.is_err() {
-		return Err(compared_g.unwrap_err().to_string());
-	}
-	let compared_g = compared_g.unwrap();
+	structure_diff_with_setting(base_json, compared_json, ParseSetting::default(), settings)
+}
+
+pub fn structure_diff_with_setting(base_json: &str, compared_json: &str, parse_settings: ParseSetting, settings: DiffSetting) -> Result<Vec<JsonDiff>, String> {
+	let base_g = build_json_graph_with_setting(base_json, parse_settings)?;
+	let compared_g = build_json_graph_with_setting(compared_json, parse_settings)?;
 
 	Ok(element_diff(&base_g, &compared_g, "", settings))
 }
@@ -820,175 +1864,189 @@ fn element_diff(base_el: &JsonElement, compared_el: &JsonElement, base_path: &st
 	use JsonElement::*;
 	use DiffType::*;
 
+	// Work is kept on an explicit stack so that deep documents don't overflow the call stack.
+	// `Compare` holds the length of its parent's path and its own key, if it is an object
+	// member; since tasks are handled depth-first, the parent's path is always a prefix of
+	// `path` when the task is popped.
+	enum Task<'a> {
+		Compare(&'a JsonElement, &'a JsonElement, usize, Option<&'a str>),
+		Emit(JsonDiff),
+	}
+
 	let path_sec = "::";
+	let mut path = base_path.to_string();
+	let mut diffs = Vec::new();
+	let mut tasks = vec![Task::Compare(base_el, compared_el, path.len(), None)];
+
+	while let Some(task) = tasks.pop() {
+		let (base_el, compared_el) = match task {
+			Task::Emit(diff) => {
+				diffs.push(diff);
+				continue;
+			},
+			Task::Compare(b, c, parent_len, key) => {
+				path.truncate(parent_len);
+				if let Some(key) = key {
+					path.push_str(path_sec);
+					path.push_str(key);
+				}
+				(b, c)
+			},
+		};
 
-	// Helper function
-	let make_json_type_diff = |a: &JsonElement, b: &JsonElement| -> Vec<JsonDiff> {
-		vec!(JsonDiff {
+		let modified = |from: String, to: String| JsonDiff {
 			diff_type: Modified,
-			from_desc: Some(format!("{:?}", a)),
-			to_desc: Some(format!("{:?}", b)),
-			base_path: base_path.to_string(),
-		})
-	};
-
-	match base_el {
-		JsonNumber(base_num) => {
-			match compared_el {
-				JsonNumber(compared_num) => {
-					let is_equal_num = base_num.is_equal(compared_num, settings.float_diff_threashold);
+			from_desc: Some(from),
+			to_desc: Some(to),
+			base_path: path.clone(),
+		};
 
-					if is_equal_num {
-						Vec::new()
-					} else {
-						vec!(JsonDiff {
-							diff_type: Modified,
-							from_desc: Some(format!("{}", base_num)),
-							to_desc: Some(format!("{}", compared_num)),
-							base_path: base_path.to_string(),
-						})
-					}
-				},
-				_ => {  // Number vs (other than Number)
-					make_json_type_diff(base_el, compared_el)
+		// Children in document order; they are pushed reversed so the first is popped first.
+		let mut children = Vec::new();
+		match (base_el, compared_el) {
+			(JsonNumber(base_num), JsonNumber(compared_num)) => {
+				if !base_num.is_equal(compared_num, settings.float_diff_threashold) {
+					diffs.push(modified(base_num.to_string(), compared_num.to_string()));
 				}
-			}
-		},
-
-		JsonString(base_str) => {
-			match compared_el {
-				JsonString(compared_str) => {
-					if base_str == compared_str {
-						Vec::new()
-					} else {
-						vec!(JsonDiff {
-							diff_type: Modified,
-							from_desc: Some(base_str.to_string()),
-							to_desc: Some(compared_str.to_string()),
-							base_path: base_path.to_string(),
-						})
+			},
+			(JsonString(base_str), JsonString(compared_str)) => {
+				if base_str != compared_str {
+					diffs.push(modified(base_str.to_string(), compared_str.to_string()));
+				}
+			},
+			(JsonArray(base_vec), JsonArray(compared_vec)) => {
+				let common = base_vec.len().min(compared_vec.len());
+				for (b_elm, c_elm) in base_vec[..common].iter().zip(&compared_vec[..common]) {
+					children.push(Task::Compare(b_elm, c_elm, path.len(), None));
+				}
+				for new_elm in &compared_vec[common..] {
+					children.push(Task::Emit(JsonDiff {
+						diff_type: Added,
+						from_desc: None,
+						to_desc: Some(debug_string(new_elm)),
+						base_path: path.clone(),
+					}));
+				}
+				for del_elm in &base_vec[common..] {
+					children.push(Task::Emit(JsonDiff {
+						diff_type: Deleted,
+						from_desc: Some(debug_string(del_elm)),
+						to_desc: None,
+						base_path: path.clone(),
+					}));
+				}
+			},
+			(JsonObject(base_obj), JsonObject(compared_obj)) => {
+				for (bk, bv) in base_obj.iter() {
+					match compared_obj.get(bk) {
+						Some(cv) => children.push(Task::Compare(bv, cv, path.len(), Some(bk.as_str()))),
+						None => children.push(Task::Emit(JsonDiff {
+							diff_type: Deleted,
+							from_desc: Some(debug_string(bv)),
+							to_desc: None,
+							base_path: format!("{}{}{}", path, path_sec, bk),
+						})),
 					}
-				},
-				_ => {
-					make_json_type_diff(base_el, compared_el)
 				}
-			}
-		},
 
-		JsonArray(base_vec) => {
-			match compared_el {
-				JsonArray(compared_vec) => {
-					let mut diffs = Vec::new();
-					if base_vec.len() == compared_vec.len() {
-						for (b_elm, c_elm) in base_vec.iter().zip(compared_vec.iter()) {
-							diffs.extend_from_slice(&element_diff(&b_elm, &c_elm, base_path, settings));
-						}
-					} else if base_vec.len() < compared_vec.len() {
-						let last = base_vec.len();
-						for (i, b_elm) in base_vec.iter().enumerate() {
-							diffs.extend_from_slice(&element_diff(&b_elm, &compared_vec[i], base_path, settings));
-						}
-						for new_elm in &compared_vec[last..] {
-							diffs.push(JsonDiff {
-								diff_type: Added,
-								from_desc: None,
-								to_desc: Some(format!("{:?}", new_elm)),
-								base_path: base_path.to_string(),
-							})
-						}
-					} else {
-						let last = compared_vec.len();
-						for (i, c_elm) in compared_vec.iter().enumerate() {
-							diffs.extend_from_slice(&element_diff(&base_vec[i], &c_elm, base_path, settings));
-						}
-						for del_elm in &base_vec[last..] {
-							diffs.push(JsonDiff {
-								diff_type: Deleted,
-								from_desc: Some(format!("{:?}", del_elm)),
-								to_desc: None,
-								base_path: base_path.to_string(),
-							})
-						}
+				for (ck, cv) in compared_obj.iter() {
+					if !base_obj.contains_key(ck) {
+						children.push(Task::Emit(JsonDiff {
+							diff_type: Added,
+							from_desc: None,
+							to_desc: Some(debug_string(cv)),
+							base_path: format!("{}{}{}", path, path_sec, ck),
+						}));
 					}
-					diffs
-				},
-
-				_ => {
-					make_json_type_diff(base_el, compared_el)
 				}
-			}
-		},
+			},
+			(JsonNull, JsonNull) => {},
+			(JsonBool(base_b), JsonBool(compared_b)) => {
+				if base_b != compared_b {
+					diffs.push(modified(base_b.to_string(), compared_b.to_string()));
+				}
+			},
+			// Different types
+			_ => diffs.push(modified(debug_string(base_el), debug_string(compared_el))),
+		}
+		tasks.extend(children.into_iter().rev());
+	}
 
-		JsonObject(base_obj) => {
-			match compared_el {
-				JsonObject(compared_obj) => {
-					let mut diffs = Vec::new();
+	diffs
+}
 
-					for (bk, bv) in base_obj.iter() {
-						let new_bp = format!("{}{}{}", base_path, path_sec, bk);
-						match compared_obj.get(bk) {
-							Some(cv) => {
-								diffs.extend_from_slice(&element_diff(&bv, &cv, &new_bp, settings));
-							},
-							None => {
-								diffs.push(JsonDiff {
-									diff_type: Deleted,
-									from_desc: Some(format!("{:?}", bv)),
-									to_desc: None,
-									base_path: new_bp,
-								})
-							}
-						}
-					}
+// Whether two documents hold the same values, comparing numbers like `JsonNum::is_equal`.
+// Doubles that are exactly equal always match, so a threshold of 0 means exact equality.
+pub(crate) fn json_equal(a: &JsonElement, b: &JsonElement, threshold: f64) -> bool {
+	use JsonElement::*;
 
-					for (ck, cv) in compared_obj.iter() {
-						let new_bp = format!("{}{}{}", base_path, path_sec, ck);
-						match base_obj.get(ck) {
-							Some(_) => {},
-							None => {
-								diffs.push(JsonDiff {
-									diff_type: Added,
-									from_desc: None,
-									to_desc: Some(format!("{:?}", cv)),
-									base_path: new_bp,
-								})
-							}
-						}
+	let mut stack = vec![(a, b)];
+	while let Some(pair) = stack.pop() {
+		match pair {
+			(JsonNumber(x), JsonNumber(y)) => {
+				let exact = x.decimal_parts().is_some() && y.decimal_parts().is_some();
+				if !x.is_equal(y, threshold) && (exact || x.as_f64() != y.as_f64()) {
+					return false;
+				}
+			},
+			(JsonString(x), JsonString(y)) if x == y => {},
+			(JsonBool(x), JsonBool(y)) if x == y => {},
+			(JsonNull, JsonNull) => {},
+			(JsonArray(x), JsonArray(y)) if x.len() == y.len() => stack.extend(x.iter().zip(y)),
+			(JsonObject(x), JsonObject(y)) if x.len() == y.len() => {
+				for (k, xv) in x {
+					match y.get(k) {
+						Some(yv) => stack.push((xv, yv)),
+						None => return false,
 					}
-
-					diffs
-				},
-
-				_ => {
-					make_json_type_diff(base_el, compared_el)
 				}
-			}
-		},
+			},
+			_ => return false,
+		}
+	}
+	true
+}
 
-		JsonNull => {
-			match compared_el {
-				JsonNull => { Vec::new() },
-				_ => make_json_type_diff(base_el, compared_el)
-			}
-		},
+// The same text as `format!("{:?}", elm)`, built without recursing into children.
+fn debug_string(elm: &JsonElement) -> String {
+	use std::fmt::Write;
 
-		JsonBool(base_b) => {
-			match compared_el {
-				JsonBool(compared_b) => {
-					if base_b == compared_b {
-						Vec::new()
-					} else {
-						vec![JsonDiff {
-							diff_type: Modified,
-							from_desc: Some(base_b.to_string()),
-							to_desc: Some(compared_b.to_string()),
-							base_path: base_path.to_string(),
-						}]
-					}
-				},
+	enum Piece<'a> {
+		Element(&'a JsonElement),
+		Key(&'a str),
+		Text(&'static str),
+	}
 
-				_ => make_json_type_diff(base_el, compared_el)
-			}
+	let mut out = String::new();
+	let mut stack = vec![Piece::Element(elm)];
+	while let Some(piece) = stack.pop() {
+		match piece {
+			Piece::Text(text) => out.push_str(text),
+			Piece::Key(key) => write!(out, "{:?}: ", key).unwrap(),
+			Piece::Element(JsonElement::JsonArray(v)) => {
+				out.push_str("JsonArray([");
+				stack.push(Piece::Text("])"));
+				for (i, child) in v.iter().enumerate().rev() {
+					stack.push(Piece::Element(child));
+					if i != 0 {
+						stack.push(Piece::Text(", "));
+					}
+				}
+			},
+			Piece::Element(JsonElement::JsonObject(obj)) => {
+				out.push_str("JsonObject({");
+				stack.push(Piece::Text("})"));
+				let members: Vec<_> = obj.iter().collect();
+				for (i, (k, v)) in members.into_iter().enumerate().rev() {
+					stack.push(Piece::Element(v));
+					stack.push(Piece::Key(k));
+					if i != 0 {
+						stack.push(Piece::Text(", "));
+					}
+				}
+			},
+			Piece::Element(leaf) => write!(out, "{:?}", leaf).unwrap(),
 		}
 	}
+	out
 }
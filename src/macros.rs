@@ -0,0 +1,78 @@
+// Builds a JsonElement from JSON-like syntax. Values that are not JSON literals are Rust
+// expressions converted with `JsonElement::from`; object keys are string literals or any
+// parenthesized expression implementing ToString.
+//
+//     let id = 7;
+//     let elm = json!({
+//         "id": id,
+//         "tags": ["a", "b"],
+//         "owner": null,
+//         (format!("k{}", id)): -1.5,
+//     });
+#[macro_export]
+macro_rules! json {
+	(null) => {
+		$crate::JsonElement::JsonNull
+	};
+	(true) => {
+		$crate::JsonElement::JsonBool(true)
+	};
+	(false) => {
+		$crate::JsonElement::JsonBool(false)
+	};
+	([]) => {
+		$crate::JsonElement::JsonArray(::std::vec::Vec::new())
+	};
+	([ $($tt:tt)+ ]) => {
+		$crate::JsonElement::JsonArray($crate::json_internal!(@array [] $($tt)+))
+	};
+	({}) => {
+		$crate::JsonElement::JsonObject(::std::collections::HashMap::new())
+	};
+	({ $($tt:tt)+ }) => {{
+		let mut object = ::std::collections::HashMap::new();
+		$crate::json_internal!(@object object $($tt)+);
+		$crate::JsonElement::JsonObject(object)
+	}};
+	($other:expr) => {
+		$crate::JsonElement::from($other)
+	};
+}
+
+// Munches the contents of `json!` arrays and objects one entry at a time. A value is either
+// a single token tree (literal, identifier, nested array or object) or a longer expression.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_internal {
+	(@array [$($elems:expr,)*]) => {
+		::std::vec![$($elems,)*]
+	};
+	(@array [$($elems:expr,)*] $next:tt , $($rest:tt)*) => {
+		$crate::json_internal!(@array [$($elems,)* $crate::json!($next),] $($rest)*)
+	};
+	(@array [$($elems:expr,)*] $last:tt) => {
+		$crate::json_internal!(@array [$($elems,)* $crate::json!($last),])
+	};
+	(@array [$($elems:expr,)*] $next:expr , $($rest:tt)*) => {
+		$crate::json_internal!(@array [$($elems,)* $crate::json!($next),] $($rest)*)
+	};
+	(@array [$($elems:expr,)*] $last:expr) => {
+		$crate::json_internal!(@array [$($elems,)* $crate::json!($last),])
+	};
+
+	(@object $object:ident) => {};
+	(@object $object:ident $key:tt : $value:tt , $($rest:tt)*) => {
+		$object.insert(::std::string::ToString::to_string(&$key), $crate::json!($value));
+		$crate::json_internal!(@object $object $($rest)*);
+	};
+	(@object $object:ident $key:tt : $value:tt) => {
+		$object.insert(::std::string::ToString::to_string(&$key), $crate::json!($value));
+	};
+	(@object $object:ident $key:tt : $value:expr , $($rest:tt)*) => {
+		$object.insert(::std::string::ToString::to_string(&$key), $crate::json!($value));
+		$crate::json_internal!(@object $object $($rest)*);
+	};
+	(@object $object:ident $key:tt : $value:expr) => {
+		$object.insert(::std::string::ToString::to_string(&$key), $crate::json!($value));
+	};
+}
@@ -1,159 +1,721 @@
 extern crate minjson;
 extern crate clap;
 
+use std::collections::HashSet;
 use std::fs::File;
+use std::path::Path;
 use std::io::Write;
 use std::io::Read;
-use std::io::BufReader;
 
 use std::io;
 
-use clap::{App, Arg};
-
-fn main() -> () {
-	let app =App::new("minjson")
-				.version("0.2")
-				.about("JSON tools")
-				.author("tinaxd")
-				.arg(Arg::with_name("mode")
-					.short("m")
-					.long("mode")
-					.required(true)
-					.takes_value(true)
-					.possible_values(&["minify", "pretty", "inspect", "diff"])
-					.value_name("MODE")
-					)
-				.arg(Arg::with_name("out")
-					.long("out")
-					.takes_value(true)
-					.value_name("FILEPATH")
-					)
-				.arg(Arg::with_name("in")
-					.long("in")
-					.takes_value(true)
-					.value_name("FILEPATH"))
-				.arg(Arg::with_name("in2")
-					.long("in2")
-					.takes_value(true)
-					.value_name("FILEPATH"))
-				.get_matches();
-
-	let mode = app.value_of("mode").unwrap();
-
-	let mut strbuf = String::new();
-	if let Some(inpath) = app.value_of("in") {
-		match File::open(inpath) {
-			Err(e) => {
-				eprintln!("{}", e);
-				return;
-			},
+use clap::{App, AppSettings, Arg, SubCommand};
+use memmap2::Mmap;
+
+mod config;
+
+fn build_app() -> App<'static, 'static> {
+	let input = || Arg::with_name("FILE")
+		.help("Input file (default: stdin)");
+
+	App::new("minjson")
+		.version("0.2")
+		.about("JSON tools")
+		.author("tinaxd")
+		.setting(AppSettings::SubcommandRequiredElseHelp)
+		.setting(AppSettings::VersionlessSubcommands)
+		.arg(Arg::with_name("out")
+			.short("o")
+			.long("out")
+			.global(true)
+			.takes_value(true)
+			.value_name("FILEPATH")
+			.help("Write output to FILEPATH instead of stdout"))
+		.arg(Arg::with_name("json5")
+			.long("json5")
+			.global(true)
+			.overrides_with("no-json5")
+			.help("Accept JSON5 input (comments, trailing commas, unquoted keys, ...)"))
+		.arg(Arg::with_name("no-json5")
+			.long("no-json5")
+			.global(true)
+			.overrides_with("json5")
+			.help("Accept only JSON, even if the config file enables JSON5"))
+		.arg(Arg::with_name("jsonc")
+			.long("jsonc")
+			.global(true)
+			.overrides_with("no-jsonc")
+			.help("Accept // and /* */ comments, which pretty keeps and minify removes"))
+		.arg(Arg::with_name("no-jsonc")
+			.long("no-jsonc")
+			.global(true)
+			.overrides_with("jsonc")
+			.help("Reject comments, even if the config file enables JSONC"))
+		.arg(Arg::with_name("ndjson")
+			.long("ndjson")
+			.global(true)
+			.help("Read newline-delimited JSON; transform filters each line's document, other commands see an array of them"))
+		.arg(Arg::with_name("jobs")
+			.short("j")
+			.long("jobs")
+			.global(true)
+			.takes_value(true)
+			.value_name("N")
+			.validator(parsable::<usize>)
+			.help("Parse NDJSON lines and top-level array elements on N threads (0: one per CPU)"))
+		.arg(Arg::with_name("arbitrary-precision")
+			.long("arbitrary-precision")
+			.global(true)
+			.overrides_with("no-arbitrary-precision")
+			.help("Keep numbers exactly as written instead of converting them to i64/f64"))
+		.arg(Arg::with_name("no-arbitrary-precision")
+			.long("no-arbitrary-precision")
+			.global(true)
+			.overrides_with("arbitrary-precision")
+			.help("Convert numbers to i64/f64, even if the config file enables --arbitrary-precision"))
+		.arg(Arg::with_name("config")
+			.long("config")
+			.global(true)
+			.takes_value(true)
+			.value_name("FILEPATH")
+			.help("Read defaults from FILEPATH instead of the nearest .minjson.toml or minjson.json"))
+		.subcommand(SubCommand::with_name("minify")
+			.about("Removes whitespace (and comments with --jsonc or --json5)")
+			.arg(input()))
+		.subcommand(SubCommand::with_name("pretty")
+			.about("Indents the document")
+			.arg(input()
+				.multiple(true)
+				.help("Input files (default: stdin); several need --write or --check, which also take directories and globs"))
+			.arg(Arg::with_name("indent")
+				.long("indent")
+				.takes_value(true)
+				.value_name("N")
+				.validator(parsable::<u32>)
+				.help("Indent each level by N spaces [default: 2]"))
+			.arg(Arg::with_name("write")
+				.long("write")
+				.requires("FILE")
+				.conflicts_with_all(&["check", "out"])
+				.help("Reformat the files in place"))
+			.arg(Arg::with_name("check")
+				.long("check")
+				.requires("FILE")
+				.help("List the files that are not formatted, exiting with 1 if there are any")))
+		.subcommand(SubCommand::with_name("inspect")
+			.about("Shows the document as a tree, or statistics about it")
+			.arg(input())
+			.arg(Arg::with_name("stats")
+				.long("stats")
+				.help("Print structural statistics instead of the tree"))
+			.arg(Arg::with_name("format")
+				.long("format")
+				.takes_value(true)
+				.possible_values(&["table", "json"])
+				.default_value("table")
+				.value_name("FORMAT")
+				.help("Format of the statistics"))
+			.arg(Arg::with_name("max-depth")
+				.long("max-depth")
+				.takes_value(true)
+				.value_name("N")
+				.validator(parsable::<usize>)
+				.help("Do not expand containers nested deeper than N"))
+			.arg(Arg::with_name("max-children")
+				.long("max-children")
+				.takes_value(true)
+				.value_name("N")
+				.validator(parsable::<usize>)
+				.help("Show at most N children per container"))
+			.arg(Arg::with_name("collapse")
+				.long("collapse")
+				.help("Show arrays of same-shaped elements on one line"))
+			.arg(Arg::with_name("ascii")
+				.long("ascii")
+				.help("Draw the tree with ASCII characters only")))
+		.subcommand(SubCommand::with_name("diff")
+			.about("Lists the structural differences between two documents")
+			.arg(Arg::with_name("FILE")
+				.required(true)
+				.help("The original document"))
+			.arg(Arg::with_name("OTHER")
+				.required(true)
+				.help("The changed document"))
+			.arg(Arg::with_name("threshold")
+				.long("threshold")
+				.takes_value(true)
+				.value_name("X")
+				.validator(parsable::<f64>)
+				.help("Treat numbers that differ by less than X as equal [default: 1e-5]"))
+			.arg(Arg::with_name("ignore")
+				.long("ignore")
+				.takes_value(true)
+				.multiple(true)
+				.number_of_values(1)
+				.value_name("PATH")
+				.help("Leave out differences at PATH and below, written as diff prints it, e.g. ::meta::updated_at")))
+		.subcommand(SubCommand::with_name("merge")
+			.about("Three-way merges two documents changed from a common base")
+			.arg(Arg::with_name("FILE")
+				.required(true)
+				.help("Our version"))
+			.arg(Arg::with_name("THEIRS")
+				.required(true)
+				.help("Their version"))
+			.arg(Arg::with_name("base")
+				.long("base")
+				.required(true)
+				.takes_value(true)
+				.value_name("FILEPATH")
+				.help("The version both were changed from")))
+		.subcommand(SubCommand::with_name("validate")
+			.about("Checks the document against a JSON Schema")
+			.arg(input())
+			.arg(Arg::with_name("schema")
+				.long("schema")
+				.required(true)
+				.takes_value(true)
+				.value_name("FILEPATH")))
+		.subcommand(SubCommand::with_name("infer-schema")
+			.about("Infers a JSON Schema from sample documents")
+			.arg(input()
+				.multiple(true)))
+		.subcommand(SubCommand::with_name("codegen")
+			.about("Generates type definitions from sample documents")
+			.arg(input()
+				.multiple(true))
+			.arg(Arg::with_name("lang")
+				.long("lang")
+				.takes_value(true)
+				.possible_values(&["rust", "typescript"])
+				.default_value("rust")
+				.value_name("LANG")
+				.help("Language of the generated definitions"))
+			.arg(Arg::with_name("name")
+				.long("name")
+				.takes_value(true)
+				.default_value("Root")
+				.value_name("NAME")
+				.help("Name of the top-level type")))
+		.subcommand(SubCommand::with_name("transform")
+			.about("Applies a jq-style filter")
+			.arg(Arg::with_name("EXPR")
+				.required(true)
+				.help("Filter to apply, e.g. '.items[] | select(.price > 10) | .name'"))
+			.arg(input()))
+		.subcommand(SubCommand::with_name("completions")
+			.about("Prints a shell completion script")
+			.arg(Arg::with_name("SHELL")
+				.required(true)
+				.possible_values(&clap::Shell::variants())))
+}
+
+// Lets clap reject malformed numbers, so parsing a validated value again can't fail.
+fn parsable<T: std::str::FromStr>(value: String) -> Result<(), String> {
+	value.parse::<T>().map(|_| ()).map_err(|_| format!("invalid value '{}'", value))
+}
+
+// Why the CLI failed. Each kind has its own exit status, see `main`.
+enum CliError {
+	// The input can't be parsed or used
+	Invalid(String),
+	// Reading or writing failed
+	Io(String),
+	// Whoever reads the output has gone away, e.g. `minjson ... | head`
+	Closed,
+}
+
+impl From<io::Error> for CliError {
+	fn from(e: io::Error) -> CliError {
+		if e.kind() == io::ErrorKind::BrokenPipe {
+			CliError::Closed
+		} else {
+			CliError::Io(e.to_string())
+		}
+	}
+}
+
+// Exits with 0 on success, 1 if diff, merge or validate found differences, conflicts or
+// violations, 2 for invalid input or arguments and 3 for I/O errors. Errors go to stderr.
+fn main() {
+	let matches = match build_app().get_matches_safe() {
+		Ok(matches) => matches,
+		Err(e) if e.use_stderr() => {
+			eprintln!("{}", e.message);
+			std::process::exit(2);
+		},
+		// --help and --version
+		Err(e) => {
+			let _ = writeln!(io::stdout(), "{}", e.message);
+			return;
+		},
+	};
 
-			Ok(infile) => {
-				let mut bf = BufReader::new(infile);
-				bf.read_to_string(&mut strbuf).expect("Unable to read json");
+	let code = match run(&matches) {
+		Ok(false) | Err(CliError::Closed) => 0,
+		Ok(true) => 1,
+		Err(CliError::Invalid(e)) => {
+			eprintln!("{}", e);
+			2
+		},
+		Err(CliError::Io(e)) => {
+			eprintln!("{}", e);
+			3
+		},
+	};
+	std::process::exit(code);
+}
+
+// Returns whether differences were found.
+fn run(matches: &clap::ArgMatches) -> Result<bool, CliError> {
+	let (mode, app) = match matches.subcommand() {
+		(mode, Some(app)) => (mode, app),
+		_ => unreachable!(),
+	};
+
+	let mut output: Box<dyn Write> = match app.value_of("out") {
+		Some(path) => Box::new(OutFile { path, file: None }),
+		None => Box::new(io::BufWriter::new(io::stdout())),
+	};
+	let differs = run_mode(mode, app, &mut output)?;
+	output.flush()?;
+	Ok(differs)
+}
+
+// The --out file, created on the first write so that invalid input leaves it untouched.
+struct OutFile<'a> {
+	path: &'a str,
+	file: Option<io::BufWriter<File>>,
+}
+
+impl OutFile<'_> {
+	fn open(&mut self) -> io::Result<&mut io::BufWriter<File>> {
+		if self.file.is_none() {
+			let file = File::create(self.path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.path, e)))?;
+			self.file = Some(io::BufWriter::new(file));
+		}
+		Ok(self.file.as_mut().unwrap())
+	}
+}
+
+impl Write for OutFile<'_> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.open()?.write(buf)
+	}
+
+	// Creates the file even if nothing was written
+	fn flush(&mut self) -> io::Result<()> {
+		self.open()?.flush()
+	}
+}
+
+fn run_mode(mode: &str, app: &clap::ArgMatches, output: &mut dyn Write) -> Result<bool, CliError> {
+	if mode == "completions" {
+		let shell = app.value_of("SHELL").unwrap().parse().unwrap();
+		let mut script = Vec::new();
+		build_app().gen_completions_to("minjson", shell, &mut script);
+		output.write_all(&script)?;
+		return Ok(false);
+	}
+
+	let config = config::Config::load(app.value_of("config"))?;
+	let section = config.section_for(app.value_of("FILE"));
+	let parse_settings = parse_setting(app, &section);
+	let pretty_settings = pretty_setting(app, &section);
+	let ndjson = app.is_present("ndjson");
+	let mut parallel = minjson::ParallelSetting { jobs: 1, ..Default::default() };
+	if let Some(n) = app.value_of("jobs") {
+		parallel.jobs = n.parse().unwrap();
+	}
+	let parse = |json: &[u8]| parse_input(json, parse_settings, ndjson, parallel).map_err(CliError::Invalid);
+	let pretty = |json: &str| -> String {
+		minjson::JsonFormatter::new_from_str(json, pretty_settings).collect()
+	};
+
+	if app.is_present("write") || app.is_present("check") {
+		let settings_for = |path: &str| {
+			let section = config.section_for(Some(path));
+			(parse_setting(app, &section), pretty_setting(app, &section))
+		};
+		let paths = expand_paths(app.values_of("FILE").unwrap())?;
+		return format_files(paths.iter().map(String::as_str), settings_for, app.is_present("write"), output);
+	}
+	if app.occurrences_of("FILE") > 1 && mode == "pretty" {
+		return Err(CliError::Invalid(String::from("Formatting several files needs --write or --check")));
+	}
+
+	let input = InputData::load(app.value_of("FILE"))?;
+	let bytes = input.bytes();
+
+	if mode == "minify" && ndjson {
+		let docs = minjson::parse_ndjson_bytes(bytes, parse_settings, parallel).map_err(CliError::Invalid)?;
+		for doc in docs {
+			writeln!(output, "{}", doc)?;
+		}
+	} else if mode == "minify" || mode == "pretty" {
+		// The minimizer and formatter pass their input through, so it is checked first
+		let strbuf = minjson::validate_json_bytes(bytes, parse_settings).map_err(|e| CliError::Invalid(e.to_string()))?;
+
+		// JSON5 is converted to JSON first, which drops comments and sorts object keys
+		let converted;
+		let string = if parse_settings.json5 {
+			let g = minjson::build_json_graph_with_setting(strbuf, parse_settings).map_err(CliError::Invalid)?;
+			check_finite(&g)?;
+			converted = g.to_string();
+			&converted
+		} else {
+			strbuf
+		};
+		if mode == "minify" {
+			minjson::JsonMinimizer::new_from_str(string).write_to(output)?;
+		} else {
+			write_formatted(string, pretty_settings, output)?;
+		}
+	} else if mode == "inspect" {
+		let g = parse(bytes)?;
+		if app.is_present("stats") {
+			let stats = minjson::JsonStats::new(&g, minjson::StatsSetting::default());
+			if app.value_of("format") == Some("json") {
+				writeln!(output, "{}", pretty(&stats.to_element().to_string()))?;
+			} else {
+				write!(output, "{}", stats)?;
 			}
+		} else {
+			let settings = minjson::TreeSetting {
+				max_depth: app.value_of("max-depth").map(|n| n.parse().unwrap()),
+				max_children: app.value_of("max-children").map(|n| n.parse().unwrap()),
+				collapse_homogeneous: app.is_present("collapse"),
+				ascii: app.is_present("ascii"),
+				..Default::default()
+			};
+			write!(output, "{}", minjson::render_tree(&g, settings))?;
+		}
+	} else if mode == "diff" {
+		let input2 = InputData::load(app.value_of("OTHER"))?;
+		let mut diff_settings = minjson::DiffSetting::default();
+		if let Some(threshold) = app.value_of("threshold").map(|x| x.parse().unwrap()).or(section.threshold) {
+			diff_settings.float_diff_threashold = threshold;
+		}
+		let mut ignore: Vec<&str> = app.values_of("ignore").map(|v| v.collect()).unwrap_or_default();
+		ignore.extend(section.ignore.iter().flatten().map(String::as_str));
+
+		let parse = |json| parse_input(json, parse_settings, false, parallel).map_err(CliError::Invalid);
+		let mut diffs = parse(bytes)?.diff(&parse(input2.bytes())?, diff_settings);
+		diffs.retain(|d| !ignore.iter().any(|&path| {
+			d.base_path == path || (d.base_path.starts_with(path) && d.base_path[path.len()..].starts_with("::"))
+		}));
+		for d in &diffs {
+			writeln!(output, "{}", pretty_diff(d))?;
+		}
+		return Ok(!diffs.is_empty());
+	} else if mode == "merge" {
+		let base = InputData::load(app.value_of("base"))?;
+		let theirs = InputData::load(app.value_of("THEIRS"))?;
+		let result = minjson::JsonElement::merge3(&parse(base.bytes())?, &parse(bytes)?, &parse(theirs.bytes())?);
+		if result.is_clean() {
+			writeln!(output, "{}", pretty(&result.merged.to_string()))?;
+		} else {
+			writeln!(output, "{}", pretty(&result.report().to_string()))?;
+			return Ok(true);
+		}
+	} else if mode == "validate" {
+		let schema = InputData::load(app.value_of("schema"))?;
+		let schema = parse_input(schema.bytes(), parse_settings, false, parallel)
+			.and_then(minjson::JsonSchema::new)
+			.map_err(|e| CliError::Invalid(format!("Invalid schema: {}", e)))?;
+		let violations = schema.validate(&parse(bytes)?);
+		for v in &violations {
+			writeln!(output, "{}", v)?;
+		}
+		return Ok(!violations.is_empty());
+	} else if mode == "infer-schema" || mode == "codegen" {
+		let mut samples = Vec::new();
+		let inpaths: Vec<&str> = app.values_of("FILE").map(|v| v.collect()).unwrap_or_default();
+		// The first input has been loaded already
+		let others = inpaths.iter().skip(1).map(|p| InputData::load(Some(p))).collect::<Result<Vec<_>, _>>()?;
+		let jsons = std::iter::once(bytes).chain(others.iter().map(InputData::bytes));
+		for (i, json) in jsons.enumerate() {
+			let name = inpaths.get(i).unwrap_or(&"<stdin>");
+			// Each NDJSON line is a sample of its own
+			let parsed = if ndjson {
+				minjson::parse_ndjson_bytes(json, parse_settings, parallel)
+			} else {
+				parse_input(json, parse_settings, false, parallel).map(|g| vec![g])
+			};
+			samples.extend(parsed.map_err(|e| CliError::Invalid(format!("{}: {}", name, e)))?);
+		}
+		if mode == "codegen" {
+			let lang = match app.value_of("lang") {
+				Some("typescript") => minjson::CodegenLang::TypeScript,
+				_ => minjson::CodegenLang::Rust,
+			};
+			write!(output, "{}", minjson::generate_types(&samples, app.value_of("name").unwrap(), lang))?;
+		} else {
+			let schema = minjson::infer_schema(&samples, minjson::InferSetting::default()).to_string();
+			writeln!(output, "{}", pretty(&schema))?;
+		}
+	} else if mode == "transform" {
+		let query = minjson::JsonQuery::new(app.value_of("EXPR").unwrap())
+			.map_err(|e| CliError::Invalid(format!("Invalid expression: {}", e)))?;
+		let results = if ndjson {
+			minjson::parse_ndjson_bytes(bytes, parse_settings, parallel).and_then(|docs| {
+				docs.iter().try_fold(Vec::new(), |mut results, doc| {
+					results.extend(query.run(doc)?);
+					Ok(results)
+				})
+			})
+		} else {
+			parse_input(bytes, parse_settings, false, parallel).and_then(|g| query.run(&g))
+		};
+		for r in results.map_err(CliError::Invalid)? {
+			writeln!(output, "{}", pretty(&r.to_string()))?;
 		}
 	} else {
-		io::stdin().read_to_string(&mut strbuf).expect("Unable to read json");
+		unreachable!()
 	}
+	Ok(false)
+}
 
-	let mut input: Box<dyn Read> = {
-		if let Some(inpath) = app.value_of("in") {
-			match File::open(inpath) {
-				Ok(i) => Box::new(std::io::BufReader::new(i)),
-				Err(e) => {
-					eprintln!("{}", e.to_string());
-					return;
-				}
+// Flags win over the config file (`section`), which wins over the built-in defaults.
+fn parse_setting(app: &clap::ArgMatches, section: &config::Section) -> minjson::ParseSetting {
+	minjson::ParseSetting {
+		json5: switch(app, "json5").or(section.json5).unwrap_or(false),
+		jsonc: switch(app, "jsonc").or(section.jsonc).unwrap_or(false),
+		arbitrary_precision: switch(app, "arbitrary-precision").or(section.arbitrary_precision).unwrap_or(false),
+		limits: section.limits,
+	}
+}
+
+// Whether `--name` or `--no-name` was given, the last one winning; None if neither was.
+fn switch(app: &clap::ArgMatches, name: &str) -> Option<bool> {
+	if app.is_present(name) {
+		Some(true)
+	} else if app.is_present(format!("no-{}", name)) {
+		Some(false)
+	} else {
+		None
+	}
+}
+
+fn pretty_setting(app: &clap::ArgMatches, section: &config::Section) -> minjson::PrettySetting {
+	let indent_width = app.value_of("indent").map(|n| n.parse().unwrap()).or(section.indent).unwrap_or(2);
+	minjson::PrettySetting{indent_width}
+}
+
+// Writes the canonical form of `text`: the `JsonFormatter` output followed by a newline. `pretty`
+// prints it, and --write and --check compare files against it.
+fn write_formatted(text: &str, settings: minjson::PrettySetting, output: &mut dyn Write) -> io::Result<()> {
+	for ch in minjson::JsonFormatter::new_from_str(text, settings) {
+		let mut buf = [0; 4];
+		output.write_all(ch.encode_utf8(&mut buf).as_bytes())?;
+	}
+	writeln!(output)
+}
+
+// Expands the FILE arguments of --write and --check. Directories are searched for `.json` files,
+// and an argument that doesn't exist but contains '*' or '?' is matched as a glob (see
+// config::Glob), for shells that don't expand `**`. Hidden files and directories are skipped.
+fn expand_paths<'a>(args: impl Iterator<Item = &'a str>) -> Result<Vec<String>, CliError> {
+	let mut paths = Vec::new();
+	let mut seen = HashSet::new();
+	for arg in args {
+		let path = Path::new(arg);
+		let mut found = Vec::new();
+		if path.is_dir() {
+			let dir = if arg.ends_with('/') { arg.to_string() } else { format!("{}/", arg) };
+			walk(dir, usize::MAX, &mut found)?;
+			found.retain(|p| p.ends_with(".json"));
+		} else if !path.exists() && arg.contains(&['*', '?'][..]) {
+			let glob = config::Glob::new(arg).map_err(CliError::Invalid)?;
+			// Only the directory before the first wildcard is searched, and only as deep as the
+			// pattern reaches unless it contains '**'
+			let wildcard = arg.find(&['*', '?'][..]).unwrap();
+			let dir = arg[..wildcard].rfind('/').map_or("", |i| &arg[..=i]);
+			let rest = &arg[dir.len()..];
+			let depth = if rest.contains("**") { usize::MAX } else { rest.matches('/').count() + 1 };
+			walk(dir.to_string(), depth, &mut found)?;
+			found.retain(|p| glob.matches_path(p));
+			if found.is_empty() {
+				return Err(CliError::Invalid(format!("{}: no files match", arg)));
 			}
 		} else {
-			Box::new(std::io::BufReader::new(std::io::stdin()))
+			found.push(arg.to_string());
 		}
-	};
+		found.sort();
+		paths.extend(found.into_iter().filter(|p| seen.insert(p.clone())));
+	}
+	Ok(paths)
+}
 
-	let mut output: Box<dyn Write> = {
-		if let Some(outpath) = app.value_of("out") {
-			let outfile = File::create(outpath);
-			match outfile {
-				Ok(o) => Box::new(std::io::BufWriter::new(o)),
-				Err(e) => {
-					eprintln!("File writing err {}", e.to_string());
-					return;
+// Adds the files under `dir`, which is empty or ends with '/', down to `max_depth` levels.
+// Symbolic links to directories are not followed.
+fn walk(dir: String, max_depth: usize, files: &mut Vec<String>) -> Result<(), CliError> {
+	let mut dirs = vec![(dir, 1)];
+	while let Some((dir, depth)) = dirs.pop() {
+		let shown = if dir.is_empty() { "." } else { &dir };
+		let entries = std::fs::read_dir(shown).map_err(|e| CliError::Io(format!("{}: {}", shown, e)))?;
+		for entry in entries {
+			let entry = entry?;
+			let name = entry.file_name().to_string_lossy().into_owned();
+			if name.starts_with('.') {
+				continue;
+			}
+			let path = format!("{}{}", dir, name);
+			if entry.file_type()?.is_dir() {
+				if depth < max_depth {
+					dirs.push((path + "/", depth + 1));
 				}
+			} else if Path::new(&path).is_file() {
+				files.push(path);
 			}
-		} else {
-			Box::new(std::io::BufWriter::new(std::io::stdout()))
 		}
-	};
-    
-    if mode == "minify" {
-		let mut string = String::new();
-		match input.read_to_string(&mut string) {
-			Err(e) => {
-				eprintln!("Failed to read json file: {}", e.to_string());
-				return;
-			},
-			Ok(_) => {
-				for ch in minjson::JsonMinimizer::new_from_str(&string) {
-					let mut buf = [0; 4];
-					ch.encode_utf8(&mut buf);
-					output.write_all(&buf).unwrap();
+	}
+	Ok(())
+}
+
+// Reformats each file in place, or with `check` lists those that aren't formatted (see
+// `write_formatted`). Every file is tried even if some fail; returns whether any file was not
+// formatted.
+fn format_files<'a, S>(paths: impl Iterator<Item = &'a str>, settings_for: S, write: bool, output: &mut dyn Write) -> Result<bool, CliError>
+	where S: Fn(&str) -> (minjson::ParseSetting, minjson::PrettySetting)
+{
+	let mut unformatted = false;
+	let mut failures = Vec::new();
+	let mut io_failed = false;
+	for path in paths {
+		let (parse_settings, settings) = settings_for(path);
+		match format_file(path, parse_settings, settings, write) {
+			Ok(true) => {
+				unformatted = true;
+				if !write {
+					writeln!(output, "{}", path)?;
 				}
-			}
+			},
+			Ok(false) => {},
+			Err(CliError::Invalid(e)) => failures.push(format!("{}: {}", path, e)),
+			Err(CliError::Io(e)) => {
+				io_failed = true;
+				failures.push(format!("{}: {}", path, e));
+			},
+			Err(CliError::Closed) => return Err(CliError::Closed),
 		}
-    } else if mode == "pretty" {
-		let mut string = String::new();
-		match input.read_to_string(&mut string) {
-			Err(e) => {
-				eprintln!("Failed to read json file: {}", e.to_string());
-				return;
+	}
+
+	match failures.join("\n") {
+		e if io_failed => Err(CliError::Io(e)),
+		e if !e.is_empty() => Err(CliError::Invalid(e)),
+		_ => Ok(unformatted && !write),
+	}
+}
+
+// Returns whether the file was not formatted. Files are replaced by renaming a formatted copy
+// over them, so they are never left half-written.
+fn format_file(path: &str, parse_settings: minjson::ParseSetting, settings: minjson::PrettySetting, write: bool) -> Result<bool, CliError> {
+	// Formatting converts JSON5 to JSON, which would drop comments
+	if parse_settings.json5 {
+		return Err(CliError::Invalid(String::from("--write and --check do not support JSON5")));
+	}
+	let bytes = std::fs::read(path)?;
+	// The formatter doesn't check its input, and must not write garbage over a file
+	let text = minjson::validate_json_bytes(&bytes, parse_settings).map_err(|e| CliError::Invalid(e.to_string()))?;
+
+	let mut formatted = Vec::new();
+	write_formatted(text, settings, &mut formatted)?;
+	if formatted == bytes {
+		return Ok(false);
+	}
+	if !write {
+		return Ok(true);
+	}
+
+	// Write through symlinks rather than replacing them
+	let target = std::fs::canonicalize(path)?;
+	let name = target.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+	let temp = target.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+	let result = File::create(&temp).and_then(|mut file| {
+		file.write_all(&formatted)?;
+		file.set_permissions(std::fs::metadata(&target)?.permissions())?;
+		file.sync_all()
+	}).and_then(|_| std::fs::rename(&temp, &target));
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp);
+	}
+	result?;
+	Ok(true)
+}
+
+// An input file, memory-mapped where possible so that large files are neither copied nor
+// held in memory twice.
+enum InputData {
+	Mapped(Mmap),
+	Buffered(Vec<u8>),
+}
+
+impl InputData {
+	// Reads stdin if `path` is None. Files that can't be mapped, such as pipes, are read instead.
+	fn load(path: Option<&str>) -> Result<InputData, CliError> {
+		let mut buf = Vec::new();
+		match path {
+			None => {
+				io::stdin().read_to_end(&mut buf).map_err(|e| CliError::Io(format!("Failed to read stdin: {}", e)))?;
 			},
-			Ok(_) => {
-				for ch in minjson::JsonFormatter::new_from_str(&string, minjson::PrettySetting{indent_width: 2}) {
-					let mut buf = [0; 4];
-					ch.encode_utf8(&mut buf);
-					output.write_all(&buf).unwrap();
+			Some(path) => {
+				let mut file = File::open(path).map_err(|e| CliError::Io(format!("{}: {}", path, e)))?;
+				// Safety: the map is only read. As with any file input, it must not be truncated
+				// by another process while the CLI runs.
+				if let Ok(map) = unsafe { Mmap::map(&file) } {
+					return Ok(InputData::Mapped(map));
 				}
-			}
+				file.read_to_end(&mut buf).map_err(|e| CliError::Io(format!("{}: {}", path, e)))?;
+			},
 		}
-    } else if mode == "inspect" {
-    	match minjson::build_json_graph(&strbuf) {
-    		Ok(g) => write!(output, "{}", format!("{:#?}", g)).unwrap(),
-    		Err(e) => write!(output, "{}", format!("{}\n", e.to_string())).unwrap(),
-    	};
-    } else if mode == "diff" {
-    	if let Some(in2path) = app.value_of("in2") {
-    		let mut strbuf2 = String::new();
-    		match File::open(in2path) {
-				Err(e) => {
-					eprintln!("{}", e);
-					return;
-				},
-
-				Ok(infile) => {
-					let mut bf = BufReader::new(infile);
-					bf.read_to_string(&mut strbuf2).expect("Unable to read json");
-				}
-			}
-			let diffs = minjson::structure_diff(&strbuf, &strbuf2, minjson::DiffSetting::default());
-			match diffs {
-				Ok(ds) => {
-					let mut buf = String::new();
-					for d in &ds {
-						buf.push_str(&pretty_diff(d));
-						buf.push('\n');
-					}
-					write!(output, "{}", buf).unwrap();
-				},
-				Err(e) => { write!(output, "{}", e.to_string()).unwrap(); },
-			}
-    	} else {
-    		eprintln!("Must specify in2 option");
-    		return;
-    	}
-    } else {
-    	unreachable!()
-    }
+		Ok(InputData::Buffered(buf))
+	}
+
+	// The raw input. The parser checks it for UTF-8 as it reads it, a block at a time, so a
+	// mapped file is not read through twice.
+	fn bytes(&self) -> &[u8] {
+		match self {
+			InputData::Mapped(map) => &map[..],
+			InputData::Buffered(buf) => &buf[..],
+		}
+	}
+}
+
+// JSON has no representation for Infinity and NaN, which JSON5 allows. The tree is walked
+// depth-first, keeping the pointer tokens of the current path, so the path of an offending
+// number is only built when there is one.
+fn check_finite(elm: &minjson::JsonElement) -> Result<(), CliError> {
+	use minjson::JsonElement::*;
+
+	let mut tokens: Vec<String> = Vec::new();
+	let mut stack = vec![(elm, 0, None)];
+	while let Some((elm, depth, token)) = stack.pop() {
+		tokens.truncate(depth);
+		tokens.extend(token);
+		match elm {
+			JsonNumber(minjson::JsonNum::Double(d)) if !d.is_finite() => {
+				let name = if d.is_nan() { "NaN" } else if *d > 0.0 { "Infinity" } else { "-Infinity" };
+				let path: String = tokens.iter().map(|t| format!("/{}", t)).collect();
+				return Err(CliError::Invalid(format!("{} at {} cannot be converted to JSON", name, if path.is_empty() { "/" } else { &path })));
+			},
+			// Reversed, so that the first offending number in the document is reported
+			JsonArray(v) => stack.extend(v.iter().enumerate().rev().map(|(i, e)| (e, tokens.len(), Some(i.to_string())))),
+			JsonObject(obj) => stack.extend(obj.iter().map(|(k, e)| (e, tokens.len(), Some(minjson::escape_pointer_token(k))))),
+			_ => {},
+		}
+	}
+	Ok(())
+}
+
+// Parses the main input: one document, whose top-level array elements are parsed in parallel
+// if `parallel` allows, or with --ndjson an array of the documents on its lines.
+fn parse_input(json: &[u8], settings: minjson::ParseSetting, ndjson: bool, parallel: minjson::ParallelSetting) -> Result<minjson::JsonElement, String> {
+	if ndjson {
+		minjson::parse_ndjson_bytes(json, settings, parallel).map(minjson::JsonElement::JsonArray)
+	} else {
+		minjson::build_json_graph_parallel_bytes(json, settings, parallel)
+	}
 }
 
 fn pretty_diff(diff: &minjson::JsonDiff) -> String {
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use crate::{json_equal, DiffSetting, JsonElement};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+	pub base_path: String,
+	pub base: Option<JsonElement>,
+	pub ours: Option<JsonElement>,
+	pub theirs: Option<JsonElement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeResult {
+	// Conflicting positions hold the value from `ours`.
+	pub merged: JsonElement,
+	pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+	pub fn is_clean(&self) -> bool {
+		self.conflicts.is_empty()
+	}
+
+	// Builds `{"merged": ..., "conflicts": [{"path", "base", "ours", "theirs"}, ...]}`.
+	// A side that does not have the value is reported as absent from the conflict object.
+	pub fn report(&self) -> JsonElement {
+		let conflicts = self.conflicts.iter().map(|c| {
+			let mut obj = HashMap::new();
+			obj.insert("path".to_string(), JsonElement::JsonString(c.base_path.clone()));
+			for (name, side) in &[("base", &c.base), ("ours", &c.ours), ("theirs", &c.theirs)] {
+				if let Some(v) = side {
+					obj.insert(name.to_string(), v.clone());
+				}
+			}
+			JsonElement::JsonObject(obj)
+		}).collect();
+
+		let mut report = HashMap::new();
+		report.insert("merged".to_string(), self.merged.clone());
+		report.insert("conflicts".to_string(), JsonElement::JsonArray(conflicts));
+		JsonElement::JsonObject(report)
+	}
+}
+
+impl JsonElement {
+	pub fn merge3(base: &JsonElement, ours: &JsonElement, theirs: &JsonElement) -> MergeResult {
+		Self::merge3_with_setting(base, ours, theirs, DiffSetting::default())
+	}
+
+	pub fn merge3_with_setting(base: &JsonElement, ours: &JsonElement, theirs: &JsonElement, settings: DiffSetting) -> MergeResult {
+		let mut conflicts = Vec::new();
+		let merged = merge_element(Some(base), Some(ours), Some(theirs), "", settings, &mut conflicts);
+
+		MergeResult {
+			merged: merged.unwrap_or(JsonElement::JsonNull),
+			conflicts,
+		}
+	}
+}
+
+fn is_same(a: Option<&JsonElement>, b: Option<&JsonElement>, settings: DiffSetting) -> bool {
+	match (a, b) {
+		(None, None) => true,
+		(Some(a), Some(b)) => json_equal(a, b, settings.float_diff_threashold),
+		_ => false,
+	}
+}
+
+fn merge_element(base: Option<&JsonElement>, ours: Option<&JsonElement>, theirs: Option<&JsonElement>, base_path: &str, settings: DiffSetting, conflicts: &mut Vec<MergeConflict>) -> Option<JsonElement> {
+	use JsonElement::*;
+
+	// Work is kept on an explicit stack so that deep documents don't overflow the call stack.
+	// `Merge` holds the length of its parent's path and its own key or index, like the tasks of
+	// `element_diff`. Merged values go to `results`, where a `Build` task collects the members
+	// of its container; `None` is a value missing from the merge.
+	enum Task<'a> {
+		Merge(Option<&'a JsonElement>, Option<&'a JsonElement>, Option<&'a JsonElement>, usize, Option<String>),
+		BuildObject(Vec<&'a String>),
+		BuildArray(usize),
+	}
+
+	let path_sec = "::";
+	let mut path = base_path.to_string();
+	let mut results: Vec<Option<JsonElement>> = Vec::new();
+	let mut tasks = vec![Task::Merge(base, ours, theirs, path.len(), None)];
+
+	while let Some(task) = tasks.pop() {
+		let (base, ours, theirs) = match task {
+			Task::Merge(base, ours, theirs, parent_len, key) => {
+				path.truncate(parent_len);
+				if let Some(key) = key {
+					path.push_str(path_sec);
+					path.push_str(&key);
+				}
+				(base, ours, theirs)
+			},
+			Task::BuildObject(keys) => {
+				let values = results.split_off(results.len() - keys.len());
+				let merged = keys.into_iter().zip(values).filter_map(|(k, v)| Some((k.clone(), v?))).collect();
+				results.push(Some(JsonObject(merged)));
+				continue;
+			},
+			Task::BuildArray(len) => {
+				let values = results.split_off(results.len() - len);
+				results.push(Some(JsonArray(values.into_iter().map(|v| v.unwrap_or(JsonNull)).collect())));
+				continue;
+			},
+		};
+
+		// Containers of the same shape are merged member by member, which gives the same result as
+		// comparing them whole first. Only values the merge doesn't descend into are compared, so
+		// each one is compared once.
+		match (base, ours, theirs) {
+			(Some(JsonObject(_)), Some(JsonObject(o)), Some(JsonObject(t))) | (None, Some(JsonObject(o)), Some(JsonObject(t))) => {
+				let b = match base {
+					Some(JsonObject(b)) => Some(b),
+					_ => None,
+				};
+
+				let mut keys: Vec<&String> = b.into_iter().flat_map(|b| b.keys()).chain(o.keys()).chain(t.keys()).collect();
+				keys.sort();
+				keys.dedup();
+
+				// Pushed in reverse, so that members are merged in key order
+				let members: Vec<Task> = keys.iter().rev()
+					.map(|k| Task::Merge(b.and_then(|b| b.get(*k)), o.get(*k), t.get(*k), path.len(), Some(k.to_string())))
+					.collect();
+				tasks.push(Task::BuildObject(keys));
+				tasks.extend(members);
+			},
+
+			(Some(JsonArray(b)), Some(JsonArray(o)), Some(JsonArray(t))) if b.len() == o.len() && o.len() == t.len() => {
+				tasks.push(Task::BuildArray(o.len()));
+				for (i, ((b_elm, o_elm), t_elm)) in b.iter().zip(o.iter()).zip(t.iter()).enumerate().rev() {
+					tasks.push(Task::Merge(Some(b_elm), Some(o_elm), Some(t_elm), path.len(), Some(i.to_string())));
+				}
+			},
+
+			_ if is_same(ours, theirs, settings) || is_same(base, theirs, settings) => results.push(ours.cloned()),
+			_ if is_same(base, ours, settings) => results.push(theirs.cloned()),
+
+			_ => {
+				conflicts.push(MergeConflict {
+					base_path: path.clone(),
+					base: base.cloned(),
+					ours: ours.cloned(),
+					theirs: theirs.cloned(),
+				});
+				results.push(ours.cloned());
+			}
+		}
+	}
+
+	results.pop().flatten()
+}
@@ -0,0 +1,223 @@
+use std::thread;
+
+use crate::{parse_document, scan, utf8_error, ElementTree, Input, JsonElement, LimitKind, ParseError, ParseSetting};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelSetting {
+	// Number of threads; 0 uses one per available CPU.
+	pub jobs: usize,
+	// Inputs are not split into pieces smaller than this many bytes, since a thread per tiny
+	// piece costs more than it saves.
+	pub min_chunk_size: usize,
+}
+
+impl Default for ParallelSetting {
+	fn default() -> ParallelSetting {
+		ParallelSetting {
+			jobs: 0,
+			min_chunk_size: 1 << 16,
+		}
+	}
+}
+
+impl ParallelSetting {
+	fn threads(&self) -> usize {
+		match self.jobs {
+			0 => thread::available_parallelism().map_or(1, |n| n.get()),
+			n => n,
+		}
+	}
+}
+
+// Parses newline-delimited JSON: one document per line, blank lines skipped. Lines are split
+// across threads and the documents returned in input order. Errors name the 1-based line.
+pub fn parse_ndjson(input: &str, settings: ParseSetting, parallel: ParallelSetting) -> Result<Vec<JsonElement>, String> {
+	ndjson(Input::Text(input), settings, parallel)
+}
+
+// Like `parse_ndjson`, for input that may not be UTF-8. Each thread checks its own lines as it
+// parses them.
+pub fn parse_ndjson_bytes(input: &[u8], settings: ParseSetting, parallel: ParallelSetting) -> Result<Vec<JsonElement>, String> {
+	ndjson(Input::Bytes(input), settings, parallel)
+}
+
+fn ndjson(input: Input, settings: ParseSetting, parallel: ParallelSetting) -> Result<Vec<JsonElement>, String> {
+	let bytes = input.as_bytes();
+	if let Some(limit) = settings.limits.max_input_size.filter(|&limit| bytes.len() > limit) {
+		return Err(ParseError::LimitExceeded(LimitKind::InputSize, limit).to_string());
+	}
+	// The limit applies to the whole input, which was checked above, not to each line.
+	let mut line_settings = settings;
+	line_settings.limits.max_input_size = None;
+
+	// Cut the input at line ends into one piece per thread, numbering the first line of each.
+	let target = (bytes.len() / parallel.threads()).max(parallel.min_chunk_size).max(1);
+	let mut pieces = Vec::new();
+	let (mut start, mut line) = (0, 1);
+	while start < bytes.len() {
+		let end = (start + target).min(bytes.len());
+		let end = bytes[end..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| end + i + 1);
+		pieces.push((input.slice(start, end), line));
+		line += bytes[start..end].iter().filter(|&&b| b == b'\n').count();
+		start = end;
+	}
+
+	run_in_order(pieces, |(piece, first_line)| {
+		let mut docs = Vec::new();
+		for (i, text) in lines(piece).enumerate() {
+			if text.is_blank() {
+				continue;
+			}
+			match parse_document(text, line_settings, true, &mut ElementTree) {
+				Ok(doc) => docs.push(doc),
+				Err(e) => return Err(format!("Line {}: {}", first_line + i, e)),
+			}
+		}
+		Ok(docs)
+	})
+}
+
+// `str::lines` for both kinds of input: split at '\n', dropping a '\r' before it.
+fn lines<'a>(input: Input<'a>) -> impl Iterator<Item = Input<'a>> {
+	let bytes = input.as_bytes();
+	let mut start = 0;
+	std::iter::from_fn(move || {
+		if start >= bytes.len() {
+			return None;
+		}
+		let end = bytes[start..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| start + i);
+		let line = &bytes[start..end];
+		let line = input.slice(start, start + line.strip_suffix(b"\r").map_or(line.len(), <[u8]>::len));
+		start = end + 1;
+		Some(line)
+	})
+}
+
+// Parses a document like `build_json_graph_with_setting`. If it is a top-level array, a
+// pre-scan finds the boundaries of its elements, which are then parsed across threads.
+// Other documents, and JSON5 and JSONC input, whose comments the pre-scan can't follow, are
+// parsed on the calling thread.
+pub fn build_json_graph_parallel(json: &str, settings: ParseSetting, parallel: ParallelSetting) -> Result<JsonElement, String> {
+	graph_parallel(Input::Text(json), settings, parallel)
+}
+
+// Like `build_json_graph_parallel`, for input that may not be UTF-8. Each thread checks its
+// own elements as it parses them.
+pub fn build_json_graph_parallel_bytes(json: &[u8], settings: ParseSetting, parallel: ParallelSetting) -> Result<JsonElement, String> {
+	graph_parallel(Input::Bytes(json), settings, parallel)
+}
+
+fn graph_parallel(json: Input, settings: ParseSetting, parallel: ParallelSetting) -> Result<JsonElement, String> {
+	let limits = settings.limits;
+	let len = json.as_bytes().len();
+	let bounds = match split_array(json.as_bytes()) {
+		Some(bounds) if !settings.json5 && !settings.jsonc && limits.max_depth != Some(0) && len > parallel.min_chunk_size && parallel.threads() > 1 => bounds,
+		_ => return parse_document(json, settings, false, &mut ElementTree).map_err(String::from),
+	};
+	if let Some(limit) = limits.max_input_size.filter(|&limit| len > limit) {
+		return Err(ParseError::LimitExceeded(LimitKind::InputSize, limit).to_string());
+	}
+	// Nothing parses what follows the array, but it must still be text
+	let end = bounds[bounds.len() - 1] + 1;
+	if let Input::Bytes(bytes) = json {
+		if let Err(e) = std::str::from_utf8(&bytes[end..]) {
+			return Err(ParseError::Syntax(utf8_error(end + e.valid_up_to())).to_string());
+		}
+	}
+
+	let mut elems: Vec<Input> = bounds.windows(2).map(|w| json.slice(w[0] + 1, w[1])).collect();
+	if elems.len() == 1 && elems[0].is_blank() {
+		elems.clear();
+	}
+	if let Some(limit) = limits.max_members.filter(|&limit| elems.len() > limit) {
+		return Err(ParseError::LimitExceeded(LimitKind::Members, limit).to_string());
+	}
+	let mut elem_settings = settings;
+	elem_settings.limits.max_depth = limits.max_depth.map(|depth| depth - 1);
+	elem_settings.limits.max_input_size = None;
+
+	// Group consecutive elements into one piece per thread, numbering the first of each.
+	let target = (len / parallel.threads()).max(parallel.min_chunk_size);
+	let mut pieces = Vec::new();
+	let (mut first, mut size) = (0, 0);
+	for (i, elem) in elems.iter().enumerate() {
+		size += elem.as_bytes().len();
+		if size >= target || i + 1 == elems.len() {
+			pieces.push((&elems[first..=i], first));
+			first = i + 1;
+			size = 0;
+		}
+	}
+
+	run_in_order(pieces, |(piece, first)| {
+		piece.iter().enumerate().map(|(i, elem)| {
+			parse_document(*elem, elem_settings, true, &mut ElementTree).map_err(|e| match e {
+				// Count the depth from the array rather than the element
+				ParseError::LimitExceeded(LimitKind::Depth, depth) => ParseError::LimitExceeded(LimitKind::Depth, depth + 1),
+				e => e,
+			}).map_err(|e| format!("Array element {}: {}", first + i, e))
+		}).collect()
+	}).map(JsonElement::JsonArray)
+}
+
+// Runs `f` on each piece, all but the first on a thread of their own, and concatenates the
+// results in order. The error of the earliest failing piece is returned.
+fn run_in_order<P, F>(mut pieces: Vec<P>, f: F) -> Result<Vec<JsonElement>, String>
+where
+	P: Send,
+	F: Fn(P) -> Result<Vec<JsonElement>, String> + Sync,
+{
+	if pieces.len() <= 1 {
+		return pieces.pop().map_or(Ok(Vec::new()), f);
+	}
+	let first = pieces.remove(0);
+	let f = &f;
+	thread::scope(|s| {
+		let handles: Vec<_> = pieces.into_iter().map(|piece| s.spawn(move || f(piece))).collect();
+		let mut results = f(first)?;
+		for handle in handles {
+			match handle.join() {
+				Ok(docs) => results.extend(docs?),
+				Err(panic) => std::panic::resume_unwind(panic),
+			}
+		}
+		Ok(results)
+	})
+}
+
+// The offsets of the opening bracket of a top-level array, the commas between its elements
+// and its closing bracket, or None if the input doesn't look like an array. Brackets are
+// only counted, not matched; the element parsers catch mismatches.
+fn split_array(bytes: &[u8]) -> Option<Vec<usize>> {
+	let start = bytes.iter().position(|b| !b.is_ascii_whitespace())?;
+	if bytes[start] != b'[' {
+		return None;
+	}
+
+	let mut bounds = vec![start];
+	let mut depth = 0usize;
+	let mut i = start + 1;
+	loop {
+		i += scan::find_structural(bytes.get(i..)?);
+		match *bytes.get(i)? {
+			b'"' => loop {
+				i += 1;
+				i += scan::find_string_end(bytes.get(i..)?, b'"');
+				match bytes.get(i)? {
+					b'"' => break,
+					// Skip the escaped character
+					_ => i += 1,
+				}
+			},
+			b'[' | b'{' => depth += 1,
+			b']' if depth == 0 => {
+				bounds.push(i);
+				return Some(bounds);
+			},
+			b']' | b'}' => depth = depth.checked_sub(1)?,
+			b',' if depth == 0 => bounds.push(i),
+			_ => {},
+		}
+		i += 1;
+	}
+}
@@ -0,0 +1,1504 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::{JsonElement, JsonNum};
+
+// A compiled jq-style filter. Every filter maps one input to a stream of outputs, so
+// `.items[] | select(.price > 10) | .name` yields one name per matching item.
+//
+// Supported: `.`, `..`, `.foo`, `."foo"`, `.[e]`, `.[a:b]`, `.[]`, `?`, `|`, `,`, `//`,
+// arithmetic, comparisons, `and`/`or`, array and object construction, string
+// interpolation `"\(e)"`, `if`/`elif`/`else`, `try`/`catch`, `reduce`, `e as $x | ...`
+// and the builtins in BUILTINS.
+#[derive(Debug, Clone)]
+pub struct JsonQuery {
+	expr: Expr,
+}
+
+impl JsonQuery {
+	pub fn new(source: &str) -> Result<JsonQuery, String> {
+		let tokens = Lexer { src: source, pos: 0 }.tokenize()?;
+		let expr = Parser { tokens, pos: 0 }.parse_all()?;
+		Ok(JsonQuery { expr })
+	}
+
+	pub fn run(&self, input: &JsonElement) -> Result<Vec<JsonElement>, String> {
+		eval(&self.expr, input, &Env::Root)
+	}
+}
+
+const BUILTINS: &[(&str, usize)] = &[
+	("empty", 0), ("not", 0), ("length", 0), ("keys", 0), ("keys_unsorted", 0), ("has", 1), ("map", 1),
+	("map_values", 1), ("select", 1), ("recurse", 0), ("sort", 0), ("sort_by", 1), ("group_by", 1),
+	("unique", 0), ("unique_by", 1), ("min", 0), ("max", 0), ("min_by", 1), ("max_by", 1), ("add", 0),
+	("any", 0), ("all", 0), ("flatten", 0), ("flatten", 1), ("range", 1), ("range", 2), ("reverse", 0),
+	("tostring", 0), ("tonumber", 0), ("tojson", 0), ("fromjson", 0), ("type", 0), ("error", 0), ("error", 1),
+	("to_entries", 0), ("from_entries", 0), ("with_entries", 1), ("join", 1), ("split", 1), ("test", 1),
+	("sub", 2), ("gsub", 2), ("ascii_downcase", 0), ("ascii_upcase", 0), ("startswith", 1), ("endswith", 1),
+	("ltrimstr", 1), ("rtrimstr", 1), ("contains", 1), ("first", 0), ("first", 1), ("last", 0), ("last", 1),
+	("limit", 2), ("floor", 0), ("ceil", 0), ("round", 0), ("sqrt", 0), ("abs", 0), ("arrays", 0),
+	("objects", 0), ("iterables", 0), ("booleans", 0), ("numbers", 0), ("strings", 0), ("nulls", 0),
+	("scalars", 0), ("values", 0),
+];
+
+const KEYWORDS: &[&str] = &["and", "or", "if", "then", "elif", "else", "end", "as", "reduce", "try", "catch"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Mod,
+	Eq,
+	Ne,
+	Lt,
+	Le,
+	Gt,
+	Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+	Identity,
+	Recurse,
+	Field(Box<Expr>, String),
+	Index(Box<Expr>, Box<Expr>),
+	Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+	Iterate(Box<Expr>),
+	Optional(Box<Expr>),
+	Literal(JsonElement),
+	Str(Vec<StrPart>),
+	Array(Option<Box<Expr>>),
+	Object(Vec<(Expr, Expr)>),
+	Neg(Box<Expr>),
+	Binary(BinOp, Box<Expr>, Box<Expr>),
+	And(Box<Expr>, Box<Expr>),
+	Or(Box<Expr>, Box<Expr>),
+	Alt(Box<Expr>, Box<Expr>),
+	Pipe(Box<Expr>, Box<Expr>),
+	Comma(Box<Expr>, Box<Expr>),
+	If(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+	Try(Box<Expr>, Option<Box<Expr>>),
+	Reduce(Box<Expr>, String, Box<Expr>, Box<Expr>),
+	As(Box<Expr>, String, Box<Expr>),
+	Var(String),
+	Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum StrPart {
+	Lit(String),
+	Interp(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+	Dot,
+	DotDot,
+	Field(String),
+	Ident(String),
+	Var(String),
+	Num(JsonNum),
+	Str(Vec<StrPart>),
+	Punct(&'static str),
+}
+
+impl Token {
+	fn describe(&self) -> String {
+		match self {
+			Token::Dot => String::from("'.'"),
+			Token::DotDot => String::from("'..'"),
+			Token::Field(name) => format!("'.{}'", name),
+			Token::Ident(name) => format!("'{}'", name),
+			Token::Var(name) => format!("'${}'", name),
+			Token::Num(n) => format!("'{}'", n),
+			Token::Str(_) => String::from("string"),
+			Token::Punct(p) => format!("'{}'", p),
+		}
+	}
+}
+
+// Two-character operators come first so that "//" is not read as two "/".
+const PUNCTS: &[&str] = &[
+	"//", "==", "!=", "<=", ">=", "|", ",", "(", ")", "[", "]", "{", "}", ":", ";", "?", "+", "-", "*", "/", "%", "<", ">",
+];
+
+struct Lexer<'a> {
+	src: &'a str,
+	pos: usize,
+}
+
+impl Lexer<'_> {
+	fn tokenize(mut self) -> Result<Vec<Token>, String> {
+		let mut tokens = Vec::new();
+		while let Some(token) = self.next_token()? {
+			tokens.push(token);
+		}
+		Ok(tokens)
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.src[self.pos..].chars().next()
+	}
+
+	fn peek_at(&self, n: usize) -> Option<char> {
+		self.src[self.pos..].chars().nth(n)
+	}
+
+	fn bump(&mut self) -> Option<char> {
+		let ch = self.peek()?;
+		self.pos += ch.len_utf8();
+		Some(ch)
+	}
+
+	fn ident(&mut self) -> String {
+		let start = self.pos;
+		while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+			self.pos += 1;
+		}
+		self.src[start..self.pos].to_string()
+	}
+
+	fn next_token(&mut self) -> Result<Option<Token>, String> {
+		loop {
+			match self.peek() {
+				Some(c) if c.is_whitespace() => {
+					self.bump();
+				},
+				Some('#') => {
+					while self.peek().is_some_and(|c| c != '\n') {
+						self.bump();
+					}
+				},
+				_ => break,
+			}
+		}
+
+		let ch = match self.peek() {
+			Some(ch) => ch,
+			None => return Ok(None),
+		};
+		let is_ident_start = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+		if ch == '.' {
+			self.pos += 1;
+			if self.peek() == Some('.') {
+				self.pos += 1;
+				return Ok(Some(Token::DotDot));
+			}
+			if is_ident_start(self.peek()) {
+				return Ok(Some(Token::Field(self.ident())));
+			}
+			if self.peek() == Some('"') {
+				self.pos += 1;
+				return match self.string()?.as_slice() {
+					[] => Ok(Some(Token::Field(String::new()))),
+					[StrPart::Lit(name)] => Ok(Some(Token::Field(name.clone()))),
+					_ => Err(String::from("Field names cannot contain interpolation")),
+				};
+			}
+			return Ok(Some(Token::Dot));
+		}
+		if ch == '$' && is_ident_start(self.peek_at(1)) {
+			self.pos += 1;
+			return Ok(Some(Token::Var(self.ident())));
+		}
+		if ch == '"' {
+			self.pos += 1;
+			return Ok(Some(Token::Str(self.string()?)));
+		}
+		if ch.is_ascii_digit() {
+			return self.number().map(Some);
+		}
+		if is_ident_start(Some(ch)) {
+			return Ok(Some(Token::Ident(self.ident())));
+		}
+		for p in PUNCTS {
+			if self.src[self.pos..].starts_with(p) {
+				self.pos += p.len();
+				return Ok(Some(Token::Punct(p)));
+			}
+		}
+		Err(format!("Unexpected character {}", ch))
+	}
+
+	fn number(&mut self) -> Result<Token, String> {
+		let start = self.pos;
+		let digits = |lexer: &mut Lexer| {
+			while lexer.peek().is_some_and(|c| c.is_ascii_digit()) {
+				lexer.pos += 1;
+			}
+		};
+		digits(self);
+		if self.peek() == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+			self.pos += 1;
+			digits(self);
+		}
+		if matches!(self.peek(), Some('e') | Some('E')) {
+			self.pos += 1;
+			if matches!(self.peek(), Some('+') | Some('-')) {
+				self.pos += 1;
+			}
+			digits(self);
+		}
+		let text = &self.src[start..self.pos];
+		if let Ok(i) = text.parse::<i64>() {
+			return Ok(Token::Num(JsonNum::Integer(i)));
+		}
+		text.parse::<f64>().map(|d| Token::Num(JsonNum::Double(d))).map_err(|_| format!("Invalid number {}", text))
+	}
+
+	// Reads a string literal after its opening quote. `\(...)` embeds an expression, which is
+	// tokenized up to the matching parenthesis and parsed on its own.
+	fn string(&mut self) -> Result<Vec<StrPart>, String> {
+		let mut parts = Vec::new();
+		let mut lit = String::new();
+		loop {
+			match self.bump() {
+				None => return Err(String::from("Unterminated string")),
+				Some('"') => break,
+				Some('\\') => match self.bump() {
+					Some('n') => lit.push('\n'),
+					Some('t') => lit.push('\t'),
+					Some('r') => lit.push('\r'),
+					Some('b') => lit.push('\u{8}'),
+					Some('f') => lit.push('\u{c}'),
+					Some(c @ '"') | Some(c @ '\\') | Some(c @ '/') => lit.push(c),
+					Some('u') => lit.push(self.unicode_escape()?),
+					Some('(') => {
+						let mut tokens = Vec::new();
+						let mut depth = 0;
+						loop {
+							match self.next_token()?.ok_or_else(|| String::from("Unterminated string interpolation"))? {
+								Token::Punct(")") if depth == 0 => break,
+								token => {
+									match token {
+										Token::Punct("(") => depth += 1,
+										Token::Punct(")") => depth -= 1,
+										_ => {},
+									}
+									tokens.push(token);
+								},
+							}
+						}
+						if !lit.is_empty() {
+							parts.push(StrPart::Lit(std::mem::take(&mut lit)));
+						}
+						parts.push(StrPart::Interp(Parser { tokens, pos: 0 }.parse_all()?));
+					},
+					Some(c) => return Err(format!("Invalid escape \\{}", c)),
+					None => return Err(String::from("Unterminated string")),
+				},
+				Some(c) => lit.push(c),
+			}
+		}
+		if !lit.is_empty() {
+			parts.push(StrPart::Lit(lit));
+		}
+		Ok(parts)
+	}
+
+	fn unicode_escape(&mut self) -> Result<char, String> {
+		let hex4 = |lexer: &mut Lexer| {
+			let hex = lexer.src.get(lexer.pos..lexer.pos + 4).ok_or_else(|| String::from("Invalid \\u escape"))?;
+			let n = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid \\u escape {}", hex))?;
+			lexer.pos += 4;
+			Ok::<u32, String>(n)
+		};
+		let high = hex4(self)?;
+		if (0xD800..0xDC00).contains(&high) && self.src[self.pos..].starts_with("\\u") {
+			self.pos += 2;
+			let low = hex4(self)?;
+			return char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)).ok_or_else(|| String::from("Invalid surrogate pair"));
+		}
+		char::from_u32(high).ok_or_else(|| format!("Invalid \\u escape {:04x}", high))
+	}
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	pos: usize,
+}
+
+impl Parser {
+	fn parse_all(mut self) -> Result<Expr, String> {
+		if self.tokens.is_empty() {
+			return Ok(Expr::Identity);
+		}
+		let expr = self.parse_pipe(true)?;
+		match self.peek() {
+			Some(token) => Err(format!("Unexpected {}", token.describe())),
+			None => Ok(expr),
+		}
+	}
+
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos)
+	}
+
+	fn next(&mut self) -> Option<Token> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		token
+	}
+
+	fn eat_punct(&mut self, p: &str) -> bool {
+		match self.peek() {
+			Some(Token::Punct(q)) if *q == p => {
+				self.pos += 1;
+				true
+			},
+			_ => false,
+		}
+	}
+
+	fn eat_keyword(&mut self, k: &str) -> bool {
+		match self.peek() {
+			Some(Token::Ident(name)) if name == k => {
+				self.pos += 1;
+				true
+			},
+			_ => false,
+		}
+	}
+
+	fn expected(&self, what: &str) -> String {
+		match self.peek() {
+			Some(token) => format!("Expected {}, found {}", what, token.describe()),
+			None => format!("Expected {}, found end of expression", what),
+		}
+	}
+
+	fn expect_punct(&mut self, p: &str) -> Result<(), String> {
+		if self.eat_punct(p) { Ok(()) } else { Err(self.expected(&format!("'{}'", p))) }
+	}
+
+	fn expect_keyword(&mut self, k: &str) -> Result<(), String> {
+		if self.eat_keyword(k) { Ok(()) } else { Err(self.expected(&format!("'{}'", k))) }
+	}
+
+	fn expect_var(&mut self) -> Result<String, String> {
+		match self.peek() {
+			Some(Token::Var(name)) => {
+				let name = name.clone();
+				self.pos += 1;
+				Ok(name)
+			},
+			_ => Err(self.expected("a $variable")),
+		}
+	}
+
+	// Object values may not contain a top-level ',' since it separates the entries.
+	fn parse_pipe(&mut self, allow_comma: bool) -> Result<Expr, String> {
+		let lhs = if allow_comma { self.parse_comma()? } else { self.parse_alt()? };
+		if self.eat_keyword("as") {
+			let var = self.expect_var()?;
+			self.expect_punct("|")?;
+			let body = self.parse_pipe(allow_comma)?;
+			return Ok(Expr::As(Box::new(lhs), var, Box::new(body)));
+		}
+		if self.eat_punct("|") {
+			let rhs = self.parse_pipe(allow_comma)?;
+			return Ok(Expr::Pipe(Box::new(lhs), Box::new(rhs)));
+		}
+		Ok(lhs)
+	}
+
+	fn parse_comma(&mut self) -> Result<Expr, String> {
+		let mut lhs = self.parse_alt()?;
+		while self.eat_punct(",") {
+			lhs = Expr::Comma(Box::new(lhs), Box::new(self.parse_alt()?));
+		}
+		Ok(lhs)
+	}
+
+	fn parse_alt(&mut self) -> Result<Expr, String> {
+		let lhs = self.parse_or()?;
+		if self.eat_punct("//") {
+			return Ok(Expr::Alt(Box::new(lhs), Box::new(self.parse_alt()?)));
+		}
+		Ok(lhs)
+	}
+
+	fn parse_or(&mut self) -> Result<Expr, String> {
+		let mut lhs = self.parse_and()?;
+		while self.eat_keyword("or") {
+			lhs = Expr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+		}
+		Ok(lhs)
+	}
+
+	fn parse_and(&mut self) -> Result<Expr, String> {
+		let mut lhs = self.parse_comparison()?;
+		while self.eat_keyword("and") {
+			lhs = Expr::And(Box::new(lhs), Box::new(self.parse_comparison()?));
+		}
+		Ok(lhs)
+	}
+
+	fn parse_comparison(&mut self) -> Result<Expr, String> {
+		let lhs = self.parse_additive()?;
+		let op = match self.peek() {
+			Some(Token::Punct("==")) => BinOp::Eq,
+			Some(Token::Punct("!=")) => BinOp::Ne,
+			Some(Token::Punct("<")) => BinOp::Lt,
+			Some(Token::Punct("<=")) => BinOp::Le,
+			Some(Token::Punct(">")) => BinOp::Gt,
+			Some(Token::Punct(">=")) => BinOp::Ge,
+			_ => return Ok(lhs),
+		};
+		self.pos += 1;
+		Ok(Expr::Binary(op, Box::new(lhs), Box::new(self.parse_additive()?)))
+	}
+
+	fn parse_additive(&mut self) -> Result<Expr, String> {
+		let mut lhs = self.parse_multiplicative()?;
+		loop {
+			let op = match self.peek() {
+				Some(Token::Punct("+")) => BinOp::Add,
+				Some(Token::Punct("-")) => BinOp::Sub,
+				_ => return Ok(lhs),
+			};
+			self.pos += 1;
+			lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_multiplicative()?));
+		}
+	}
+
+	fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+		let mut lhs = self.parse_unary()?;
+		loop {
+			let op = match self.peek() {
+				Some(Token::Punct("*")) => BinOp::Mul,
+				Some(Token::Punct("/")) => BinOp::Div,
+				Some(Token::Punct("%")) => BinOp::Mod,
+				_ => return Ok(lhs),
+			};
+			self.pos += 1;
+			lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.parse_unary()?));
+		}
+	}
+
+	fn parse_unary(&mut self) -> Result<Expr, String> {
+		if self.eat_punct("-") {
+			return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+		}
+		self.parse_postfix()
+	}
+
+	fn parse_postfix(&mut self) -> Result<Expr, String> {
+		let mut expr = self.parse_term()?;
+		loop {
+			match self.peek() {
+				Some(Token::Field(name)) => {
+					expr = Expr::Field(Box::new(expr), name.clone());
+					self.pos += 1;
+				},
+				// `.a.[0]` is the same as `.a[0]`
+				Some(Token::Dot) if matches!(self.tokens.get(self.pos + 1), Some(Token::Punct("["))) => {
+					self.pos += 1;
+				},
+				Some(Token::Punct("[")) => {
+					self.pos += 1;
+					expr = self.parse_brackets(expr)?;
+				},
+				Some(Token::Punct("?")) => {
+					self.pos += 1;
+					expr = Expr::Optional(Box::new(expr));
+				},
+				_ => return Ok(expr),
+			}
+		}
+	}
+
+	// `[]`, `[e]`, `[a:b]`, `[a:]` or `[:b]` after a term.
+	fn parse_brackets(&mut self, target: Expr) -> Result<Expr, String> {
+		let target = Box::new(target);
+		if self.eat_punct("]") {
+			return Ok(Expr::Iterate(target));
+		}
+		if self.eat_punct(":") {
+			let hi = self.parse_pipe(true)?;
+			self.expect_punct("]")?;
+			return Ok(Expr::Slice(target, None, Some(Box::new(hi))));
+		}
+		let index = self.parse_pipe(true)?;
+		if self.eat_punct(":") {
+			if self.eat_punct("]") {
+				return Ok(Expr::Slice(target, Some(Box::new(index)), None));
+			}
+			let hi = self.parse_pipe(true)?;
+			self.expect_punct("]")?;
+			return Ok(Expr::Slice(target, Some(Box::new(index)), Some(Box::new(hi))));
+		}
+		self.expect_punct("]")?;
+		Ok(Expr::Index(target, Box::new(index)))
+	}
+
+	fn parse_term(&mut self) -> Result<Expr, String> {
+		let token = self.next().ok_or_else(|| String::from("Unexpected end of expression"))?;
+		match token {
+			Token::Dot => Ok(Expr::Identity),
+			Token::DotDot => Ok(Expr::Recurse),
+			Token::Field(name) => Ok(Expr::Field(Box::new(Expr::Identity), name)),
+			Token::Num(n) => Ok(Expr::Literal(JsonElement::JsonNumber(n))),
+			Token::Str(parts) => Ok(Expr::Str(parts)),
+			Token::Var(name) => Ok(Expr::Var(name)),
+			Token::Punct("(") => {
+				let expr = self.parse_pipe(true)?;
+				self.expect_punct(")")?;
+				Ok(expr)
+			},
+			Token::Punct("[") => {
+				if self.eat_punct("]") {
+					return Ok(Expr::Array(None));
+				}
+				let expr = self.parse_pipe(true)?;
+				self.expect_punct("]")?;
+				Ok(Expr::Array(Some(Box::new(expr))))
+			},
+			Token::Punct("{") => self.parse_object(),
+			Token::Ident(name) => match name.as_str() {
+				"true" => Ok(Expr::Literal(JsonElement::JsonBool(true))),
+				"false" => Ok(Expr::Literal(JsonElement::JsonBool(false))),
+				"null" => Ok(Expr::Literal(JsonElement::JsonNull)),
+				"if" => self.parse_if(),
+				"try" => {
+					let body = self.parse_postfix()?;
+					let handler = if self.eat_keyword("catch") { Some(Box::new(self.parse_postfix()?)) } else { None };
+					Ok(Expr::Try(Box::new(body), handler))
+				},
+				"reduce" => {
+					let source = self.parse_postfix()?;
+					self.expect_keyword("as")?;
+					let var = self.expect_var()?;
+					self.expect_punct("(")?;
+					let init = self.parse_pipe(true)?;
+					self.expect_punct(";")?;
+					let update = self.parse_pipe(true)?;
+					self.expect_punct(")")?;
+					Ok(Expr::Reduce(Box::new(source), var, Box::new(init), Box::new(update)))
+				},
+				keyword if KEYWORDS.contains(&keyword) => Err(format!("Unexpected '{}'", keyword)),
+				_ => {
+					let mut args = Vec::new();
+					if self.eat_punct("(") {
+						loop {
+							args.push(self.parse_pipe(true)?);
+							if self.eat_punct(")") {
+								break;
+							}
+							self.expect_punct(";")?;
+						}
+					}
+					if !BUILTINS.contains(&(name.as_str(), args.len())) {
+						return Err(format!("{}/{} is not defined", name, args.len()));
+					}
+					Ok(Expr::Call(name, args))
+				},
+			},
+			token => Err(format!("Unexpected {}", token.describe())),
+		}
+	}
+
+	fn parse_if(&mut self) -> Result<Expr, String> {
+		let mut branches = Vec::new();
+		loop {
+			let cond = self.parse_pipe(true)?;
+			self.expect_keyword("then")?;
+			let body = self.parse_pipe(true)?;
+			branches.push((cond, body));
+			if self.eat_keyword("elif") {
+				continue;
+			}
+			if self.eat_keyword("else") {
+				let otherwise = self.parse_pipe(true)?;
+				self.expect_keyword("end")?;
+				return Ok(Expr::If(branches, Some(Box::new(otherwise))));
+			}
+			self.expect_keyword("end")?;
+			return Ok(Expr::If(branches, None));
+		}
+	}
+
+	// `{a: e, "b": e, (e): e, $x, c}`; a bare key `c` is short for `c: .c`.
+	fn parse_object(&mut self) -> Result<Expr, String> {
+		let mut entries = Vec::new();
+		if self.eat_punct("}") {
+			return Ok(Expr::Object(entries));
+		}
+		loop {
+			let (key, shorthand) = match self.next() {
+				Some(Token::Ident(name)) => (Expr::Literal(JsonElement::JsonString(name.clone())), Some(Expr::Field(Box::new(Expr::Identity), name))),
+				Some(Token::Var(name)) => (Expr::Literal(JsonElement::JsonString(name.clone())), Some(Expr::Var(name))),
+				Some(Token::Str(parts)) => {
+					let key = Expr::Str(parts);
+					(key.clone(), Some(Expr::Index(Box::new(Expr::Identity), Box::new(key))))
+				},
+				Some(Token::Punct("(")) => {
+					let key = self.parse_pipe(true)?;
+					self.expect_punct(")")?;
+					(key, None)
+				},
+				_ => {
+					self.pos -= 1;
+					return Err(self.expected("an object key"));
+				},
+			};
+			let value = if self.eat_punct(":") {
+				self.parse_pipe(false)?
+			} else {
+				shorthand.ok_or_else(|| self.expected("':'"))?
+			};
+			entries.push((key, value));
+			if self.eat_punct("}") {
+				return Ok(Expr::Object(entries));
+			}
+			self.expect_punct(",")?;
+		}
+	}
+}
+
+enum Env<'a> {
+	Root,
+	Bind(&'a str, &'a JsonElement, &'a Env<'a>),
+}
+
+impl Env<'_> {
+	fn lookup(&self, name: &str) -> Option<&JsonElement> {
+		match self {
+			Env::Root => None,
+			Env::Bind(n, value, _) if *n == name => Some(value),
+			Env::Bind(_, _, parent) => parent.lookup(name),
+		}
+	}
+}
+
+fn eval(expr: &Expr, input: &JsonElement, env: &Env) -> Result<Vec<JsonElement>, String> {
+	use JsonElement::*;
+
+	let mut out = Vec::new();
+	match expr {
+		Expr::Identity => out.push(input.clone()),
+		Expr::Recurse => recurse(input, &mut out),
+		Expr::Field(target, name) => {
+			let key = JsonString(name.clone());
+			for t in eval(target, input, env)? {
+				out.push(index(&t, &key)?);
+			}
+		},
+		Expr::Index(target, idx) => {
+			let idxs = eval(idx, input, env)?;
+			for t in eval(target, input, env)? {
+				for i in &idxs {
+					out.push(index(&t, i)?);
+				}
+			}
+		},
+		Expr::Slice(target, lo, hi) => {
+			let bounds = |bound: &Option<Box<Expr>>| match bound {
+				Some(e) => eval(e, input, env).map(|v| v.into_iter().map(Some).collect()),
+				None => Ok(vec![None]),
+			};
+			let (los, his): (Vec<Option<JsonElement>>, Vec<Option<JsonElement>>) = (bounds(lo)?, bounds(hi)?);
+			for t in eval(target, input, env)? {
+				for hi in &his {
+					for lo in &los {
+						out.push(slice(&t, lo.as_ref(), hi.as_ref())?);
+					}
+				}
+			}
+		},
+		Expr::Iterate(target) => {
+			for t in eval(target, input, env)? {
+				out.extend(iterate(&t)?);
+			}
+		},
+		Expr::Optional(inner) => out = eval(inner, input, env).unwrap_or_default(),
+		Expr::Literal(value) => out.push(value.clone()),
+		Expr::Str(parts) => {
+			let mut strings = vec![String::new()];
+			for part in parts {
+				match part {
+					StrPart::Lit(s) => strings.iter_mut().for_each(|acc| acc.push_str(s)),
+					StrPart::Interp(e) => {
+						let values = eval(e, input, env)?;
+						strings = values.iter().flat_map(|v| strings.iter().map(move |acc| format!("{}{}", acc, stringify(v)))).collect();
+					},
+				}
+			}
+			out.extend(strings.into_iter().map(JsonString));
+		},
+		Expr::Array(None) => out.push(JsonArray(Vec::new())),
+		Expr::Array(Some(e)) => out.push(JsonArray(eval(e, input, env)?)),
+		Expr::Object(entries) => {
+			let mut objects = vec![HashMap::new()];
+			for (key, value) in entries {
+				let keys = eval(key, input, env)?;
+				let values = eval(value, input, env)?;
+				let mut next = Vec::new();
+				for obj in &objects {
+					for k in &keys {
+						let k = match k {
+							JsonString(s) => s,
+							other => return Err(format!("Object keys must be strings, not {}", describe(other))),
+						};
+						for v in &values {
+							let mut obj: HashMap<String, JsonElement> = obj.clone();
+							obj.insert(k.clone(), v.clone());
+							next.push(obj);
+						}
+					}
+				}
+				objects = next;
+			}
+			out.extend(objects.into_iter().map(JsonObject));
+		},
+		Expr::Neg(e) => {
+			for v in eval(e, input, env)? {
+				match &v {
+					JsonNumber(n) => out.push(arith(&JsonNum::Integer(0), n, i64::checked_sub, |a, b| a - b)),
+					other => return Err(format!("{} cannot be negated", describe(other))),
+				}
+			}
+		},
+		// Like jq, the right-hand side varies slowest
+		Expr::Binary(op, lhs, rhs) => {
+			let ls = eval(lhs, input, env)?;
+			for r in eval(rhs, input, env)? {
+				for l in &ls {
+					out.push(binary(*op, l, &r)?);
+				}
+			}
+		},
+		Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+			let is_and = matches!(expr, Expr::And(..));
+			for l in eval(lhs, input, env)? {
+				if truthy(&l) != is_and {
+					out.push(JsonBool(!is_and));
+					continue;
+				}
+				for r in eval(rhs, input, env)? {
+					out.push(JsonBool(truthy(&r)));
+				}
+			}
+		},
+		Expr::Alt(lhs, rhs) => {
+			out = eval(lhs, input, env).unwrap_or_default().into_iter().filter(truthy).collect();
+			if out.is_empty() {
+				out = eval(rhs, input, env)?;
+			}
+		},
+		Expr::Pipe(lhs, rhs) => {
+			for v in eval(lhs, input, env)? {
+				out.extend(eval(rhs, &v, env)?);
+			}
+		},
+		Expr::Comma(lhs, rhs) => {
+			out = eval(lhs, input, env)?;
+			out.extend(eval(rhs, input, env)?);
+		},
+		Expr::If(branches, otherwise) => out = eval_if(branches, otherwise.as_deref(), input, env)?,
+		Expr::Try(body, handler) => {
+			out = match (eval(body, input, env), handler) {
+				(Ok(values), _) => values,
+				(Err(e), Some(handler)) => eval(handler, &JsonString(e), env)?,
+				(Err(_), None) => Vec::new(),
+			};
+		},
+		Expr::Reduce(source, var, init, update) => {
+			let items = eval(source, input, env)?;
+			for mut acc in eval(init, input, env)? {
+				for item in &items {
+					let env = Env::Bind(var, item, env);
+					acc = eval(update, &acc, &env)?.pop().unwrap_or(JsonNull);
+				}
+				out.push(acc);
+			}
+		},
+		Expr::As(source, var, body) => {
+			for value in eval(source, input, env)? {
+				out.extend(eval(body, input, &Env::Bind(var, &value, env))?);
+			}
+		},
+		Expr::Var(name) => out.push(env.lookup(name).cloned().ok_or_else(|| format!("${} is not defined", name))?),
+		Expr::Call(name, args) => out = call(name, args, input, env)?,
+	}
+	Ok(out)
+}
+
+fn eval_if(branches: &[(Expr, Expr)], otherwise: Option<&Expr>, input: &JsonElement, env: &Env) -> Result<Vec<JsonElement>, String> {
+	let ((cond, body), rest) = match branches.split_first() {
+		Some(split) => split,
+		None => return match otherwise {
+			Some(e) => eval(e, input, env),
+			None => Ok(vec![input.clone()]),
+		},
+	};
+	let mut out = Vec::new();
+	for c in eval(cond, input, env)? {
+		if truthy(&c) {
+			out.extend(eval(body, input, env)?);
+		} else {
+			out.extend(eval_if(rest, otherwise, input, env)?);
+		}
+	}
+	Ok(out)
+}
+
+fn truthy(value: &JsonElement) -> bool {
+	!matches!(value, JsonElement::JsonNull | JsonElement::JsonBool(false))
+}
+
+// jq calls every number "number"
+fn type_name(value: &JsonElement) -> &'static str {
+	match value {
+		JsonElement::JsonNumber(_) => "number",
+		other => other.type_name(),
+	}
+}
+
+fn describe(value: &JsonElement) -> String {
+	let text = value.to_string();
+	let text = if text.chars().count() > 11 { format!("{}...", text.chars().take(10).collect::<String>()) } else { text };
+	format!("{} ({})", type_name(value), text)
+}
+
+fn stringify(value: &JsonElement) -> String {
+	match value {
+		JsonElement::JsonString(s) => s.clone(),
+		other => other.to_string(),
+	}
+}
+
+// Integral results are kept as integers.
+fn number(n: f64) -> JsonElement {
+	if n.fract() == 0.0 && n.abs() < 9007199254740992.0 {
+		JsonElement::JsonNumber(JsonNum::Integer(n as i64))
+	} else {
+		JsonElement::JsonNumber(JsonNum::Double(n))
+	}
+}
+
+fn as_number(value: &JsonElement) -> Option<f64> {
+	match value {
+		JsonElement::JsonNumber(n) => Some(n.as_f64()),
+		_ => None,
+	}
+}
+
+fn arith(a: &JsonNum, b: &JsonNum, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64) -> JsonElement {
+	if let (JsonNum::Integer(x), JsonNum::Integer(y)) = (a, b) {
+		if let Some(r) = int_op(*x, *y) {
+			return JsonElement::JsonNumber(JsonNum::Integer(r));
+		}
+	}
+	number(float_op(a.as_f64(), b.as_f64()))
+}
+
+fn binary(op: BinOp, a: &JsonElement, b: &JsonElement) -> Result<JsonElement, String> {
+	use JsonElement::*;
+
+	let cmp = || compare(a, b);
+	Ok(match op {
+		BinOp::Eq => JsonBool(cmp() == Ordering::Equal),
+		BinOp::Ne => JsonBool(cmp() != Ordering::Equal),
+		BinOp::Lt => JsonBool(cmp() == Ordering::Less),
+		BinOp::Le => JsonBool(cmp() != Ordering::Greater),
+		BinOp::Gt => JsonBool(cmp() == Ordering::Greater),
+		BinOp::Ge => JsonBool(cmp() != Ordering::Less),
+		BinOp::Add => match (a, b) {
+			(JsonNull, x) | (x, JsonNull) => x.clone(),
+			(JsonNumber(x), JsonNumber(y)) => arith(x, y, i64::checked_add, |x, y| x + y),
+			(JsonString(x), JsonString(y)) => JsonString(format!("{}{}", x, y)),
+			(JsonArray(x), JsonArray(y)) => JsonArray(x.iter().chain(y.iter()).cloned().collect()),
+			(JsonObject(x), JsonObject(y)) => JsonObject(x.iter().chain(y.iter()).map(|(k, v)| (k.clone(), v.clone())).collect()),
+			_ => return Err(format!("{} and {} cannot be added", describe(a), describe(b))),
+		},
+		BinOp::Sub => match (a, b) {
+			(JsonNumber(x), JsonNumber(y)) => arith(x, y, i64::checked_sub, |x, y| x - y),
+			(JsonArray(x), JsonArray(y)) => JsonArray(x.iter().filter(|e| !y.iter().any(|r| compare(e, r) == Ordering::Equal)).cloned().collect()),
+			_ => return Err(format!("{} and {} cannot be subtracted", describe(a), describe(b))),
+		},
+		BinOp::Mul => match (a, b) {
+			(JsonNumber(x), JsonNumber(y)) => arith(x, y, i64::checked_mul, |x, y| x * y),
+			(JsonString(s), JsonNumber(n)) | (JsonNumber(n), JsonString(s)) => {
+				let n = n.as_f64();
+				if n <= 0.0 { JsonNull } else { JsonString(s.repeat(n.ceil() as usize)) }
+			},
+			(JsonObject(_), JsonObject(_)) => deep_merge(a, b),
+			_ => return Err(format!("{} and {} cannot be multiplied", describe(a), describe(b))),
+		},
+		BinOp::Div => match (a, b) {
+			(JsonNumber(_), JsonNumber(y)) if y.as_f64() == 0.0 => return Err(format!("{} and {} cannot be divided because the divisor is zero", describe(a), describe(b))),
+			(JsonNumber(x), JsonNumber(y)) => arith(x, y, |x, y| if x % y == 0 { x.checked_div(y) } else { None }, |x, y| x / y),
+			(JsonString(x), JsonString(y)) => split(x, y),
+			_ => return Err(format!("{} and {} cannot be divided", describe(a), describe(b))),
+		},
+		BinOp::Mod => match (a, b) {
+			(JsonNumber(x), JsonNumber(y)) => {
+				let (x, y) = (x.as_f64() as i64, y.as_f64() as i64);
+				if y == 0 {
+					return Err(format!("{} and {} cannot be divided because the divisor is zero", describe(a), describe(b)));
+				}
+				JsonNumber(JsonNum::Integer(x.wrapping_rem(y)))
+			},
+			_ => return Err(format!("{} and {} cannot be divided", describe(a), describe(b))),
+		},
+	})
+}
+
+// Objects present on both sides are merged key by key, anything else is taken from `b`.
+// Walks both trees with an explicit stack, so that deep documents don't overflow the call stack.
+fn deep_merge(a: &JsonElement, b: &JsonElement) -> JsonElement {
+	let mut merged = a.clone();
+	let mut stack = vec![(&mut merged, b)];
+	while let Some((target, source)) = stack.pop() {
+		match (target, source) {
+			(JsonElement::JsonObject(x), JsonElement::JsonObject(y)) => {
+				let added: HashSet<&String> = y.keys().filter(|k| !x.contains_key(*k)).collect();
+				for k in &added {
+					x.insert(k.to_string(), y[*k].clone());
+				}
+				for (k, existing) in x.iter_mut() {
+					match y.get(k) {
+						Some(v) if !added.contains(k) => stack.push((existing, v)),
+						_ => {},
+					}
+				}
+			},
+			(target, source) => *target = source.clone(),
+		}
+	}
+	merged
+}
+
+// jq's total order: null < false < true < numbers < strings < arrays < objects.
+// Objects compare their sorted key sets first, then their values key by key.
+fn compare(a: &JsonElement, b: &JsonElement) -> Ordering {
+	use JsonElement::*;
+
+	// Pairs are compared in order until one differs, from an explicit stack so that deep
+	// documents don't overflow the call stack. `Then` is the result if all pairs before it are equal.
+	enum Task<'a> {
+		Compare(&'a JsonElement, &'a JsonElement),
+		Then(Ordering),
+	}
+
+	let rank = |v: &JsonElement| match v {
+		JsonNull => 0,
+		JsonBool(false) => 1,
+		JsonBool(true) => 2,
+		JsonNumber(_) => 3,
+		JsonString(_) => 4,
+		JsonArray(_) => 5,
+		JsonObject(_) => 6,
+	};
+	let mut tasks = vec![Task::Compare(a, b)];
+	while let Some(task) = tasks.pop() {
+		let order = match task {
+			Task::Then(order) => order,
+			Task::Compare(a, b) => match (a, b) {
+				(JsonNumber(JsonNum::Integer(x)), JsonNumber(JsonNum::Integer(y))) => x.cmp(y),
+				(JsonNumber(x), JsonNumber(y)) => {
+					if x.is_equal(y, 0.0) {
+						Ordering::Equal
+					} else {
+						x.as_f64().partial_cmp(&y.as_f64()).unwrap_or(Ordering::Less)
+					}
+				},
+				(JsonString(x), JsonString(y)) => x.cmp(y),
+				(JsonArray(x), JsonArray(y)) => {
+					tasks.push(Task::Then(x.len().cmp(&y.len())));
+					tasks.extend(x.iter().zip(y.iter()).rev().map(|(l, r)| Task::Compare(l, r)));
+					Ordering::Equal
+				},
+				(JsonObject(x), JsonObject(y)) => {
+					let (xk, yk) = (sorted_keys(x), sorted_keys(y));
+					if xk == yk {
+						tasks.extend(xk.iter().rev().map(|k| Task::Compare(&x[*k], &y[*k])));
+					}
+					xk.cmp(&yk)
+				},
+				_ => rank(a).cmp(&rank(b)),
+			},
+		};
+		if order != Ordering::Equal {
+			return order;
+		}
+	}
+	Ordering::Equal
+}
+
+fn sorted_keys(obj: &HashMap<String, JsonElement>) -> Vec<&String> {
+	let mut keys: Vec<&String> = obj.keys().collect();
+	keys.sort();
+	keys
+}
+
+fn index(target: &JsonElement, idx: &JsonElement) -> Result<JsonElement, String> {
+	use JsonElement::*;
+
+	match (target, idx) {
+		(JsonNull, JsonString(_)) | (JsonNull, JsonNumber(_)) | (JsonNull, JsonNull) => Ok(JsonNull),
+		(JsonObject(obj), JsonString(k)) => Ok(obj.get(k).cloned().unwrap_or(JsonNull)),
+		(JsonArray(v), JsonNumber(n)) => {
+			let i = n.as_f64().floor() as i64;
+			let i = if i < 0 { v.len() as i64 + i } else { i };
+			Ok(if i >= 0 { v.get(i as usize).cloned().unwrap_or(JsonNull) } else { JsonNull })
+		},
+		(_, JsonString(k)) => Err(format!("Cannot index {} with \"{}\"", type_name(target), k)),
+		_ => Err(format!("Cannot index {} with {}", type_name(target), type_name(idx))),
+	}
+}
+
+fn slice(target: &JsonElement, lo: Option<&JsonElement>, hi: Option<&JsonElement>) -> Result<JsonElement, String> {
+	let len = match target {
+		JsonElement::JsonNull => return Ok(JsonElement::JsonNull),
+		JsonElement::JsonArray(v) => v.len(),
+		JsonElement::JsonString(s) => s.chars().count(),
+		other => return Err(format!("Cannot index {} with object", type_name(other))),
+	};
+	let bound = |b: Option<&JsonElement>, default: usize| -> Result<usize, String> {
+		match b {
+			None | Some(JsonElement::JsonNull) => Ok(default),
+			Some(JsonElement::JsonNumber(n)) => {
+				let i = n.as_f64().floor() as i64;
+				let i = if i < 0 { len as i64 + i } else { i };
+				Ok(i.clamp(0, len as i64) as usize)
+			},
+			Some(other) => Err(format!("Start and end indices of a slice must be numbers, not {}", type_name(other))),
+		}
+	};
+	let lo = bound(lo, 0)?;
+	let hi = bound(hi, len)?.max(lo);
+	Ok(match target {
+		JsonElement::JsonArray(v) => JsonElement::JsonArray(v[lo..hi].to_vec()),
+		JsonElement::JsonString(s) => JsonElement::JsonString(s.chars().skip(lo).take(hi - lo).collect()),
+		_ => unreachable!(),
+	})
+}
+
+// Object values come out in key order so that results are deterministic.
+fn iterate(value: &JsonElement) -> Result<Vec<JsonElement>, String> {
+	match value {
+		JsonElement::JsonArray(v) => Ok(v.clone()),
+		JsonElement::JsonObject(obj) => Ok(sorted_keys(obj).into_iter().map(|k| obj[k].clone()).collect()),
+		other => Err(format!("Cannot iterate over {}", describe(other))),
+	}
+}
+
+// Every value in pre-order, children in the order `iterate` gives them.
+fn recurse(value: &JsonElement, out: &mut Vec<JsonElement>) {
+	let mut stack = vec![value];
+	while let Some(value) = stack.pop() {
+		out.push(value.clone());
+		match value {
+			JsonElement::JsonArray(v) => stack.extend(v.iter().rev()),
+			JsonElement::JsonObject(obj) => stack.extend(sorted_keys(obj).into_iter().rev().map(|k| &obj[k])),
+			_ => {},
+		}
+	}
+}
+
+fn split(s: &str, sep: &str) -> JsonElement {
+	if s.is_empty() {
+		return JsonElement::JsonArray(Vec::new());
+	}
+	if sep.is_empty() {
+		return JsonElement::JsonArray(s.chars().map(|c| JsonElement::JsonString(c.to_string())).collect());
+	}
+	JsonElement::JsonArray(s.split(sep).map(|p| JsonElement::JsonString(p.to_string())).collect())
+}
+
+fn array<'a>(value: &'a JsonElement, name: &str) -> Result<&'a Vec<JsonElement>, String> {
+	match value {
+		JsonElement::JsonArray(v) => Ok(v),
+		other => Err(format!("{} requires an array, not {}", name, describe(other))),
+	}
+}
+
+fn string<'a>(value: &'a JsonElement, name: &str) -> Result<&'a str, String> {
+	match value {
+		JsonElement::JsonString(s) => Ok(s),
+		other => Err(format!("{} requires a string, not {}", name, describe(other))),
+	}
+}
+
+// Pairs every element with the outputs of `f` on it, sorted by those outputs.
+fn sorted_by(input: &JsonElement, f: &Expr, env: &Env, name: &str) -> Result<Vec<(JsonElement, JsonElement)>, String> {
+	let mut keyed = Vec::new();
+	for item in array(input, name)? {
+		keyed.push((JsonElement::JsonArray(eval(f, item, env)?), item.clone()));
+	}
+	keyed.sort_by(|a, b| compare(&a.0, &b.0));
+	Ok(keyed)
+}
+
+fn grouped(keyed: Vec<(JsonElement, JsonElement)>) -> Vec<Vec<JsonElement>> {
+	let mut groups: Vec<(JsonElement, Vec<JsonElement>)> = Vec::new();
+	for (key, item) in keyed {
+		match groups.last_mut() {
+			Some((last, group)) if compare(last, &key) == Ordering::Equal => group.push(item),
+			_ => groups.push((key, vec![item])),
+		}
+	}
+	groups.into_iter().map(|(_, group)| group).collect()
+}
+
+fn contains(a: &JsonElement, b: &JsonElement) -> Result<bool, String> {
+	use JsonElement::*;
+
+	// Containers being checked, innermost last, so that deep documents don't overflow the call
+	// stack. An object frame holds the members of `b` and the next one to look up; an array
+	// frame the element of `b` being searched for and the candidate in `a` being tried.
+	enum Frame<'a> {
+		Object(&'a HashMap<String, JsonElement>, Vec<(&'a String, &'a JsonElement)>, usize),
+		Array(&'a [JsonElement], &'a [JsonElement], usize, usize),
+	}
+
+	let mut frames: Vec<Frame> = Vec::new();
+	let mut pair = Some((a, b));
+	loop {
+		// The result of the last pair, or None when it opened a frame
+		let mut result = match pair.take() {
+			None => None,
+			Some((a, b)) => match (a, b) {
+				(JsonObject(x), JsonObject(y)) => {
+					frames.push(Frame::Object(x, y.iter().collect(), 0));
+					None
+				},
+				(JsonArray(x), JsonArray(y)) => {
+					frames.push(Frame::Array(x, y, 0, 0));
+					None
+				},
+				(JsonString(x), JsonString(y)) => Some(x.contains(y.as_str())),
+				_ if type_name(a) == type_name(b) => Some(compare(a, b) == Ordering::Equal),
+				_ => return Err(format!("{} and {} cannot have their containment checked", describe(a), describe(b))),
+			},
+		};
+
+		// Hand the result to the frames until one needs another pair checked
+		while pair.is_none() {
+			let done = match frames.last_mut() {
+				None => return Ok(result.unwrap_or(true)),
+				Some(Frame::Object(x, members, next)) => {
+					match result {
+						Some(false) => Some(false),
+						_ => {
+							if result.is_some() {
+								*next += 1;
+							}
+							match members.get(*next) {
+								None => Some(true),
+								Some((k, bv)) => match x.get(*k) {
+									Some(av) => {
+										pair = Some((av, *bv));
+										None
+									},
+									None => Some(false),
+								},
+							}
+						},
+					}
+				},
+				Some(Frame::Array(x, y, bi, ai)) => {
+					match result {
+						Some(true) => {
+							*bi += 1;
+							*ai = 0;
+						},
+						Some(false) => *ai += 1,
+						None => {},
+					}
+					if *bi == y.len() {
+						Some(true)
+					} else if *ai == x.len() {
+						Some(false)
+					} else {
+						pair = Some((&x[*ai], &y[*bi]));
+						None
+					}
+				},
+			};
+			if done.is_some() {
+				frames.pop();
+				result = done;
+			}
+		}
+	}
+}
+
+fn flatten(items: &[JsonElement], depth: f64, out: &mut Vec<JsonElement>) {
+	// The arrays being flattened, innermost last, with the depth left below each
+	let mut stack = vec![(items.iter(), depth)];
+	while let Some((iter, depth)) = stack.last_mut() {
+		let depth = *depth;
+		match iter.next() {
+			Some(JsonElement::JsonArray(v)) if depth > 0.0 => stack.push((v.iter(), depth - 1.0)),
+			Some(other) => out.push(other.clone()),
+			None => {
+				stack.pop();
+			},
+		}
+	}
+}
+
+fn to_entries(value: &JsonElement) -> Result<JsonElement, String> {
+	match value {
+		JsonElement::JsonObject(obj) => Ok(JsonElement::JsonArray(sorted_keys(obj).into_iter().map(|k| {
+			let mut entry = HashMap::new();
+			entry.insert("key".to_string(), JsonElement::JsonString(k.clone()));
+			entry.insert("value".to_string(), obj[k].clone());
+			JsonElement::JsonObject(entry)
+		}).collect())),
+		other => Err(format!("to_entries requires an object, not {}", describe(other))),
+	}
+}
+
+fn from_entries(value: &JsonElement) -> Result<JsonElement, String> {
+	let mut obj = HashMap::new();
+	for entry in array(value, "from_entries")? {
+		let field = |names: &[&str]| names.iter().find_map(|n| match entry {
+			JsonElement::JsonObject(e) => e.get(*n).filter(|v| **v != JsonElement::JsonNull).cloned(),
+			_ => None,
+		});
+		let key = match &mut field(&["key", "k", "name", "Name", "Key", "K"]) {
+			Some(JsonElement::JsonString(s)) => std::mem::take(s),
+			Some(JsonElement::JsonNumber(n)) => n.to_string(),
+			Some(JsonElement::JsonBool(b)) => b.to_string(),
+			_ => return Err(format!("Cannot use {} as an object entry", describe(entry))),
+		};
+		obj.insert(key, field(&["value", "v", "Value", "V"]).unwrap_or(JsonElement::JsonNull));
+	}
+	Ok(JsonElement::JsonObject(obj))
+}
+
+// Applies `f` to every output of the argument expression.
+fn with_arg<F: FnMut(&JsonElement) -> Result<JsonElement, String>>(arg: &Expr, input: &JsonElement, env: &Env, f: F) -> Result<Vec<JsonElement>, String> {
+	eval(arg, input, env)?.iter().map(f).collect()
+}
+
+fn call(name: &str, args: &[Expr], input: &JsonElement, env: &Env) -> Result<Vec<JsonElement>, String> {
+	use JsonElement::*;
+
+	let one = |value: JsonElement| Ok(vec![value]);
+	let math = |f: fn(f64) -> f64| match input {
+		JsonNumber(JsonNum::Integer(_)) if name != "sqrt" && name != "abs" => one(input.clone()),
+		JsonNumber(n) => one(number(f(n.as_f64()))),
+		other => Err(format!("{} requires a number, not {}", name, describe(other))),
+	};
+	let filter_type = |types: &[&str]| Ok(if types.contains(&type_name(input)) { vec![input.clone()] } else { Vec::new() });
+
+	match (name, args.len()) {
+		("empty", _) => Ok(Vec::new()),
+		("not", _) => one(JsonBool(!truthy(input))),
+		("length", _) => one(match input {
+			JsonNull => number(0.0),
+			JsonBool(_) => return Err(format!("{} has no length", describe(input))),
+			JsonNumber(n) => number(n.as_f64().abs()),
+			JsonString(s) => number(s.chars().count() as f64),
+			JsonArray(v) => number(v.len() as f64),
+			JsonObject(obj) => number(obj.len() as f64),
+		}),
+		("keys", _) | ("keys_unsorted", _) => one(match input {
+			JsonObject(obj) => JsonArray(sorted_keys(obj).into_iter().map(|k| JsonString(k.clone())).collect()),
+			JsonArray(v) => JsonArray((0..v.len()).map(|i| number(i as f64)).collect()),
+			other => return Err(format!("{} has no keys", describe(other))),
+		}),
+		("has", _) => with_arg(&args[0], input, env, |k| match (input, k) {
+			(JsonObject(obj), JsonString(k)) => Ok(JsonBool(obj.contains_key(k))),
+			(JsonArray(v), JsonNumber(n)) => Ok(JsonBool(n.as_f64() >= 0.0 && n.as_f64() < v.len() as f64)),
+			_ => Err(format!("Cannot check whether {} has a {} key", type_name(input), type_name(k))),
+		}),
+		("map", _) => {
+			let mut out = Vec::new();
+			for item in iterate(input)? {
+				out.extend(eval(&args[0], &item, env)?);
+			}
+			one(JsonArray(out))
+		},
+		("map_values", _) => {
+			let first = |v: &JsonElement| eval(&args[0], v, env).map(|mut out| if out.is_empty() { None } else { Some(out.swap_remove(0)) });
+			one(match input {
+				JsonObject(obj) => {
+					let mut mapped = HashMap::new();
+					for (k, v) in obj {
+						if let Some(v) = first(v)? {
+							mapped.insert(k.clone(), v);
+						}
+					}
+					JsonObject(mapped)
+				},
+				JsonArray(v) => {
+					let mut mapped = Vec::new();
+					for item in v {
+						mapped.extend(first(item)?);
+					}
+					JsonArray(mapped)
+				},
+				other => return Err(format!("Cannot iterate over {}", describe(other))),
+			})
+		},
+		("select", _) => Ok(eval(&args[0], input, env)?.iter().filter(|c| truthy(c)).map(|_| input.clone()).collect()),
+		("recurse", _) => eval(&Expr::Recurse, input, env),
+		("sort", _) => {
+			let mut items = array(input, name)?.clone();
+			items.sort_by(compare);
+			one(JsonArray(items))
+		},
+		("sort_by", _) => one(JsonArray(sorted_by(input, &args[0], env, name)?.into_iter().map(|(_, v)| v).collect())),
+		("group_by", _) => one(JsonArray(grouped(sorted_by(input, &args[0], env, name)?).into_iter().map(JsonArray).collect())),
+		("unique_by", _) => one(JsonArray(grouped(sorted_by(input, &args[0], env, name)?).into_iter().map(|g| g[0].clone()).collect())),
+		("min_by", _) => one(sorted_by(input, &args[0], env, name)?.into_iter().next().map(|(_, v)| v).unwrap_or(JsonNull)),
+		("max_by", _) => one(sorted_by(input, &args[0], env, name)?.into_iter().last().map(|(_, v)| v).unwrap_or(JsonNull)),
+		("unique", _) | ("min", _) | ("max", _) => {
+			let mut items = array(input, name)?.clone();
+			items.sort_by(compare);
+			one(match name {
+				"min" => items.first().cloned().unwrap_or(JsonNull),
+				"max" => items.last().cloned().unwrap_or(JsonNull),
+				_ => {
+					items.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+					JsonArray(items)
+				},
+			})
+		},
+		("add", _) => {
+			let mut acc = JsonNull;
+			for item in iterate(input)? {
+				acc = binary(BinOp::Add, &acc, &item)?;
+			}
+			one(acc)
+		},
+		("any", _) => one(JsonBool(array(input, name)?.iter().any(truthy))),
+		("all", _) => one(JsonBool(array(input, name)?.iter().all(truthy))),
+		("flatten", 0) => {
+			let mut out = Vec::new();
+			flatten(array(input, name)?, f64::INFINITY, &mut out);
+			one(JsonArray(out))
+		},
+		("flatten", _) => with_arg(&args[0], input, env, |depth| {
+			let depth = as_number(depth).filter(|d| *d >= 0.0).ok_or_else(|| String::from("flatten depth must not be negative"))?;
+			let mut out = Vec::new();
+			flatten(array(input, name)?, depth, &mut out);
+			Ok(JsonArray(out))
+		}),
+		("range", n) => {
+			let (starts, ends) = if n == 1 { (vec![number(0.0)], eval(&args[0], input, env)?) } else { (eval(&args[0], input, env)?, eval(&args[1], input, env)?) };
+			let mut out = Vec::new();
+			for start in &starts {
+				for end in &ends {
+					let (mut i, end) = match (as_number(start), as_number(end)) {
+						(Some(s), Some(e)) => (s, e),
+						_ => return Err(String::from("range bounds must be numbers")),
+					};
+					while i < end {
+						out.push(number(i));
+						i += 1.0;
+					}
+				}
+			}
+			Ok(out)
+		},
+		("reverse", _) => one(match input {
+			JsonNull => JsonArray(Vec::new()),
+			JsonString(s) => JsonString(s.chars().rev().collect()),
+			other => JsonArray(array(other, name)?.iter().rev().cloned().collect()),
+		}),
+		("tostring", _) => one(JsonString(stringify(input))),
+		("tojson", _) => one(JsonString(input.to_string())),
+		("tonumber", _) => match input {
+			JsonNumber(_) => one(input.clone()),
+			JsonString(s) => match crate::build_json_graph(s.trim()) {
+				Ok(n @ JsonNumber(_)) => one(n),
+				_ => Err(format!("Cannot parse '{}' as a number", s)),
+			},
+			other => Err(format!("{} cannot be parsed as a number", describe(other))),
+		},
+		("fromjson", _) => crate::build_json_graph(string(input, name)?).map(|v| vec![v]),
+		("type", _) => one(JsonString(type_name(input).to_string())),
+		("error", 0) => Err(stringify(input)),
+		("error", _) => Err(stringify(eval(&args[0], input, env)?.first().unwrap_or(&JsonNull))),
+		("to_entries", _) => one(to_entries(input)?),
+		("from_entries", _) => one(from_entries(input)?),
+		("with_entries", _) => {
+			let mut mapped = Vec::new();
+			if let JsonArray(entries) = &to_entries(input)? {
+				for entry in entries {
+					mapped.extend(eval(&args[0], entry, env)?);
+				}
+			}
+			one(from_entries(&JsonArray(mapped))?)
+		},
+		("join", _) => with_arg(&args[0], input, env, |sep| {
+			let sep = string(sep, name)?;
+			let mut parts = Vec::new();
+			for item in array(input, name)? {
+				parts.push(match item {
+					JsonNull => String::new(),
+					JsonString(s) => s.clone(),
+					JsonNumber(_) | JsonBool(_) => item.to_string(),
+					other => return Err(format!("Cannot join with {}", describe(other))),
+				});
+			}
+			Ok(JsonString(parts.join(sep)))
+		}),
+		("split", _) => with_arg(&args[0], input, env, |sep| Ok(split(string(input, name)?, string(sep, name)?))),
+		("test", _) => with_arg(&args[0], input, env, |re| {
+			let re = Regex::new(string(re, name)?).map_err(|e| e.to_string())?;
+			Ok(JsonBool(re.is_match(string(input, name)?)))
+		}),
+		("sub", _) | ("gsub", _) => {
+			let mut out = Vec::new();
+			for re in eval(&args[0], input, env)? {
+				let re = Regex::new(string(&re, name)?).map_err(|e| e.to_string())?;
+				for replacement in eval(&args[1], input, env)? {
+					let s = string(input, name)?;
+					let replacement = regex::NoExpand(string(&replacement, name)?);
+					out.push(JsonString(if name == "sub" { re.replace(s, replacement) } else { re.replace_all(s, replacement) }.into_owned()));
+				}
+			}
+			Ok(out)
+		},
+		("ascii_downcase", _) => one(JsonString(string(input, name)?.to_ascii_lowercase())),
+		("ascii_upcase", _) => one(JsonString(string(input, name)?.to_ascii_uppercase())),
+		("startswith", _) => with_arg(&args[0], input, env, |p| Ok(JsonBool(string(input, name)?.starts_with(string(p, name)?)))),
+		("endswith", _) => with_arg(&args[0], input, env, |p| Ok(JsonBool(string(input, name)?.ends_with(string(p, name)?)))),
+		("ltrimstr", _) | ("rtrimstr", _) => with_arg(&args[0], input, env, |p| Ok(match (input, p) {
+			(JsonString(s), JsonString(p)) if name == "ltrimstr" => JsonString(s.strip_prefix(p.as_str()).unwrap_or(s).to_string()),
+			(JsonString(s), JsonString(p)) => JsonString(s.strip_suffix(p.as_str()).unwrap_or(s).to_string()),
+			_ => input.clone(),
+		})),
+		("contains", _) => with_arg(&args[0], input, env, |b| contains(input, b).map(JsonBool)),
+		("first", 0) => one(index(input, &number(0.0))?),
+		("last", 0) => one(index(input, &number(-1.0))?),
+		("first", _) => Ok(eval(&args[0], input, env)?.into_iter().take(1).collect()),
+		("last", _) => Ok(eval(&args[0], input, env)?.pop().into_iter().collect()),
+		("limit", _) => {
+			let mut out = Vec::new();
+			for n in eval(&args[0], input, env)? {
+				let n = as_number(&n).ok_or_else(|| String::from("limit requires a number"))?;
+				out.extend(eval(&args[1], input, env)?.into_iter().take(n.max(0.0) as usize));
+			}
+			Ok(out)
+		},
+		("floor", _) => math(f64::floor),
+		("ceil", _) => math(f64::ceil),
+		("round", _) => math(f64::round),
+		("sqrt", _) => math(f64::sqrt),
+		("abs", _) => math(f64::abs),
+		("arrays", _) => filter_type(&["array"]),
+		("objects", _) => filter_type(&["object"]),
+		("iterables", _) => filter_type(&["array", "object"]),
+		("booleans", _) => filter_type(&["boolean"]),
+		("numbers", _) => filter_type(&["number"]),
+		("strings", _) => filter_type(&["string"]),
+		("nulls", _) => filter_type(&["null"]),
+		("scalars", _) => filter_type(&["null", "boolean", "number", "string"]),
+		("values", _) => filter_type(&["boolean", "number", "string", "array", "object"]),
+		_ => Err(format!("{}/{} is not defined", name, args.len())),
+	}
+}
@@ -0,0 +1,241 @@
+// Byte scanners for the minimizer, the parser and the parallel splitter. Each returns the
+// index of the first byte of interest, or the length of the input if there is none. x86_64
+// uses AVX2 when the CPU supports it and SSE2 otherwise; other targets compare eight bytes
+// at a time in a u64.
+
+// The end of a run of string contents: the closing quote or a backslash.
+pub(crate) fn find_string_end(hay: &[u8], quote: u8) -> usize {
+	scan(hay, Class::StringEnd(quote))
+}
+
+// The end of a run that minifies to itself: whitespace and other ASCII control characters,
+// '"', '/', and any non-ASCII byte, since some non-ASCII characters are whitespace as well.
+pub(crate) fn find_layout(hay: &[u8]) -> usize {
+	scan(hay, Class::Layout)
+}
+
+// The characters that open or close strings and containers, and commas.
+pub(crate) fn find_structural(hay: &[u8]) -> usize {
+	scan(hay, Class::Structural)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+	StringEnd(u8),
+	Layout,
+	Structural,
+}
+
+impl Class {
+	fn matches(self, b: u8) -> bool {
+		match self {
+			Class::StringEnd(quote) => b == quote || b == b'\\',
+			Class::Layout => b <= b' ' || b >= 0x80 || b == b'"' || b == b'/',
+			Class::Structural => matches!(b, b'"' | b'[' | b']' | b'{' | b'}' | b','),
+		}
+	}
+}
+
+fn scan(hay: &[u8], class: Class) -> usize {
+	#[cfg(target_arch = "x86_64")]
+	{
+		if hay.len() >= 32 && is_x86_feature_detected!("avx2") {
+			// Safety: AVX2 support was just checked.
+			return unsafe { x86::scan_avx2(hay, class) };
+		}
+		if hay.len() >= 16 {
+			// Safety: SSE2 is part of the x86_64 baseline.
+			return unsafe { x86::scan_sse2(hay, class) };
+		}
+	}
+	scan_words(hay, class)
+}
+
+fn scan_bytes(hay: &[u8], class: Class) -> usize {
+	hay.iter().position(|&b| class.matches(b)).unwrap_or(hay.len())
+}
+
+// Portable version: skips eight bytes at a time while none of them can match.
+fn scan_words(hay: &[u8], class: Class) -> usize {
+	const LO: u64 = 0x0101_0101_0101_0101;
+	const HI: u64 = 0x8080_8080_8080_8080;
+	// Flags (at least) every byte equal to zero, and never a word without one.
+	let has_zero = |x: u64| x.wrapping_sub(LO) & !x & HI;
+
+	let mut i = 0;
+	while i + 8 <= hay.len() {
+		let mut bytes = [0; 8];
+		bytes.copy_from_slice(&hay[i..i + 8]);
+		let word = u64::from_le_bytes(bytes);
+		let flags = match class {
+			Class::StringEnd(quote) => has_zero(word ^ (LO * quote as u64)) | has_zero(word ^ (LO * b'\\' as u64)),
+			// Bytes below 0x21 borrow in the subtraction; bytes of 0x80 and above have the top bit set.
+			Class::Layout => (word.wrapping_sub(LO * 0x21) & !word & HI) | (word & HI)
+				| has_zero(word ^ (LO * b'"' as u64)) | has_zero(word ^ (LO * b'/' as u64)),
+			Class::Structural => [b'"', b',', b'[', b']', b'{', b'}'].iter().fold(0, |flags, &c| flags | has_zero(word ^ (LO * c as u64))),
+		};
+		if flags != 0 {
+			return i + scan_bytes(&hay[i..i + 8], class);
+		}
+		i += 8;
+	}
+	i + scan_bytes(&hay[i..], class)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+	use std::arch::x86_64::*;
+
+	use super::{scan_bytes, Class};
+
+	#[target_feature(enable = "sse2")]
+	pub(super) unsafe fn scan_sse2(hay: &[u8], class: Class) -> usize {
+		let backslash = _mm_set1_epi8(b'\\' as i8);
+		let dquote = _mm_set1_epi8(b'"' as i8);
+		let slash = _mm_set1_epi8(b'/' as i8);
+		// As signed bytes, everything at or above 0x80 is negative and so below 0x21 as well.
+		let layout_bound = _mm_set1_epi8(0x21);
+		let structural = [_mm_set1_epi8(b',' as i8), _mm_set1_epi8(b'[' as i8), _mm_set1_epi8(b']' as i8), _mm_set1_epi8(b'{' as i8), _mm_set1_epi8(b'}' as i8)];
+
+		let mut i = 0;
+		while i + 16 <= hay.len() {
+			let v = _mm_loadu_si128(hay.as_ptr().add(i) as *const __m128i);
+			let mask = match class {
+				Class::StringEnd(quote) => _mm_or_si128(_mm_cmpeq_epi8(v, _mm_set1_epi8(quote as i8)), _mm_cmpeq_epi8(v, backslash)),
+				Class::Layout => _mm_or_si128(
+					_mm_cmplt_epi8(v, layout_bound),
+					_mm_or_si128(_mm_cmpeq_epi8(v, dquote), _mm_cmpeq_epi8(v, slash)),
+				),
+				Class::Structural => {
+					let mut m = _mm_cmpeq_epi8(v, dquote);
+					for &c in &structural {
+						m = _mm_or_si128(m, _mm_cmpeq_epi8(v, c));
+					}
+					m
+				},
+			};
+			let bits = _mm_movemask_epi8(mask);
+			if bits != 0 {
+				return i + bits.trailing_zeros() as usize;
+			}
+			i += 16;
+		}
+		i + scan_bytes(&hay[i..], class)
+	}
+
+	#[target_feature(enable = "avx2")]
+	pub(super) unsafe fn scan_avx2(hay: &[u8], class: Class) -> usize {
+		let backslash = _mm256_set1_epi8(b'\\' as i8);
+		let dquote = _mm256_set1_epi8(b'"' as i8);
+		let slash = _mm256_set1_epi8(b'/' as i8);
+		let layout_bound = _mm256_set1_epi8(0x21);
+		let structural = [_mm256_set1_epi8(b',' as i8), _mm256_set1_epi8(b'[' as i8), _mm256_set1_epi8(b']' as i8), _mm256_set1_epi8(b'{' as i8), _mm256_set1_epi8(b'}' as i8)];
+
+		let mut i = 0;
+		while i + 32 <= hay.len() {
+			let v = _mm256_loadu_si256(hay.as_ptr().add(i) as *const __m256i);
+			let mask = match class {
+				Class::StringEnd(quote) => _mm256_or_si256(_mm256_cmpeq_epi8(v, _mm256_set1_epi8(quote as i8)), _mm256_cmpeq_epi8(v, backslash)),
+				Class::Layout => _mm256_or_si256(
+					_mm256_cmpgt_epi8(layout_bound, v),
+					_mm256_or_si256(_mm256_cmpeq_epi8(v, dquote), _mm256_cmpeq_epi8(v, slash)),
+				),
+				Class::Structural => {
+					let mut m = _mm256_cmpeq_epi8(v, dquote);
+					for &c in &structural {
+						m = _mm256_or_si256(m, _mm256_cmpeq_epi8(v, c));
+					}
+					m
+				},
+			};
+			let bits = _mm256_movemask_epi8(mask);
+			if bits != 0 {
+				return i + bits.trailing_zeros() as usize;
+			}
+			i += 32;
+		}
+		i + scan_sse2(&hay[i..], class)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CLASSES: &[Class] = &[Class::StringEnd(b'"'), Class::StringEnd(b'\''), Class::Layout, Class::Structural];
+
+	type Backend = (&'static str, fn(&[u8], Class) -> usize);
+
+	// Every backend this CPU can run, each of which must agree with `scan_bytes`.
+	fn backends() -> Vec<Backend> {
+		let mut backends: Vec<Backend> = vec![("words", scan_words), ("dispatch", scan)];
+		#[cfg(target_arch = "x86_64")]
+		{
+			// Safety: SSE2 is part of the x86_64 baseline.
+			backends.push(("sse2", |hay, class| unsafe { x86::scan_sse2(hay, class) }));
+			if is_x86_feature_detected!("avx2") {
+				// Safety: AVX2 support was just checked.
+				backends.push(("avx2", |hay, class| unsafe { x86::scan_avx2(hay, class) }));
+			}
+		}
+		backends
+	}
+
+	fn check(hay: &[u8]) {
+		for &class in CLASSES {
+			let expected = scan_bytes(hay, class);
+			for (name, backend) in backends() {
+				assert_eq!(backend(hay, class), expected, "{} {:?} {:?}", name, class, hay);
+			}
+		}
+	}
+
+	// A small xorshift generator, so that failures reproduce.
+	struct Rng(u64);
+
+	impl Rng {
+		fn next(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+	}
+
+	// One byte of interest at every offset, in buffers long enough to take each backend through
+	// its wide loop and its tail.
+	#[test]
+	fn single_match_at_every_offset() {
+		for &special in b"\"'\\/[]{},\n\x00\x1f \x7f\x80\xff" {
+			for offset in 0..=64 {
+				for len in offset + 1..offset + 40 {
+					let mut hay = vec![b'a'; len];
+					hay[offset] = special;
+					check(&hay);
+					check(&hay[..offset]);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn random_buffers() {
+		const BYTES: &[u8] = b"\"'\\/[]{},: \t\nabcdefgh0123456789\x80\xc3\xa9\xff";
+		let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+		for len in 0..=130 {
+			for _ in 0..200 {
+				// Mostly plain bytes, so that matches are spread out
+				let hay: Vec<u8> = (0..len).map(|_| match rng.next() % 16 {
+					0 => BYTES[(rng.next() % BYTES.len() as u64) as usize],
+					1 => rng.next() as u8,
+					_ => b'x',
+				}).collect();
+				check(&hay);
+				// Unaligned starts
+				for start in 1..len.min(4) {
+					check(&hay[start..]);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,377 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{escape_pointer_token, json_equal, JsonElement, JsonNum};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+	pub instance_path: String,
+	pub schema_path: String,
+	pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(formatter, "{}: {} (schema: {})", display_path(&self.instance_path), self.message, display_path(&self.schema_path))
+	}
+}
+
+fn display_path(path: &str) -> &str {
+	if path.is_empty() { "/" } else { path }
+}
+
+// A JSON Schema (draft 2020-12) covering the core validation keywords.
+// `$ref` may only point into the schema document itself ("#" or "#/json/pointer").
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+	root: JsonElement,
+	patterns: HashMap<String, Regex>,
+}
+
+impl JsonSchema {
+	pub fn new(schema: JsonElement) -> Result<JsonSchema, String> {
+		let mut patterns = HashMap::new();
+		collect_patterns(&schema, &mut patterns)?;
+		Ok(JsonSchema { root: schema, patterns })
+	}
+
+	pub fn validate(&self, instance: &JsonElement) -> Vec<SchemaViolation> {
+		let mut violations = Vec::new();
+		let mut refs = Vec::new();
+		self.validate_at(&self.root, instance, "", "", &mut refs, &mut violations);
+		violations
+	}
+
+	pub fn is_valid(&self, instance: &JsonElement) -> bool {
+		self.validate(instance).is_empty()
+	}
+
+	fn is_valid_at(&self, schema: &JsonElement, instance: &JsonElement, instance_path: &str, schema_path: &str, refs: &mut Vec<(String, String)>) -> bool {
+		let mut violations = Vec::new();
+		self.validate_at(schema, instance, instance_path, schema_path, refs, &mut violations);
+		violations.is_empty()
+	}
+
+	fn validate_at(&self, schema: &JsonElement, instance: &JsonElement, instance_path: &str, schema_path: &str, refs: &mut Vec<(String, String)>, violations: &mut Vec<SchemaViolation>) {
+		use JsonElement::*;
+
+		macro_rules! violation {
+			($keyword:expr, $($msg:tt)*) => {{
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: format!("{}/{}", schema_path, $keyword),
+					message: format!($($msg)*),
+				});
+			}}
+		}
+
+		let keywords = match schema {
+			JsonBool(true) => return,
+			JsonBool(false) => {
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: schema_path.to_string(),
+					message: "schema is false; no value is allowed".to_string(),
+				});
+				return;
+			},
+			JsonObject(obj) => obj,
+			_ => {
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: schema_path.to_string(),
+					message: "schema must be an object or a boolean".to_string(),
+				});
+				return;
+			},
+		};
+
+		if let Some(t) = keywords.get("type") {
+			let allowed: Vec<&str> = match t {
+				JsonString(s) => vec![s.as_str()],
+				JsonArray(v) => v.iter().filter_map(|e| match e {
+					JsonString(s) => Some(s.as_str()),
+					_ => None,
+				}).collect(),
+				_ => Vec::new(),
+			};
+			if !allowed.iter().any(|t| type_matches(t, instance)) {
+				violation!("type", "expected {}, got {}", allowed.join(" or "), instance.type_name());
+			}
+		}
+
+		if let Some(JsonArray(values)) = keywords.get("enum") {
+			if !values.iter().any(|v| is_json_equal(v, instance)) {
+				violation!("enum", "{} is not one of the enumerated values", instance);
+			}
+		}
+
+		if let Some(value) = keywords.get("const") {
+			if !is_json_equal(value, instance) {
+				violation!("const", "expected {}, got {}", value, instance);
+			}
+		}
+
+		if let JsonNumber(n) = instance {
+			// Integers and decimals are compared exactly, as f64 would round large ones
+			let bound = |keyword| match keywords.get(keyword) {
+				Some(JsonNumber(b)) => n.compare(b).map(|ordering| (ordering, b)),
+				_ => None,
+			};
+			if let Some((ordering, b)) = bound("minimum") {
+				if ordering == Ordering::Less {
+					violation!("minimum", "{} is less than {}", n, b);
+				}
+			}
+			if let Some((ordering, b)) = bound("maximum") {
+				if ordering == Ordering::Greater {
+					violation!("maximum", "{} is greater than {}", n, b);
+				}
+			}
+			if let Some((ordering, b)) = bound("exclusiveMinimum") {
+				if ordering != Ordering::Greater {
+					violation!("exclusiveMinimum", "{} is less than or equal to {}", n, b);
+				}
+			}
+			if let Some((ordering, b)) = bound("exclusiveMaximum") {
+				if ordering != Ordering::Less {
+					violation!("exclusiveMaximum", "{} is greater than or equal to {}", n, b);
+				}
+			}
+		}
+
+		if let JsonString(s) = instance {
+			let len = s.chars().count();
+			if let Some(min) = size_keyword(keywords, "minLength") {
+				if len < min {
+					violation!("minLength", "string is shorter than {} characters", min);
+				}
+			}
+			if let Some(max) = size_keyword(keywords, "maxLength") {
+				if len > max {
+					violation!("maxLength", "string is longer than {} characters", max);
+				}
+			}
+			if let Some(JsonString(pattern)) = keywords.get("pattern") {
+				if let Some(re) = self.patterns.get(pattern) {
+					if !re.is_match(s) {
+						violation!("pattern", "string does not match pattern {}", pattern);
+					}
+				}
+			}
+		}
+
+		if let JsonArray(items) = instance {
+			if let Some(min) = size_keyword(keywords, "minItems") {
+				if items.len() < min {
+					violation!("minItems", "array has fewer than {} items", min);
+				}
+			}
+			if let Some(max) = size_keyword(keywords, "maxItems") {
+				if items.len() > max {
+					violation!("maxItems", "array has more than {} items", max);
+				}
+			}
+		}
+
+		if let JsonObject(members) = instance {
+			if let Some(min) = size_keyword(keywords, "minProperties") {
+				if members.len() < min {
+					violation!("minProperties", "object has fewer than {} properties", min);
+				}
+			}
+			if let Some(max) = size_keyword(keywords, "maxProperties") {
+				if members.len() > max {
+					violation!("maxProperties", "object has more than {} properties", max);
+				}
+			}
+			if let Some(JsonArray(required)) = keywords.get("required") {
+				for key in required {
+					if let JsonString(key) = key {
+						if !members.contains_key(key) {
+							violation!("required", "missing required property \"{}\"", key);
+						}
+					}
+				}
+			}
+		}
+
+		if let Some(JsonString(reference)) = keywords.get("$ref") {
+			let ref_path = format!("{}/$ref", schema_path);
+			let target = match reference.strip_prefix('#') {
+				Some(pointer) => self.root.pointer(pointer),
+				None => None,
+			};
+
+			match target {
+				None => violation!("$ref", "cannot resolve reference {}", reference),
+				Some(target) => {
+					let key = (reference.clone(), instance_path.to_string());
+					if refs.contains(&key) {
+						violation!("$ref", "circular reference {}", reference);
+					} else {
+						refs.push(key);
+						self.validate_at(target, instance, instance_path, &ref_path, refs, violations);
+						refs.pop();
+					}
+				},
+			}
+		}
+
+		match instance {
+			JsonObject(members) => {
+				let properties = match keywords.get("properties") {
+					Some(JsonObject(p)) => Some(p),
+					_ => None,
+				};
+				let mut keys: Vec<&String> = members.keys().collect();
+				keys.sort();
+				for key in keys {
+					let value = &members[key];
+					let child_path = format!("{}/{}", instance_path, escape_pointer_token(key));
+					match properties.and_then(|p| p.get(key)) {
+						Some(subschema) => {
+							let sub_path = format!("{}/properties/{}", schema_path, escape_pointer_token(key));
+							self.validate_at(subschema, value, &child_path, &sub_path, refs, violations);
+						},
+						None => {
+							if let Some(subschema) = keywords.get("additionalProperties") {
+								let sub_path = format!("{}/additionalProperties", schema_path);
+								self.validate_at(subschema, value, &child_path, &sub_path, refs, violations);
+							}
+						},
+					}
+				}
+			},
+			JsonArray(items) => {
+				if let Some(subschema) = keywords.get("items") {
+					let sub_path = format!("{}/items", schema_path);
+					for (i, item) in items.iter().enumerate() {
+						let child_path = format!("{}/{}", instance_path, i);
+						self.validate_at(subschema, item, &child_path, &sub_path, refs, violations);
+					}
+				}
+			},
+			_ => {},
+		}
+
+		if let Some(JsonArray(subschemas)) = keywords.get("allOf") {
+			for (i, subschema) in subschemas.iter().enumerate() {
+				let sub_path = format!("{}/allOf/{}", schema_path, i);
+				self.validate_at(subschema, instance, instance_path, &sub_path, refs, violations);
+			}
+		}
+
+		if let Some(JsonArray(subschemas)) = keywords.get("anyOf") {
+			let matched = subschemas.iter().enumerate().any(|(i, subschema)| {
+				let sub_path = format!("{}/anyOf/{}", schema_path, i);
+				self.is_valid_at(subschema, instance, instance_path, &sub_path, refs)
+			});
+			if !matched {
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: format!("{}/anyOf", schema_path),
+					message: "value does not match any schema in anyOf".to_string(),
+				});
+			}
+		}
+
+		if let Some(JsonArray(subschemas)) = keywords.get("oneOf") {
+			let matched = subschemas.iter().enumerate().filter(|(i, subschema)| {
+				let sub_path = format!("{}/oneOf/{}", schema_path, i);
+				self.is_valid_at(subschema, instance, instance_path, &sub_path, refs)
+			}).count();
+			if matched != 1 {
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: format!("{}/oneOf", schema_path),
+					message: format!("value matches {} schemas in oneOf, expected exactly 1", matched),
+				});
+			}
+		}
+
+		if let Some(subschema) = keywords.get("not") {
+			let sub_path = format!("{}/not", schema_path);
+			if self.is_valid_at(subschema, instance, instance_path, &sub_path, refs) {
+				violations.push(SchemaViolation {
+					instance_path: instance_path.to_string(),
+					schema_path: sub_path,
+					message: "value must not match the schema in not".to_string(),
+				});
+			}
+		}
+	}
+}
+
+// Compiles the patterns of every schema reachable from `root`: the keywords holding subschemas,
+// and the targets of `$ref`. Values such as `const`, `enum` or `examples` are data and skipped.
+fn collect_patterns(root: &JsonElement, patterns: &mut HashMap<String, Regex>) -> Result<(), String> {
+	let mut refs = Vec::new();
+	let mut pending = vec![(root, String::new())];
+	while let Some((schema, schema_path)) = pending.pop() {
+		let keywords = match schema {
+			JsonElement::JsonObject(obj) => obj,
+			_ => continue,
+		};
+		for (k, v) in keywords.iter() {
+			let sub_path = format!("{}/{}", schema_path, escape_pointer_token(k));
+			match (k.as_str(), v) {
+				("pattern", JsonElement::JsonString(p)) => {
+					let re = Regex::new(p).map_err(|e| format!("invalid pattern at {}: {}", sub_path, e))?;
+					patterns.insert(p.clone(), re);
+				},
+				("properties", JsonElement::JsonObject(subschemas)) | ("$defs", JsonElement::JsonObject(subschemas)) | ("definitions", JsonElement::JsonObject(subschemas)) => {
+					for (name, subschema) in subschemas.iter() {
+						pending.push((subschema, format!("{}/{}", sub_path, escape_pointer_token(name))));
+					}
+				},
+				("allOf", JsonElement::JsonArray(subschemas)) | ("anyOf", JsonElement::JsonArray(subschemas)) | ("oneOf", JsonElement::JsonArray(subschemas)) => {
+					for (i, subschema) in subschemas.iter().enumerate() {
+						pending.push((subschema, format!("{}/{}", sub_path, i)));
+					}
+				},
+				("additionalProperties", _) | ("items", _) | ("not", _) => pending.push((v, sub_path)),
+				("$ref", JsonElement::JsonString(reference)) if !refs.contains(reference) => {
+					refs.push(reference.clone());
+					if let Some(pointer) = reference.strip_prefix('#') {
+						if let Some(target) = root.pointer(pointer) {
+							pending.push((target, pointer.to_string()));
+						}
+					}
+				},
+				_ => {},
+			}
+		}
+	}
+	Ok(())
+}
+
+fn size_keyword(keywords: &HashMap<String, JsonElement>, keyword: &str) -> Option<usize> {
+	match keywords.get(keyword) {
+		Some(JsonElement::JsonNumber(JsonNum::Integer(n))) if *n >= 0 => Some(*n as usize),
+		Some(JsonElement::JsonNumber(n)) if n.as_f64() >= 0.0 => Some(n.as_f64() as usize),
+		_ => None,
+	}
+}
+
+fn is_json_equal(a: &JsonElement, b: &JsonElement) -> bool {
+	json_equal(a, b, 0.0)
+}
+
+fn type_matches(t: &str, instance: &JsonElement) -> bool {
+	use JsonElement::*;
+
+	match (t, instance) {
+		("null", JsonNull) => true,
+		("boolean", JsonBool(_)) => true,
+		("string", JsonString(_)) => true,
+		("array", JsonArray(_)) => true,
+		("object", JsonObject(_)) => true,
+		("number", JsonNumber(_)) => true,
+		("integer", JsonNumber(JsonNum::Integer(_))) => true,
+		("integer", JsonNumber(n)) => n.as_f64().fract() == 0.0,
+		_ => false,
+	}
+}
@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{self, SerializeMap, Serializer};
+use serde::{forward_to_deserialize_any, Deserialize, Serialize};
+
+use crate::convert::wide_integer;
+use crate::{JsonElement, JsonNum};
+
+// Converts any serializable value into a JsonElement, following the same data model as
+// serde_json: structs and maps become objects, enums are externally tagged, and map keys
+// that are numbers or booleans are turned into strings.
+pub fn to_element<T: Serialize>(value: &T) -> Result<JsonElement, String> {
+	value.serialize(ElementSerializer).map_err(|e| e.0)
+}
+
+pub fn from_element<T: DeserializeOwned>(elm: &JsonElement) -> Result<T, String> {
+	T::deserialize(ElementDeserializer(elm.clone())).map_err(|e| e.0)
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+	fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "{}", self.0)
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error(msg.to_string())
+	}
+}
+
+impl de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error(msg.to_string())
+	}
+}
+
+impl Serialize for JsonNum {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			JsonNum::Integer(i) => serializer.serialize_i64(*i),
+			JsonNum::Double(d) => serializer.serialize_f64(*d),
+			// serde has no arbitrary-precision numbers; use the narrowest type that holds the value
+			JsonNum::Decimal(s) => {
+				if let Ok(i) = s.parse::<i64>() {
+					serializer.serialize_i64(i)
+				} else if let Ok(u) = s.parse::<u64>() {
+					serializer.serialize_u64(u)
+				} else {
+					serializer.serialize_f64(self.as_f64())
+				}
+			},
+		}
+	}
+}
+
+impl Serialize for JsonElement {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use JsonElement::*;
+
+		match self {
+			JsonNumber(n) => n.serialize(serializer),
+			JsonString(s) => serializer.serialize_str(s),
+			JsonArray(v) => serializer.collect_seq(v),
+			JsonObject(obj) => {
+				// Sorted like the Display impl so that serialized output is stable
+				let mut keys: Vec<&String> = obj.keys().collect();
+				keys.sort();
+				let mut map = serializer.serialize_map(Some(keys.len()))?;
+				for k in keys {
+					map.serialize_entry(k, &obj[k])?;
+				}
+				map.end()
+			},
+			JsonNull => serializer.serialize_unit(),
+			JsonBool(b) => serializer.serialize_bool(*b),
+		}
+	}
+}
+
+struct JsonNumVisitor;
+
+impl<'de> Visitor<'de> for JsonNumVisitor {
+	type Value = JsonNum;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "a number")
+	}
+
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<JsonNum, E> {
+		Ok(JsonNum::Integer(v))
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<JsonNum, E> {
+		Ok(wide_integer(v))
+	}
+
+	fn visit_i128<E: de::Error>(self, v: i128) -> Result<JsonNum, E> {
+		Ok(wide_integer(v))
+	}
+
+	fn visit_u128<E: de::Error>(self, v: u128) -> Result<JsonNum, E> {
+		Ok(wide_integer(v))
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<JsonNum, E> {
+		Ok(JsonNum::Double(v))
+	}
+}
+
+impl<'de> Deserialize<'de> for JsonNum {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<JsonNum, D::Error> {
+		deserializer.deserialize_any(JsonNumVisitor)
+	}
+}
+
+struct JsonElementVisitor;
+
+impl<'de> Visitor<'de> for JsonElementVisitor {
+	type Value = JsonElement;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		write!(formatter, "any JSON value")
+	}
+
+	fn visit_bool<E: de::Error>(self, v: bool) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonBool(v))
+	}
+
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNumber(JsonNum::Integer(v)))
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn visit_i128<E: de::Error>(self, v: i128) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn visit_u128<E: de::Error>(self, v: u128) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNumber(JsonNum::Double(v)))
+	}
+
+	fn visit_str<E: de::Error>(self, v: &str) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonString(v.to_string()))
+	}
+
+	fn visit_string<E: de::Error>(self, v: String) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonString(v))
+	}
+
+	fn visit_none<E: de::Error>(self) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNull)
+	}
+
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<JsonElement, D::Error> {
+		JsonElement::deserialize(deserializer)
+	}
+
+	fn visit_unit<E: de::Error>(self) -> Result<JsonElement, E> {
+		Ok(JsonElement::JsonNull)
+	}
+
+	fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<JsonElement, D::Error> {
+		JsonElement::deserialize(deserializer)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonElement, A::Error> {
+		let mut elems = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+		while let Some(elm) = seq.next_element()? {
+			elems.push(elm);
+		}
+		Ok(JsonElement::JsonArray(elems))
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonElement, A::Error> {
+		let mut obj = HashMap::new();
+		while let Some(k) = map.next_key()? {
+			obj.insert(k, map.next_value()?);
+		}
+		Ok(JsonElement::JsonObject(obj))
+	}
+}
+
+impl<'de> Deserialize<'de> for JsonElement {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<JsonElement, D::Error> {
+		deserializer.deserialize_any(JsonElementVisitor)
+	}
+}
+
+struct ElementSerializer;
+
+impl Serializer for ElementSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+	type SerializeSeq = SeqSerializer;
+	type SerializeTuple = SeqSerializer;
+	type SerializeTupleStruct = SeqSerializer;
+	type SerializeTupleVariant = SeqSerializer;
+	type SerializeMap = MapSerializer;
+	type SerializeStruct = MapSerializer;
+	type SerializeStructVariant = MapSerializer;
+
+	fn serialize_bool(self, v: bool) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonBool(v))
+	}
+
+	fn serialize_i8(self, v: i8) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i16(self, v: i16) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i32(self, v: i32) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i64(self, v: i64) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNumber(JsonNum::Integer(v)))
+	}
+
+	fn serialize_i128(self, v: i128) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn serialize_u8(self, v: u8) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_u16(self, v: u16) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_u32(self, v: u32) -> Result<JsonElement, Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_u64(self, v: u64) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn serialize_u128(self, v: u128) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNumber(wide_integer(v)))
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<JsonElement, Error> {
+		self.serialize_f64(v as f64)
+	}
+
+	fn serialize_f64(self, v: f64) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNumber(JsonNum::Double(v)))
+	}
+
+	fn serialize_char(self, v: char) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonString(v.to_string()))
+	}
+
+	fn serialize_str(self, v: &str) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonString(v.to_string()))
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonArray(v.iter().map(|b| JsonElement::JsonNumber(JsonNum::Integer(*b as i64))).collect()))
+	}
+
+	fn serialize_none(self) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNull)
+	}
+
+	fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<JsonElement, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNull)
+	}
+
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonNull)
+	}
+
+	fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<JsonElement, Error> {
+		Ok(JsonElement::JsonString(variant.to_string()))
+	}
+
+	fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<JsonElement, Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<JsonElement, Error> {
+		let mut obj = HashMap::new();
+		obj.insert(variant.to_string(), value.serialize(self)?);
+		Ok(JsonElement::JsonObject(obj))
+	}
+
+	fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+		Ok(SeqSerializer { variant: None, elems: Vec::with_capacity(len.unwrap_or(0)) })
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, Error> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, Error> {
+		Ok(SeqSerializer { variant: Some(variant), elems: Vec::with_capacity(len) })
+	}
+
+	fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+		Ok(MapSerializer { variant: None, obj: HashMap::new(), key: None })
+	}
+
+	fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapSerializer, Error> {
+		self.serialize_map(None)
+	}
+
+	fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<MapSerializer, Error> {
+		Ok(MapSerializer { variant: Some(variant), obj: HashMap::new(), key: None })
+	}
+}
+
+// Enum variants are written as `{"Variant": value}`.
+fn wrap_variant(variant: Option<&'static str>, value: JsonElement) -> JsonElement {
+	match variant {
+		Some(v) => {
+			let mut obj = HashMap::new();
+			obj.insert(v.to_string(), value);
+			JsonElement::JsonObject(obj)
+		},
+		None => value,
+	}
+}
+
+struct SeqSerializer {
+	variant: Option<&'static str>,
+	elems: Vec<JsonElement>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+		self.elems.push(value.serialize(ElementSerializer)?);
+		Ok(())
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		Ok(wrap_variant(self.variant, JsonElement::JsonArray(self.elems)))
+	}
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+		ser::SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		ser::SerializeSeq::end(self)
+	}
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+		ser::SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		ser::SerializeSeq::end(self)
+	}
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+		ser::SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		ser::SerializeSeq::end(self)
+	}
+}
+
+struct MapSerializer {
+	variant: Option<&'static str>,
+	obj: HashMap<String, JsonElement>,
+	key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+		self.key = Some(match &mut key.serialize(ElementSerializer)? {
+			JsonElement::JsonString(s) => std::mem::take(s),
+			JsonElement::JsonNumber(n) => n.to_string(),
+			JsonElement::JsonBool(b) => b.to_string(),
+			other => return Err(Error(format!("Object keys must be strings, numbers or booleans, not {}", other.type_name()))),
+		});
+		Ok(())
+	}
+
+	fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+		let key = self.key.take().ok_or_else(|| Error(String::from("serialize_value called before serialize_key")))?;
+		self.obj.insert(key, value.serialize(ElementSerializer)?);
+		Ok(())
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		Ok(wrap_variant(self.variant, JsonElement::JsonObject(self.obj)))
+	}
+}
+
+impl ser::SerializeStruct for MapSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		self.obj.insert(key.to_string(), value.serialize(ElementSerializer)?);
+		Ok(())
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		ser::SerializeMap::end(self)
+	}
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+	type Ok = JsonElement;
+	type Error = Error;
+
+	fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+		ser::SerializeStruct::serialize_field(self, key, value)
+	}
+
+	fn end(self) -> Result<JsonElement, Error> {
+		ser::SerializeMap::end(self)
+	}
+}
+
+struct ElementDeserializer(JsonElement);
+
+impl<'de> IntoDeserializer<'de, Error> for ElementDeserializer {
+	type Deserializer = ElementDeserializer;
+
+	fn into_deserializer(self) -> ElementDeserializer {
+		self
+	}
+}
+
+impl<'de> Deserializer<'de> for ElementDeserializer {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		use JsonElement::*;
+
+		// JsonElement implements Drop, so children are taken out through a mutable borrow.
+		let mut elm = self.0;
+		match &mut elm {
+			JsonNull => visitor.visit_unit(),
+			JsonBool(b) => visitor.visit_bool(*b),
+			JsonNumber(JsonNum::Integer(i)) => visitor.visit_i64(*i),
+			JsonNumber(JsonNum::Double(d)) => visitor.visit_f64(*d),
+			JsonNumber(n @ JsonNum::Decimal(_)) => {
+				let text = n.to_string();
+				if let Ok(i) = text.parse::<i64>() {
+					visitor.visit_i64(i)
+				} else if let Ok(u) = text.parse::<u64>() {
+					visitor.visit_u64(u)
+				} else {
+					visitor.visit_f64(n.as_f64())
+				}
+			},
+			JsonString(s) => visitor.visit_string(std::mem::take(s)),
+			JsonArray(v) => {
+				let mut seq = SeqDeserializer::new(std::mem::take(v).into_iter().map(ElementDeserializer));
+				let value = visitor.visit_seq(&mut seq)?;
+				seq.end()?;
+				Ok(value)
+			},
+			JsonObject(obj) => {
+				let mut map = MapDeserializer::new(std::mem::take(obj).into_iter().map(|(k, v)| (KeyDeserializer(k), ElementDeserializer(v))));
+				let value = visitor.visit_map(&mut map)?;
+				map.end()?;
+				Ok(value)
+			},
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		match self.0 {
+			JsonElement::JsonNull => visitor.visit_none(),
+			_ => visitor.visit_some(self),
+		}
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	// Accepts `"Variant"` for unit variants and `{"Variant": value}` for the others.
+	fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		let mut elm = self.0;
+		match &mut elm {
+			JsonElement::JsonString(variant) => visitor.visit_enum(EnumDeserializer { variant: std::mem::take(variant), value: None }),
+			JsonElement::JsonObject(obj) if obj.len() == 1 => {
+				let (variant, value) = std::mem::take(obj).into_iter().next().unwrap();
+				visitor.visit_enum(EnumDeserializer { variant, value: Some(value) })
+			},
+			other => Err(Error(format!("Expected a string or an object with one key for an enum, found {}", other.type_name()))),
+		}
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+	}
+}
+
+// Object keys are always strings; they are parsed when the target expects a number or boolean key.
+struct KeyDeserializer(String);
+
+impl<'de> IntoDeserializer<'de, Error> for KeyDeserializer {
+	type Deserializer = KeyDeserializer;
+
+	fn into_deserializer(self) -> KeyDeserializer {
+		self
+	}
+}
+
+macro_rules! deserialize_parsed_key {
+	($($method:ident => $visit:ident: $ty:ty),*) => {
+		$(
+			fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+				match self.0.parse::<$ty>() {
+					Ok(n) => visitor.$visit(n),
+					Err(_) => visitor.visit_string(self.0),
+				}
+			}
+		)*
+	};
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_string(self.0)
+	}
+
+	deserialize_parsed_key! {
+		deserialize_bool => visit_bool: bool,
+		deserialize_i8 => visit_i8: i8,
+		deserialize_i16 => visit_i16: i16,
+		deserialize_i32 => visit_i32: i32,
+		deserialize_i64 => visit_i64: i64,
+		deserialize_i128 => visit_i128: i128,
+		deserialize_u8 => visit_u8: u8,
+		deserialize_u16 => visit_u16: u16,
+		deserialize_u32 => visit_u32: u32,
+		deserialize_u64 => visit_u64: u64,
+		deserialize_u128 => visit_u128: u128,
+		deserialize_f32 => visit_f32: f32,
+		deserialize_f64 => visit_f64: f64
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_enum(EnumDeserializer { variant: self.0, value: None })
+	}
+
+	forward_to_deserialize_any! {
+		char str string bytes byte_buf option unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+	}
+}
+
+struct EnumDeserializer {
+	variant: String,
+	value: Option<JsonElement>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+	type Error = Error;
+	type Variant = VariantDeserializer;
+
+	fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, VariantDeserializer), Error> {
+		let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+		Ok((variant, VariantDeserializer(self.value)))
+	}
+}
+
+struct VariantDeserializer(Option<JsonElement>);
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+	type Error = Error;
+
+	fn unit_variant(self) -> Result<(), Error> {
+		match self.0 {
+			None | Some(JsonElement::JsonNull) => Ok(()),
+			Some(other) => Err(Error(format!("Expected a unit variant, found {}", other.type_name()))),
+		}
+	}
+
+	fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+		match self.0 {
+			Some(value) => seed.deserialize(ElementDeserializer(value)),
+			None => Err(Error(String::from("Expected a newtype variant, found a unit variant"))),
+		}
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+		match self.0 {
+			Some(value @ JsonElement::JsonArray(_)) => ElementDeserializer(value).deserialize_any(visitor),
+			_ => Err(Error(String::from("Expected an array for a tuple variant"))),
+		}
+	}
+
+	fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		match self.0 {
+			Some(value @ JsonElement::JsonObject(_)) => ElementDeserializer(value).deserialize_any(visitor),
+			_ => Err(Error(String::from("Expected an object for a struct variant"))),
+		}
+	}
+}
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{escape_pointer_token, JsonElement, JsonNum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSetting {
+	// How many entries the "largest"/"longest" rankings keep.
+	pub top_n: usize,
+}
+
+impl Default for StatsSetting {
+	fn default() -> StatsSetting {
+		StatsSetting {
+			top_n: 5,
+		}
+	}
+}
+
+// Structural statistics of a document. Paths are JSON Pointers; in `type_histogram`
+// every array index is replaced by `*` so that all elements of an array share one entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct JsonStats {
+	pub max_depth: usize,
+	pub type_counts: BTreeMap<&'static str, usize>,
+	pub largest_arrays: Vec<(String, usize)>,
+	pub largest_objects: Vec<(String, usize)>,
+	pub longest_strings: Vec<(String, usize)>,
+	pub distinct_keys: usize,
+	pub total_string_bytes: usize,
+	pub type_histogram: BTreeMap<String, BTreeMap<&'static str, usize>>,
+}
+
+impl JsonStats {
+	pub fn new(elm: &JsonElement, settings: StatsSetting) -> JsonStats {
+		let mut stats = JsonStats::default();
+		let mut keys = HashSet::new();
+		stats.visit(elm, settings.top_n, &mut keys);
+		stats.distinct_keys = keys.len();
+		stats
+	}
+
+	pub fn node_count(&self) -> usize {
+		self.type_counts.values().sum()
+	}
+
+	// Walks the tree depth-first with an explicit stack, so that deep documents don't overflow
+	// the call stack. Each entry holds the length of its parent's paths, which are a prefix of
+	// `path` and `generic_path` when it is popped, and its own index or key.
+	fn visit<'a>(&mut self, elm: &'a JsonElement, top_n: usize, keys: &mut HashSet<&'a str>) {
+		use JsonElement::*;
+
+		let mut path = String::new();
+		let mut generic_path = String::new();
+		let mut stack = vec![(elm, 1, 0, 0, None, None)];
+		while let Some((elm, depth, path_len, generic_len, index, key)) = stack.pop() {
+			path.truncate(path_len);
+			generic_path.truncate(generic_len);
+			if let Some(i) = index {
+				path.push_str(&format!("/{}", i));
+				generic_path.push_str("/*");
+			}
+			if let Some(k) = key {
+				let token = escape_pointer_token(k);
+				path.push_str(&format!("/{}", token));
+				generic_path.push_str(&format!("/{}", token));
+			}
+
+			self.max_depth = self.max_depth.max(depth);
+			*self.type_counts.entry(elm.type_name()).or_insert(0) += 1;
+			match self.type_histogram.get_mut(&generic_path) {
+				Some(counts) => *counts.entry(elm.type_name()).or_insert(0) += 1,
+				None => {
+					self.type_histogram.entry(generic_path.clone()).or_default().insert(elm.type_name(), 1);
+				},
+			}
+
+			match elm {
+				JsonString(s) => {
+					self.total_string_bytes += s.len();
+					rank(&mut self.longest_strings, top_n, &path, s.chars().count());
+				},
+				JsonArray(v) => {
+					rank(&mut self.largest_arrays, top_n, &path, v.len());
+					for (i, child) in v.iter().enumerate() {
+						stack.push((child, depth + 1, path.len(), generic_path.len(), Some(i), None));
+					}
+				},
+				JsonObject(obj) => {
+					rank(&mut self.largest_objects, top_n, &path, obj.len());
+					for (k, child) in obj.iter() {
+						keys.insert(k);
+						stack.push((child, depth + 1, path.len(), generic_path.len(), None, Some(k.as_str())));
+					}
+				},
+				_ => {},
+			}
+		}
+	}
+
+	pub fn to_element(&self) -> JsonElement {
+		use JsonElement::*;
+
+		let count = |n: usize| JsonNumber(JsonNum::Integer(n as i64));
+		let ranking = |r: &Vec<(String, usize)>| {
+			JsonArray(r.iter().map(|(path, n)| {
+				let mut entry = HashMap::new();
+				entry.insert("path".to_string(), JsonString(path.clone()));
+				entry.insert("size".to_string(), count(*n));
+				JsonObject(entry)
+			}).collect())
+		};
+		let counts = |c: &BTreeMap<&'static str, usize>| {
+			JsonObject(c.iter().map(|(t, n)| (t.to_string(), count(*n))).collect())
+		};
+
+		let mut obj = HashMap::new();
+		obj.insert("max_depth".to_string(), count(self.max_depth));
+		obj.insert("nodes".to_string(), count(self.node_count()));
+		obj.insert("type_counts".to_string(), counts(&self.type_counts));
+		obj.insert("largest_arrays".to_string(), ranking(&self.largest_arrays));
+		obj.insert("largest_objects".to_string(), ranking(&self.largest_objects));
+		obj.insert("longest_strings".to_string(), ranking(&self.longest_strings));
+		obj.insert("distinct_keys".to_string(), count(self.distinct_keys));
+		obj.insert("total_string_bytes".to_string(), count(self.total_string_bytes));
+		obj.insert("type_histogram".to_string(), JsonObject(self.type_histogram.iter().map(|(p, c)| (p.clone(), counts(c))).collect()));
+		JsonObject(obj)
+	}
+}
+
+// Keeps the `top_n` largest sizes, ties broken by path, in that order.
+fn rank(ranking: &mut Vec<(String, usize)>, top_n: usize, path: &str, size: usize) {
+	let pos = ranking.partition_point(|(p, n)| *n > size || (*n == size && p.as_str() < path));
+	if pos < top_n {
+		ranking.insert(pos, (path.to_string(), size));
+		ranking.truncate(top_n);
+	}
+}
+
+fn display_path(path: &str) -> &str {
+	if path.is_empty() { "/" } else { path }
+}
+
+// Renders the statistics as a plain text table.
+impl std::fmt::Display for JsonStats {
+	fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(formatter, "{:<24}{}", "max depth", self.max_depth)?;
+		writeln!(formatter, "{:<24}{}", "nodes", self.node_count())?;
+		for (t, n) in self.type_counts.iter() {
+			writeln!(formatter, "  {:<22}{}", t, n)?;
+		}
+		writeln!(formatter, "{:<24}{}", "distinct keys", self.distinct_keys)?;
+		writeln!(formatter, "{:<24}{}", "total string bytes", self.total_string_bytes)?;
+
+		for (title, ranking) in &[("largest arrays", &self.largest_arrays), ("largest objects", &self.largest_objects), ("longest strings", &self.longest_strings)] {
+			if ranking.is_empty() {
+				continue;
+			}
+			writeln!(formatter)?;
+			writeln!(formatter, "{}", title)?;
+			for (path, n) in ranking.iter() {
+				writeln!(formatter, "  {:>10}  {}", n, display_path(path))?;
+			}
+		}
+
+		writeln!(formatter)?;
+		writeln!(formatter, "type histogram")?;
+		let width = self.type_histogram.keys().map(|p| display_path(p).chars().count()).max().unwrap_or(0);
+		for (path, counts) in self.type_histogram.iter() {
+			let counts: Vec<String> = counts.iter().map(|(t, n)| format!("{}: {}", t, n)).collect();
+			writeln!(formatter, "  {:<width$}  {}", display_path(path), counts.join(", "), width = width)?;
+		}
+		Ok(())
+	}
+}
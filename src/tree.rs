@@ -0,0 +1,141 @@
+use crate::JsonElement;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeSetting {
+	// Containers deeper than this are shown without their children.
+	pub max_depth: Option<usize>,
+	// Only the first `max_children` children of a container are shown.
+	pub max_children: Option<usize>,
+	// Arrays whose elements all have the same shape are shown as one line, e.g. `[ 1000 × object{id,name} ]`.
+	pub collapse_homogeneous: bool,
+	pub ascii: bool,
+	// Strings longer than this many characters are truncated in previews.
+	pub preview_width: usize,
+}
+
+impl Default for TreeSetting {
+	fn default() -> TreeSetting {
+		TreeSetting {
+			max_depth: None,
+			max_children: None,
+			collapse_homogeneous: false,
+			ascii: false,
+			preview_width: 40,
+		}
+	}
+}
+
+struct Glyphs {
+	branch: &'static str,
+	last: &'static str,
+	pipe: &'static str,
+	blank: &'static str,
+	times: &'static str,
+	ellipsis: &'static str,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs { branch: "├── ", last: "└── ", pipe: "│   ", blank: "    ", times: "×", ellipsis: "…" };
+const ASCII_GLYPHS: Glyphs = Glyphs { branch: "|-- ", last: "`-- ", pipe: "|   ", blank: "    ", times: "x", ellipsis: "..." };
+
+pub fn render_tree(elm: &JsonElement, settings: TreeSetting) -> String {
+	use JsonElement::*;
+
+	// Nodes are rendered depth-first from an explicit stack, so that deep documents don't
+	// overflow the call stack.
+	enum Task<'a> {
+		Node { elm: &'a JsonElement, label: Option<String>, prefix: String, child_prefix: String, depth: usize },
+		Line(String),
+	}
+
+	let glyphs = if settings.ascii { &ASCII_GLYPHS } else { &UNICODE_GLYPHS };
+	let mut out = String::new();
+	let mut tasks = vec![Task::Node { elm, label: None, prefix: String::new(), child_prefix: String::new(), depth: 0 }];
+	while let Some(task) = tasks.pop() {
+		let (elm, label, prefix, child_prefix, depth) = match task {
+			Task::Line(line) => {
+				out.push_str(&line);
+				continue;
+			},
+			Task::Node { elm, label, prefix, child_prefix, depth } => (elm, label, prefix, child_prefix, depth),
+		};
+
+		out.push_str(&prefix);
+		if let Some(label) = label {
+			out.push_str(&label);
+			out.push_str(": ");
+		}
+
+		let children: Vec<(String, &JsonElement)> = match elm {
+			JsonArray(v) => {
+				if settings.collapse_homogeneous && v.len() > 1 {
+					let shape = shape_of(&v[0]);
+					if v[1..].iter().all(|e| shape_of(e) == shape) {
+						out.push_str(&format!("[ {} {} {} ]\n", v.len(), glyphs.times, shape));
+						continue;
+					}
+				}
+				out.push_str(&format!("array[{}]\n", v.len()));
+				v.iter().enumerate().map(|(i, e)| (format!("[{}]", i), e)).collect()
+			},
+			JsonObject(obj) => {
+				out.push_str(&format!("object{{{}}}\n", obj.len()));
+				let mut keys: Vec<&String> = obj.keys().collect();
+				keys.sort();
+				keys.into_iter().map(|k| (k.clone(), &obj[k])).collect()
+			},
+			JsonNull => {
+				out.push_str("null\n");
+				continue;
+			},
+			_ => {
+				out.push_str(&format!("{} {}\n", elm.type_name(), preview(elm, settings.preview_width, glyphs)));
+				continue;
+			},
+		};
+
+		if settings.max_depth.is_some_and(|max| depth >= max) {
+			continue;
+		}
+
+		// Pushed in reverse, so that the first child is rendered first
+		let shown = settings.max_children.unwrap_or(children.len()).min(children.len());
+		let hidden = children.len() - shown;
+		if hidden > 0 {
+			tasks.push(Task::Line(format!("{}{}{} {} more\n", child_prefix, glyphs.last, glyphs.ellipsis, hidden)));
+		}
+		for (i, (label, child)) in children.into_iter().take(shown).enumerate().rev() {
+			let is_last = i + 1 == shown && hidden == 0;
+			let (branch, cont) = if is_last { (glyphs.last, glyphs.blank) } else { (glyphs.branch, glyphs.pipe) };
+			tasks.push(Task::Node {
+				elm: child,
+				label: Some(label),
+				prefix: format!("{}{}", child_prefix, branch),
+				child_prefix: format!("{}{}", child_prefix, cont),
+				depth: depth + 1,
+			});
+		}
+	}
+	out
+}
+
+fn shape_of(elm: &JsonElement) -> String {
+	match elm {
+		JsonElement::JsonObject(obj) => {
+			let mut keys: Vec<&str> = obj.keys().map(|k| k.as_str()).collect();
+			keys.sort();
+			format!("object{{{}}}", keys.join(","))
+		},
+		_ => elm.type_name().to_string(),
+	}
+}
+
+fn preview(elm: &JsonElement, width: usize, glyphs: &Glyphs) -> String {
+	let text = elm.to_string();
+	if text.chars().count() <= width {
+		text
+	} else {
+		let mut truncated: String = text.chars().take(width).collect();
+		truncated.push_str(glyphs.ellipsis);
+		truncated
+	}
+}
@@ -0,0 +1,139 @@
+use minjson::{json, JsonArena, JsonElement, JsonNode, ParseSetting};
+
+fn arena(json: &str) -> JsonArena {
+	minjson::build_json_arena(json).unwrap()
+}
+
+// Checks every read of `node`, and of everything below it, against the same value parsed by
+// `build_json_graph`. `pointer` is the node's path from `root`, which is looked up too.
+fn check(root: JsonNode, node: JsonNode, elm: &JsonElement, pointer: &str) {
+	use JsonElement::*;
+
+	assert_eq!(root.pointer(pointer).map(|n| n.to_element()).as_ref(), Some(elm), "{}", pointer);
+	assert_eq!(node.to_element(), *elm, "{}", pointer);
+	assert_eq!(node.to_string(), elm.to_string(), "{}", pointer);
+	assert_eq!(node.type_name(), elm.type_name(), "{}", pointer);
+	assert_eq!(node.is_null(), *elm == JsonNull);
+	assert_eq!(node.as_bool(), if let JsonBool(b) = elm { Some(*b) } else { None });
+	assert_eq!(node.as_number().as_ref(), if let JsonNumber(n) = elm { Some(n) } else { None });
+	assert_eq!(node.as_str(), if let JsonString(s) = elm { Some(s.as_str()) } else { None });
+
+	match elm {
+		JsonArray(v) => {
+			assert_eq!((node.len(), node.is_empty()), (v.len(), v.is_empty()));
+			assert_eq!(node.elements().count(), v.len());
+			assert!(node.element(v.len()).is_none());
+			assert!(node.get("0").is_none());
+			for (i, (child, e)) in node.elements().zip(v).enumerate() {
+				assert_eq!(node.element(i).map(|n| n.to_element()), Some(child.to_element()));
+				check(root, child, e, &format!("{}/{}", pointer, i));
+			}
+		},
+		JsonObject(obj) => {
+			assert_eq!((node.len(), node.is_empty()), (obj.len(), obj.is_empty()));
+			let mut keys: Vec<&String> = obj.keys().collect();
+			keys.sort();
+			let members: Vec<(&str, JsonNode)> = node.members().collect();
+			assert_eq!(members.iter().map(|(k, _)| *k).collect::<Vec<_>>(), keys, "{}", pointer);
+			for (key, child) in members {
+				let found = node.get(key).unwrap_or_else(|| panic!("{}: get({:?}) found nothing", pointer, key));
+				assert_eq!(found.to_element(), child.to_element());
+				check(root, child, &obj[key], &format!("{}/{}", pointer, minjson::escape_pointer_token(key)));
+			}
+			// Keys next to the real ones in sort order must not be found
+			for key in keys {
+				let mut shorter = key.clone();
+				shorter.pop();
+				for missing in [format!("{}\0", key), format!("{}~", key), shorter] {
+					assert_eq!(node.get(&missing).is_some(), obj.contains_key(&missing), "{}: get({:?})", pointer, missing);
+				}
+			}
+			assert!(node.element(0).is_none());
+		},
+		_ => {
+			assert_eq!((node.len(), node.is_empty()), (0, true));
+			assert_eq!(node.elements().count() + node.members().count(), 0);
+			assert!(node.get("").is_none() && node.element(0).is_none());
+		},
+	}
+}
+
+fn check_document(json: &str) {
+	let elm = minjson::build_json_graph(json).unwrap();
+	let arena = arena(json);
+	check(arena.root(), arena.root(), &elm, "");
+	assert_eq!(JsonElement::from(&arena), elm);
+	assert_eq!(arena.to_string(), elm.to_string());
+}
+
+#[test]
+fn reads_match_the_element_tree() {
+	for json in &[
+		"null", "true", "-0", "1.5e3", "12345678901234567890", r#""sé\n""#, "[]", "{}",
+		r#"[1, [2, [3, []]], {"a": {"b": [null, false]}}]"#,
+		r#"{"": 0, "~": 1, "/": 2, "a/b~c": 3, "é": 4, "a": [{"": {"": null}}]}"#,
+	] {
+		check_document(json);
+	}
+	let settings = ParseSetting { arbitrary_precision: true, ..Default::default() };
+	let json = "[0.1, 1e400, -123456789012345678901234567890.5]";
+	let arena = minjson::build_json_arena_with_setting(json, settings).unwrap();
+	let elm = minjson::build_json_graph_with_setting(json, settings).unwrap();
+	check(arena.root(), arena.root(), &elm, "");
+}
+
+// Keys that share prefixes and differ in case and length, in an order unlike their sorted one,
+// so that the binary search in `get` has to take both directions.
+#[test]
+fn binary_search_over_many_keys() {
+	let mut keys: Vec<String> = Vec::new();
+	for i in (0..200).rev() {
+		keys.push(format!("k{}", i));
+		keys.push(format!("K{}", i * 7 % 31));
+	}
+	keys.extend(["", "k", "kk", "k0\0", "\u{10ffff}", "\u{e9}", "Z"].iter().map(|s| s.to_string()));
+	let members: Vec<String> = keys.iter().enumerate().map(|(i, k)| format!("{}: {}", JsonElement::from(k.as_str()), i)).collect();
+	let json = format!("{{{}}}", members.join(", "));
+	check_document(&json);
+
+	let arena = arena(&json);
+	for missing in &["k200", "K31", "k-1", "a", "\u{10fffe}", "kkk"] {
+		assert!(arena.root().get(missing).is_none(), "{}", missing);
+	}
+}
+
+#[test]
+fn last_duplicate_key_wins() {
+	let json = r#"{"a": 1, "b": [1], "a": {"x": 1}, "c": null, "b": [2], "a": [3], "b": "last"}"#;
+	check_document(json);
+	let doc = arena(json);
+	let root = doc.root();
+	assert_eq!(root.len(), 3);
+	assert_eq!(root.get("a").unwrap().to_element(), json!([3]));
+	assert_eq!(root.get("b").unwrap().as_str(), Some("last"));
+	assert_eq!(doc.pointer("/a/0").unwrap().to_element(), json!(3));
+	assert!(doc.pointer("/a/x").is_none());
+	assert_eq!(root.members().map(|(k, _)| k).collect::<Vec<_>>(), ["a", "b", "c"]);
+	// The replaced values are still stored
+	assert!(doc.node_count() > 7);
+
+	// In nested objects too, each keeping its own duplicates apart
+	let json = r#"{"o": {"k": 1, "k": 2}, "p": {"k": 3}, "o": {"k": 4, "j": 0, "k": 5}}"#;
+	check_document(json);
+	assert_eq!(JsonElement::from(&arena(json)), json!({"o": {"k": 5, "j": 0}, "p": {"k": 3}}));
+}
+
+#[test]
+fn pointers() {
+	let arena = arena(r#"{"a": [10, {"b~c": {"d/e": true}}], "": {"": "empty"}, "01": 1}"#);
+	assert_eq!(arena.pointer("").unwrap().len(), 3);
+	assert_eq!(arena.pointer("/a/1/b~0c/d~1e").unwrap().as_bool(), Some(true));
+	assert_eq!(arena.pointer("//").unwrap().as_str(), Some("empty"));
+	assert_eq!(arena.pointer("/01").unwrap().to_element(), json!(1));
+	assert!(arena.pointer("a").is_none());
+	assert!(arena.pointer("/a/2").is_none());
+	assert!(arena.pointer("/a/-1").is_none());
+	assert!(arena.pointer("/a/0/x").is_none());
+	let node = arena.pointer("/a/1").unwrap();
+	assert_eq!(node.pointer("/b~0c/d~1e").unwrap().as_bool(), Some(true));
+}
@@ -0,0 +1,36 @@
+use minjson::{json, CodegenLang, JsonElement};
+
+fn rust(sample: JsonElement) -> String {
+	minjson::generate_types(&[sample], "Root", CodegenLang::Rust)
+}
+
+// The `pub name: Type` lines of the generated code, in order.
+fn fields(code: &str) -> Vec<&str> {
+	code.lines().filter_map(|l| l.trim().strip_prefix("pub ")?.split(':').next()).filter(|f| !f.starts_with("type ") && !f.starts_with("struct ")).collect()
+}
+
+#[test]
+fn keys_without_identifier_characters() {
+	let code = rust(json!({"": 1, "_": 2, "__": 3, "a-b": "x", "a_b": "y", "type": true}));
+	let fields = fields(&code);
+	assert_eq!(fields, ["field", "field_2", "field_3", "a_b", "a_b_2", "r#type"], "{}", code);
+	for field in &fields {
+		let ident = field.trim_start_matches("r#");
+		assert!(ident.starts_with(|c: char| c.is_ascii_alphabetic()) || (ident.starts_with('_') && ident.len() > 1), "{}", field);
+	}
+	// "a_b" is renamed too, as "a-b" took its field name
+	for key in ["", "_", "__", "a-b", "a_b"] {
+		assert!(code.contains(&format!("#[serde(rename = \"{}\")]", key)), "{} is not renamed in {}", key, code);
+	}
+	assert!(!code.contains("rename = \"type\""));
+}
+
+#[test]
+fn root_array_item_name() {
+	assert!(rust(json!([{"a": 1}])).contains("pub type Root = Vec<RootItem>;\n"));
+	assert!(rust(json!([[{"a": 1}]])).contains("pub type Root = Vec<Vec<RootItem>>;\n"));
+	let code = rust(json!({"lines": [{"a": 1}], "entries": [{"b": 2}]}));
+	assert!(code.contains("pub lines: Vec<Line>,") && code.contains("pub entries: Vec<Entry>,"), "{}", code);
+	let ts = minjson::generate_types(&[json!([{"a": 1}])], "Root", CodegenLang::TypeScript);
+	assert!(ts.contains("export type Root = RootItem[];\n") && ts.contains("export interface RootItem {"), "{}", ts);
+}
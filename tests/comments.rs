@@ -0,0 +1,58 @@
+use minjson::{JsonFormatter, JsonMinimizer, PrettySetting};
+
+fn pretty(json: &str) -> String {
+	JsonFormatter::new_from_str(json, PrettySetting { indent_width: 2 }).collect()
+}
+
+fn minify(json: &str) -> String {
+	JsonMinimizer::new_from_str(json).collect()
+}
+
+#[test]
+fn trailing_comment_keeps_line_break() {
+	assert_eq!(pretty("{\"a\": 1, /* note */\n \"b\": 2}"), "{\n  \"a\": 1, /* note */\n  \"b\": 2\n}");
+	assert_eq!(pretty("[ /* first */ 1, 2]"), "[ /* first */\n  1,\n  2\n]");
+}
+
+#[test]
+fn jsonc_round_trip() {
+	let jsonc = r#"// Settings
+{
+  "name": "minjson", // the crate
+  /* limits */
+  "limits": {"depth": 64, /* inline */ "members": [1, 2, /* last */
+    3]},
+  "url": "http://example.com/*not a comment*/" /* after a value */,
+  "empty": [] // done
+}
+"#;
+	let formatted = pretty(jsonc);
+	assert_eq!(pretty(&formatted), formatted);
+	assert_eq!(minify(&formatted), minify(jsonc));
+	for comment in ["// Settings", "// the crate", "/* limits */", "/* inline */", "/* last */", "/* after a value */", "// done"] {
+		assert!(formatted.contains(comment), "{} is missing from {}", comment, formatted);
+	}
+	assert!(formatted.contains("/* last */\n"));
+}
+
+#[test]
+fn validate_allows_comments_only_in_jsonc_or_json5() {
+	let jsonc = minjson::ParseSetting { jsonc: true, ..Default::default() };
+	let json5 = minjson::ParseSetting { json5: true, ..Default::default() };
+	let commented = "// head\n[1, /* two */ 2] // tail\n";
+	assert!(minjson::validate_json(commented, jsonc).is_ok());
+	assert!(minjson::validate_json(commented, json5).is_ok());
+	assert!(minjson::validate_json(commented, Default::default()).is_err());
+	assert!(minjson::validate_json("[1, 2]\n", Default::default()).is_ok());
+	// JSONC allows comments and nothing else of JSON5
+	for text in ["[1] x", "[1] /* open", "[1, 2,]", "{a: 1}", "[1] [2]", "['a']"] {
+		assert!(minjson::validate_json(text, jsonc).is_err(), "{} was accepted", text);
+	}
+}
+
+#[test]
+fn empty_containers() {
+	assert_eq!(pretty("{}"), "{}");
+	assert_eq!(pretty("[ ]"), "[]");
+	assert_eq!(pretty("{\"a\": {}, \"b\": [[]]}"), "{\n  \"a\": {},\n  \"b\": [\n    []\n  ]\n}");
+}
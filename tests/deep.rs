@@ -0,0 +1,100 @@
+use minjson::{json, DiffSetting, JsonElement, JsonQuery, JsonStats, StatsSetting, TreeSetting};
+
+// Deep enough that anything recursing once per level overflows the stack of a test thread.
+// The slower operations get a shallower document, which still is.
+const DEPTH: usize = 500_000;
+const SHALLOWER: usize = 100_000;
+
+fn nested_arrays(depth: usize, leaf: &str) -> String {
+	format!("{}{}{}", "[".repeat(depth), leaf, "]".repeat(depth))
+}
+
+fn nested_objects(depth: usize, leaf: &str) -> String {
+	format!("{}{}{}", "{\"a\":".repeat(depth), leaf, "}".repeat(depth))
+}
+
+#[test]
+fn parse_diff_clone_drop() {
+	for (base, changed) in [(nested_arrays(DEPTH, "1"), nested_arrays(DEPTH, "2")), (nested_objects(DEPTH, "1"), nested_objects(DEPTH, "2"))] {
+		let g = minjson::build_json_graph(&base).unwrap();
+		let copy = g.clone();
+		assert!(copy == g);
+		assert_eq!(g.to_string(), base);
+
+		let diffs = minjson::structure_diff(&base, &changed, DiffSetting::default()).unwrap();
+		assert_eq!(diffs.len(), 1);
+		assert!(minjson::structure_diff(&base, &base, DiffSetting::default()).unwrap().is_empty());
+
+		drop(copy);
+		drop(g);
+	}
+}
+
+#[test]
+fn merge() {
+	let base = minjson::build_json_graph(&nested_objects(SHALLOWER, "1")).unwrap();
+	let ours = minjson::build_json_graph(&nested_objects(SHALLOWER, "2")).unwrap();
+	let theirs = minjson::build_json_graph(&nested_objects(SHALLOWER, "3")).unwrap();
+
+	let result = JsonElement::merge3(&base, &ours, &theirs);
+	assert_eq!(result.conflicts.len(), 1);
+	assert_eq!(result.conflicts[0].base_path.len(), 3 * SHALLOWER);
+	assert!(result.merged == ours);
+
+	let result = JsonElement::merge3(&base, &base, &theirs);
+	assert!(result.is_clean() && result.merged == theirs);
+}
+
+#[test]
+fn query() {
+	let arrays = minjson::build_json_graph(&nested_arrays(SHALLOWER, "1")).unwrap();
+	let objects = minjson::build_json_graph(&nested_objects(SHALLOWER, "1")).unwrap();
+	let eval = |filter: &str, input: &JsonElement| JsonQuery::new(filter).unwrap().run(input).unwrap();
+
+	assert_eq!(eval("flatten", &arrays).pop().unwrap().to_string(), "[1]");
+	assert_eq!(eval(". == . and . >= .", &objects).pop().unwrap(), json!(true));
+	assert_eq!(eval("contains(.)", &objects).pop().unwrap(), json!(true));
+	assert!(eval(". * .", &objects).pop().unwrap() == objects);
+	assert_eq!(eval("tojson | length", &arrays).pop().unwrap().to_string(), (2 * SHALLOWER + 1).to_string());
+}
+
+// The output of these grows with the square of the depth, so they get shallower documents.
+#[test]
+fn inspect() {
+	let depth = 5_000;
+	let g = minjson::build_json_graph(&nested_objects(depth, "[1]")).unwrap();
+
+	let tree = minjson::render_tree(&g, TreeSetting { ascii: true, ..Default::default() });
+	assert_eq!(tree.lines().count(), depth + 2);
+	let stats = JsonStats::new(&g, StatsSetting::default());
+	assert_eq!(stats.max_depth, depth + 2);
+	assert_eq!(stats.node_count(), depth + 2);
+}
+
+#[test]
+fn edit() {
+	let mut g = minjson::build_json_graph(&nested_arrays(DEPTH, "1")).unwrap();
+	let mut count = 0;
+	let mut longest = 0;
+	g.walk_mut(|path, elm| {
+		count += 1;
+		longest = longest.max(path.len());
+		if *elm == json!(1) {
+			*elm = json!(2);
+		}
+	});
+	assert_eq!((count, longest), (DEPTH + 1, 2 * DEPTH));
+	assert!(g == minjson::build_json_graph(&nested_arrays(DEPTH, "2")).unwrap());
+
+	let mut g = minjson::build_json_graph(&nested_objects(SHALLOWER, "1")).unwrap();
+	g.map(|path, elm| if path.len() == 2 * SHALLOWER { json!(2) } else { elm });
+	assert!(g == minjson::build_json_graph(&nested_objects(SHALLOWER, "2")).unwrap());
+
+	let mut g = minjson::build_json_graph(&nested_arrays(DEPTH, "1, 2")).unwrap();
+	g.retain(|_, elm| *elm != json!(2));
+	assert!(g == minjson::build_json_graph(&nested_arrays(DEPTH, "1")).unwrap());
+
+	let mut g = minjson::build_json_graph(&nested_objects(SHALLOWER, "1")).unwrap();
+	g.retain(|path, _| path.len() < 2 * SHALLOWER);
+	assert!(g == minjson::build_json_graph(&nested_objects(SHALLOWER - 1, "{}")).unwrap());
+}
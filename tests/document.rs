@@ -0,0 +1,51 @@
+use minjson::JsonDocument;
+
+fn remove(text: &str, pointer: &str) -> String {
+	let mut doc = JsonDocument::parse(text).unwrap();
+	doc.remove(pointer).unwrap();
+	doc.to_string()
+}
+
+#[test]
+fn rejects_trailing_text_in_scalars() {
+	for text in ["[nullx]", "[1-2]", "{\"a\": truefalse}", "{\"a\": 1 2}", "01"] {
+		assert!(JsonDocument::parse(text).is_err(), "{} was accepted", text);
+	}
+	assert!(JsonDocument::parse("[null, -1.5e3, true]").is_ok());
+}
+
+#[test]
+fn remove_keeps_layout() {
+	assert_eq!(remove("[1, 2, 3]", "/0"), "[2, 3]");
+	assert_eq!(remove("[1, 2, 3]", "/1"), "[1, 3]");
+	assert_eq!(remove("[1, 2, 3]", "/2"), "[1, 2]");
+	assert_eq!(remove("[ 1, 2 ]", "/0"), "[ 2 ]");
+	assert_eq!(remove("{\n  \"a\": 1,\n  \"b\": 2\n}", "/a"), "{\n  \"b\": 2\n}");
+	assert_eq!(remove("[ // list\n  1,\n  2\n]", "/0"), "[ // list\n  2\n]");
+}
+
+#[test]
+fn rejects_invalid_strings() {
+	for text in [r#"["\q"]"#, r#"{"a": "\u12"}"#, r#"["\u12g4"]"#, r#"{"\x": 1}"#, "[\"a\\\"]"] {
+		assert!(JsonDocument::parse(text).is_err(), "{} was accepted", text);
+	}
+	let text = r#"{"é\n": ["\"\\\/\b\f\r\t"]}"#;
+	assert_eq!(JsonDocument::parse(text).unwrap().to_string(), text);
+}
+
+#[test]
+fn insert_matches_indentation() {
+	let value = minjson::json!({"b": [1]});
+	for (text, expected) in [
+		("{\n\t\"a\": 1\n}", "{\n\t\"a\": 1,\n\t\"k\": {\n\t\t\"b\": [\n\t\t\t1\n\t\t]\n\t}\n}"),
+		("{\n    \"a\": 1\n}", "{\n    \"a\": 1,\n    \"k\": {\n        \"b\": [\n            1\n        ]\n    }\n}"),
+	] {
+		let mut doc = JsonDocument::parse(text).unwrap();
+		doc.insert("", "k", &value).unwrap();
+		assert_eq!(doc.to_string(), expected);
+	}
+
+	let mut doc = JsonDocument::parse("{\n\t\"a\": {\n\t\t\"x\": 1\n\t}\n}").unwrap();
+	doc.set("/a/y", &value).unwrap();
+	assert_eq!(doc.to_string(), "{\n\t\"a\": {\n\t\t\"x\": 1,\n\t\t\"y\": {\n\t\t\t\"b\": [\n\t\t\t\t1\n\t\t\t]\n\t\t}\n\t}\n}");
+}
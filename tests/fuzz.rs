@@ -0,0 +1,92 @@
+use minjson::{ParallelSetting, ParseLimits, ParseSetting};
+
+const SEEDS: &[&str] = &[
+	r#"{"name": "minjson", "tags": ["a", "b\n\u00e9\ud83d\ude00"], "n": -12.5e3, "ok": true, "none": null}"#,
+	r#"[1, 2.5, -0, 1e400, 12345678901234567890, "x", [], {}, [[{"a": [null]}]]]"#,
+	r#"{"a": {"a": {"a": 1, "a": 2}}, "": "", "\"": "\\"}"#,
+	"// comment\n{unquoted: 'single', trailing: [1, 2,], hex: 0x1F, inf: -Infinity, nan: NaN, dot: .5, plus: +1,}",
+	"[\"\u{00e9}\u{4e2d}\", {\"\u{1f600}\": 1}]",
+];
+
+// A small xorshift generator, so that failures reproduce.
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		(self.next() % n as u64) as usize
+	}
+}
+
+// Each parser must return rather than panic, and they must agree on whether the input is valid.
+fn parse_all(bytes: &[u8]) {
+	let json = String::from_utf8_lossy(bytes);
+	let limits = ParseLimits { max_depth: Some(8), max_string_length: Some(16), max_members: Some(6), max_input_size: None };
+	for &(json5, jsonc) in &[(false, false), (false, true), (true, false)] {
+		for &limits in &[ParseLimits::default(), limits] {
+			for &arbitrary_precision in &[false, true] {
+				let settings = ParseSetting { json5, jsonc, arbitrary_precision, limits };
+				let graph = minjson::try_build_json_graph(&json, settings).is_ok();
+				assert_eq!(minjson::try_build_json_value_ref(&json, settings).is_ok(), graph, "value_ref: {:?} {:?}", json, settings);
+				assert_eq!(minjson::try_build_json_arena(&json, settings).is_ok(), graph, "arena: {:?} {:?}", json, settings);
+				let parallel = ParallelSetting { jobs: 2, min_chunk_size: 0 };
+				assert_eq!(minjson::build_json_graph_parallel(&json, settings, parallel).is_ok(), graph, "parallel: {:?} {:?}", json, settings);
+				// The byte parsers agree with the others on UTF-8 input, and reject anything else
+				let utf8 = std::str::from_utf8(bytes).is_ok();
+				let valid = utf8 && minjson::validate_json(&json, settings).is_ok();
+				assert_eq!(minjson::validate_json_bytes(bytes, settings).is_ok(), valid, "validate_bytes: {:?} {:?}", bytes, settings);
+				let graph = utf8 && graph;
+				assert_eq!(minjson::build_json_graph_parallel_bytes(bytes, settings, parallel).is_ok(), graph, "parallel_bytes: {:?} {:?}", bytes, settings);
+			}
+		}
+	}
+}
+
+#[test]
+fn truncated() {
+	for seed in SEEDS {
+		for end in 0..seed.len() {
+			parse_all(&seed.as_bytes()[..end]);
+		}
+	}
+}
+
+#[test]
+fn mutated() {
+	const INTERESTING: &[u8] = b"{}[]:,\"\\'/*\n 0-+.eE\x00\xc3\xa9\xff";
+	let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+	for seed in SEEDS {
+		for _ in 0..400 {
+			let mut bytes = seed.as_bytes().to_vec();
+			for _ in 0..1 + rng.below(4) {
+				let i = rng.below(bytes.len() + 1);
+				let b = if rng.below(2) == 0 { INTERESTING[rng.below(INTERESTING.len())] } else { rng.next() as u8 };
+				match rng.below(3) {
+					0 if i < bytes.len() => bytes[i] = b,
+					1 if i < bytes.len() => {
+						bytes.remove(i);
+					},
+					_ => bytes.insert(i, b),
+				}
+			}
+			parse_all(&bytes);
+		}
+	}
+}
+
+#[test]
+fn random() {
+	const ALPHABET: &[u8] = b"{}[]:,\"\\ 0123456789-+.eEtrufalsn\n/*'xIN";
+	let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+	for _ in 0..2000 {
+		let len = rng.below(64);
+		let bytes: Vec<u8> = (0..len).map(|_| if rng.below(8) == 0 { rng.next() as u8 } else { ALPHABET[rng.below(ALPHABET.len())] }).collect();
+		parse_all(&bytes);
+	}
+}
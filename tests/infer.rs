@@ -0,0 +1,37 @@
+use minjson::{InferSetting, JsonSchema, ParseSetting};
+
+fn parse(json: &str) -> minjson::JsonElement {
+	minjson::build_json_graph_with_setting(json, ParseSetting { arbitrary_precision: true, ..Default::default() }).unwrap()
+}
+
+// Every sample must be valid against the schema inferred from the samples.
+fn check_round_trip(samples: &[&str]) -> JsonSchema {
+	let samples: Vec<_> = samples.iter().map(|s| parse(s)).collect();
+	let schema = minjson::infer_schema(&samples, InferSetting::default());
+	let validator = JsonSchema::new(schema.clone()).unwrap();
+	for sample in &samples {
+		let violations = validator.validate(sample);
+		assert!(violations.is_empty(), "{} against {}: {:?}", sample, schema, violations);
+	}
+	validator
+}
+
+#[test]
+fn samples_match_their_schema() {
+	check_round_trip(&[r#"{"id": 1, "name": "a", "tags": ["x"]}"#, r#"{"id": 2, "name": null, "score": 1.5}"#]);
+	check_round_trip(&["[1, 2.5, -3]", "[]", "[\"a\", true, null, {}]"]);
+}
+
+#[test]
+fn bounds_outside_i64_and_f64_stay_exact() {
+	let validator = check_round_trip(&["[1, 100000000000000000000000]", "[-100000000000000000000000.5]"]);
+	assert!(!validator.validate(&parse("[100000000000000000000001]")).is_empty());
+	assert!(!validator.validate(&parse("[-100000000000000000000000.6]")).is_empty());
+
+	// 2^53 + 1, which f64 rounds down to 2^53
+	let validator = check_round_trip(&["[0, 9007199254740993]"]);
+	let violations = validator.validate(&parse("[9007199254740994]"));
+	assert_eq!(violations.len(), 1);
+	assert_eq!(violations[0].message, "9007199254740994 is greater than 9007199254740993");
+	assert!(validator.validate(&parse("[9007199254740993]")).is_empty());
+}
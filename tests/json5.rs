@@ -0,0 +1,126 @@
+use minjson::{json, JsonElement, JsonNum, ParseSetting};
+
+fn json5() -> ParseSetting {
+	ParseSetting { json5: true, ..Default::default() }
+}
+
+fn parse(text: &str) -> JsonElement {
+	minjson::build_json_graph_with_setting(text, json5()).unwrap_or_else(|e| panic!("{}: {}", text, e))
+}
+
+fn rejects(text: &str) -> String {
+	minjson::validate_json(text, json5()).expect_err(text).to_string()
+}
+
+fn double(elm: JsonElement) -> f64 {
+	match elm {
+		JsonElement::JsonNumber(JsonNum::Double(d)) => d,
+		other => panic!("{:?} is not a double", other),
+	}
+}
+
+#[test]
+fn hex_numbers() {
+	assert_eq!(parse("0x1F"), json!(31));
+	assert_eq!(parse("0XaB"), json!(171));
+	assert_eq!(parse("-0x10"), json!(-16));
+	assert_eq!(parse("+0x0"), json!(0));
+	assert_eq!(parse("0x7FFFFFFFFFFFFFFF"), json!(i64::MAX));
+	// Too wide for i64, but not for f64
+	assert_eq!(parse("0x10000000000000000"), json!(18446744073709551616.0));
+	// "e" is a digit, not an exponent
+	assert_eq!(parse("0x1e"), json!(30));
+	for bad in &["0x", "0xG", "0x1.5", "0x1p", "0x_1"] {
+		rejects(bad);
+	}
+	assert!(minjson::validate_json("0x1F", ParseSetting::default()).is_err());
+}
+
+#[test]
+fn infinity_and_nan() {
+	assert_eq!(double(parse("Infinity")), f64::INFINITY);
+	assert_eq!(double(parse("+Infinity")), f64::INFINITY);
+	assert_eq!(double(parse("-Infinity")), f64::NEG_INFINITY);
+	assert!(double(parse("NaN")).is_nan());
+	assert!(double(parse("-NaN")).is_nan());
+	assert_eq!(double(parse("[Infinity]").pointer("/0").unwrap().clone()), f64::INFINITY);
+	for bad in &["infinity", "Inf", "nan", "NAN", "Infinityx"] {
+		rejects(bad);
+	}
+	assert!(minjson::validate_json("NaN", ParseSetting::default()).is_err());
+}
+
+#[test]
+fn other_numbers() {
+	assert_eq!(parse(".5"), json!(0.5));
+	assert_eq!(parse("5."), json!(5.0));
+	assert_eq!(parse("+1"), json!(1));
+	assert_eq!(parse("-.5e1"), json!(-5.0));
+	for bad in &[".", "+", "+-1", "01", "1..2", ".e1"] {
+		rejects(bad);
+	}
+}
+
+#[test]
+fn single_quoted_strings() {
+	assert_eq!(parse("'abc'"), json!("abc"));
+	assert_eq!(parse(r#"'say "hi"'"#), json!("say \"hi\""));
+	assert_eq!(parse(r"'it\'s'"), json!("it's"));
+	assert_eq!(parse(r#""it's""#), json!("it's"));
+	assert_eq!(parse("['a', \"b\"]"), json!(["a", "b"]));
+	rejects("'unterminated");
+	rejects("'mismatched\"");
+	assert!(minjson::validate_json("'a'", ParseSetting::default()).is_err());
+}
+
+#[test]
+fn identifier_keys() {
+	assert_eq!(parse("{a: 1, _b: 2, $c: 3, d1: 4, é: 5}"), json!({"a": 1, "_b": 2, "$c": 3, "d1": 4, "é": 5}));
+	assert_eq!(parse("{'single': 1, \"double\": 2, bare: 3}"), json!({"single": 1, "double": 2, "bare": 3}));
+	assert_eq!(parse("{null: 1, true: 2, Infinity: 3}"), json!({"null": 1, "true": 2, "Infinity": 3}));
+	for bad in &["{1a: 1}", "{a-b: 1}", "{a b: 1}", "{: 1}", "{a}"] {
+		rejects(bad);
+	}
+	// Identifiers are keys only
+	rejects("[abc]");
+}
+
+#[test]
+fn escapes() {
+	assert_eq!(parse(r"'\x41\x7a'"), json!("Az"));
+	assert_eq!(parse(r"'\v\0'"), json!("\u{0b}\0"));
+	assert_eq!(parse(r"'\a\c\''"), json!("ac'"));
+	assert_eq!(parse(r#""é😀\n""#), json!("é\u{1f600}\n"));
+	// A backslash before a line break continues the string on the next line
+	assert_eq!(parse("'line \\\nbreak'"), json!("line break"));
+	assert_eq!(parse("'line \\\r\nbreak'"), json!("line break"));
+	assert_eq!(parse("'line \\\u{2028}break'"), json!("line break"));
+	for bad in &[r"'\x4'", r"'\xZZ'", r"'\1'", r"'\01'", r"'\u12'"] {
+		rejects(bad);
+	}
+	assert!(minjson::validate_json(r#""\x41""#, ParseSetting::default()).is_err());
+}
+
+#[test]
+fn trailing_commas_and_comments() {
+	assert_eq!(parse("[1, 2,]"), json!([1, 2]));
+	assert_eq!(parse("{a: 1,}"), json!({"a": 1}));
+	assert_eq!(parse("// head\n{/* a */ a: /* b */ 1 // c\n, b: [2, /* d */],}"), json!({"a": 1, "b": [2]}));
+	for bad in &["[1,,]", "[,]", "{,}", "{a: 1,,}"] {
+		rejects(bad);
+	}
+}
+
+// Parsing without `whole` stops after the first value and ignores what follows, even text
+// that would fail to lex, like an unterminated comment.
+#[test]
+fn text_after_the_value() {
+	for text in &["[1] /*", "{a: 1} /* unterminated", "1 // fine\n/*", "'a' \\", "[1] ]"] {
+		assert!(minjson::build_json_graph_with_setting(text, json5()).is_ok(), "{}", text);
+		assert!(minjson::build_json_value_ref_with_setting(text, json5()).is_ok(), "{}", text);
+		assert!(minjson::build_json_arena_with_setting(text, json5()).is_ok(), "{}", text);
+		rejects(text);
+	}
+	assert_eq!(rejects("[1] /*"), "Reached EOF while parsing comment");
+	assert!(minjson::validate_json("[1] /* closed */ // line", json5()).is_ok());
+}
@@ -0,0 +1,70 @@
+use minjson::{LimitKind, ParallelSetting, ParseError, ParseLimits, ParseSetting};
+
+// Parses with every backend and checks that they agree, returning the common result.
+fn parse(json: &str, limits: ParseLimits) -> Result<(), ParseError> {
+	let settings = ParseSetting { limits, ..Default::default() };
+	let graph = minjson::try_build_json_graph(json, settings).map(drop);
+	assert_eq!(minjson::try_build_json_value_ref(json, settings).map(drop), graph, "value_ref: {}", json);
+	assert_eq!(minjson::try_build_json_arena(json, settings).map(drop), graph, "arena: {}", json);
+	assert_eq!(minjson::validate_json(json, settings), graph, "validate: {}", json);
+
+	// Errors in one element of a split array name the element
+	let parallel = ParallelSetting { jobs: 2, min_chunk_size: 0 };
+	match (minjson::build_json_graph_parallel(json, settings, parallel), &graph) {
+		(Ok(_), Ok(())) => {},
+		(Err(e), Err(expected)) => assert!(e.ends_with(&expected.to_string()), "parallel: {}: {}", json, e),
+		(result, _) => panic!("parallel: {}: {:?}", json, result),
+	}
+	graph
+}
+
+// `at_limit` must parse with the limit set to `limit`, and `over_limit` must fail with it.
+fn check(kind: LimitKind, limit: usize, set: fn(&mut ParseLimits, usize), at_limit: &str, over_limit: &str) {
+	let mut limits = ParseLimits::default();
+	set(&mut limits, limit);
+	assert_eq!(parse(at_limit, limits), Ok(()), "{}", at_limit);
+	assert_eq!(parse(over_limit, limits), Err(ParseError::LimitExceeded(kind, limit)), "{}", over_limit);
+}
+
+#[test]
+fn depth() {
+	let set = |l: &mut ParseLimits, n| l.max_depth = Some(n);
+	check(LimitKind::Depth, 0, set, "1", "[]");
+	check(LimitKind::Depth, 1, set, "[1, 2]", "[[1], 2]");
+	check(LimitKind::Depth, 3, set, "[{\"a\": [1]}, [[2]]]", "[{\"a\": [[1]]}]");
+	check(LimitKind::Depth, 3, set, "[[[]], [[]], [[]]]", "[[[]], [[[]]]]");
+}
+
+#[test]
+fn string_length() {
+	let set = |l: &mut ParseLimits, n| l.max_string_length = Some(n);
+	check(LimitKind::StringLength, 3, set, "[\"abc\"]", "[\"abcd\"]");
+	check(LimitKind::StringLength, 3, set, "{\"abc\": 1}", "{\"abcd\": 1}");
+	// Escapes count as the bytes they decode to
+	check(LimitKind::StringLength, 2, set, "[\"\\n\\u0041\"]", "[\"\\n\\n\\n\"]");
+	check(LimitKind::StringLength, 3, set, "[\"\\u00e9\\n\"]", "[\"\\u00e9\\u00e9\"]");
+	check(LimitKind::StringLength, 0, set, "[\"\"]", "[\"a\"]");
+}
+
+#[test]
+fn members() {
+	let set = |l: &mut ParseLimits, n| l.max_members = Some(n);
+	check(LimitKind::Members, 3, set, "[1, 2, 3]", "[1, 2, 3, 4]");
+	check(LimitKind::Members, 2, set, "{\"a\": 1, \"b\": [1, 2]}", "{\"a\": 1, \"b\": 2, \"c\": 3}");
+	check(LimitKind::Members, 2, set, "[[1, 2], [3, 4]]", "[[1, 2], [3, 4, 5]]");
+	check(LimitKind::Members, 0, set, "[]", "[1]");
+}
+
+#[test]
+fn input_size() {
+	let set = |l: &mut ParseLimits, n| l.max_input_size = Some(n);
+	check(LimitKind::InputSize, 7, set, "[1,2,3]", "[1,2,34]");
+	check(LimitKind::InputSize, 9, set, "[1, 2, 3]", "[1, 2, 3] ");
+}
+
+#[test]
+fn duplicate_keys_count_once() {
+	let set = |l: &mut ParseLimits, n| l.max_members = Some(n);
+	check(LimitKind::Members, 2, set, "{\"a\": 1, \"a\": 2, \"a\": 3}", "{\"a\": 1, \"b\": 2, \"a\": 3, \"c\": 4}");
+	check(LimitKind::Members, 1, set, "[{\"a\": {\"a\": 1, \"a\": 2}, \"a\": 3}]", "[{\"a\": 1}, {\"a\": 1, \"b\": 2}]");
+}
@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, HashMap};
+
+use minjson::{json, JsonElement, JsonNum};
+use minjson::JsonElement::*;
+
+fn parse(json: &str) -> JsonElement {
+	minjson::build_json_graph(json).unwrap()
+}
+
+#[test]
+fn literals() {
+	assert_eq!(json!(null), JsonNull);
+	assert_eq!(json!(true), JsonBool(true));
+	assert_eq!(json!(false), JsonBool(false));
+	assert_eq!(json!("a\"b"), JsonString("a\"b".to_string()));
+	assert_eq!(json!(0), JsonNumber(JsonNum::Integer(0)));
+	assert_eq!(json!(1.5), JsonNumber(JsonNum::Double(1.5)));
+	assert_eq!(json!([]), JsonArray(Vec::new()));
+	assert_eq!(json!({}), JsonObject(HashMap::new()));
+}
+
+#[test]
+fn negative_numbers() {
+	assert_eq!(json!(-1), JsonNumber(JsonNum::Integer(-1)));
+	assert_eq!(json!(-2.5e3), JsonNumber(JsonNum::Double(-2500.0)));
+	assert_eq!(json!([-1, -2.5, 3]), parse("[-1, -2.5, 3]"));
+	assert_eq!(json!({"a": -1, "b": [-0.5]}), parse(r#"{"a": -1, "b": [-0.5]}"#));
+	assert_eq!(json!(i64::MIN), JsonNumber(JsonNum::Integer(i64::MIN)));
+	assert_eq!(json!(i128::MIN), JsonNumber(JsonNum::Decimal(i128::MIN.to_string())));
+}
+
+#[test]
+fn nested() {
+	let elm = json!({
+		"name": "minjson",
+		"tags": ["a", ["b", {"c": null}], []],
+		"meta": {"stable": false, "counts": {"stars": 10, "forks": [1, 2]}, "empty": {}},
+	});
+	assert_eq!(elm, parse(r#"{
+		"name": "minjson",
+		"tags": ["a", ["b", {"c": null}], []],
+		"meta": {"stable": false, "counts": {"stars": 10, "forks": [1, 2]}, "empty": {}}
+	}"#));
+	assert_eq!(json!([[[[1]]]]), parse("[[[[1]]]]"));
+}
+
+#[test]
+fn expressions() {
+	let id = 7;
+	let name = String::from("x");
+	let tags = vec!["a", "b"];
+	let elm = json!({
+		"id": id,
+		"double": id * 2,
+		"name": &name,
+		"tags": tags.clone(),
+		"first": tags.first().copied(),
+		"missing": None::<i32>,
+		"nested": json!([id, -id]),
+	});
+	assert_eq!(elm, parse(r#"{"id": 7, "double": 14, "name": "x", "tags": ["a", "b"], "first": "a", "missing": null, "nested": [7, -7]}"#));
+	assert_eq!(json!([id + 1, name.len(), 'c']), parse(r#"[8, 1, "c"]"#));
+}
+
+#[test]
+fn computed_keys() {
+	let id = 7;
+	let key = String::from("k");
+	let elm = json!({
+		(format!("item{}", id)): 1,
+		(key): [true],
+		(id): "number keys are converted with ToString",
+		"plain": 2,
+	});
+	assert_eq!(elm, parse(r#"{"item7": 1, "k": [true], "7": "number keys are converted with ToString", "plain": 2}"#));
+}
+
+#[test]
+fn trailing_commas() {
+	assert_eq!(json!([1, 2,]), parse("[1, 2]"));
+	assert_eq!(json!([-1,]), parse("[-1]"));
+	assert_eq!(json!({"a": 1,}), parse(r#"{"a": 1}"#));
+	assert_eq!(json!({"a": -1, "b": {"c": [null,],},}), parse(r#"{"a": -1, "b": {"c": [null]}}"#));
+}
+
+#[test]
+fn from_impls() {
+	assert_eq!(JsonElement::from(u8::MAX), JsonNumber(JsonNum::Integer(255)));
+	assert_eq!(JsonElement::from(u64::MAX), JsonNumber(JsonNum::Decimal(u64::MAX.to_string())));
+	assert_eq!(JsonElement::from(-3isize), JsonNumber(JsonNum::Integer(-3)));
+	assert_eq!(JsonElement::from(0.5f32), JsonNumber(JsonNum::Double(0.5)));
+	assert_eq!(JsonElement::from(JsonNum::Integer(4)), JsonNumber(JsonNum::Integer(4)));
+	assert_eq!(JsonElement::from('é'), json!("é"));
+	assert_eq!(JsonElement::from(()), JsonNull);
+	assert_eq!(JsonElement::from(Some(1)), json!(1));
+	assert_eq!(JsonElement::from(None::<&str>), JsonNull);
+	assert_eq!(JsonElement::from(vec![Some(1), None]), json!([1, null]));
+	assert_eq!(JsonElement::from(&[1.5, -2.0][..]), json!([1.5, -2.0]));
+
+	let mut map = HashMap::new();
+	map.insert("a", vec![1]);
+	assert_eq!(JsonElement::from(map), json!({"a": [1]}));
+	let mut map = BTreeMap::new();
+	map.insert(String::from("b"), json!({"c": null}));
+	assert_eq!(JsonElement::from(map), json!({"b": {"c": null}}));
+
+	assert_eq!((1..4).collect::<JsonElement>(), json!([1, 2, 3]));
+	assert_eq!(vec![("x", true), ("y", false)].into_iter().collect::<JsonElement>(), json!({"x": true, "y": false}));
+}
@@ -0,0 +1,94 @@
+use minjson::{json, DiffSetting, JsonElement, MergeConflict};
+
+fn merge(base: JsonElement, ours: JsonElement, theirs: JsonElement) -> minjson::MergeResult {
+	JsonElement::merge3(&base, &ours, &theirs)
+}
+
+#[test]
+fn clean_merges() {
+	let base = json!({"name": "app", "version": 1, "deps": {"a": "1.0", "b": "2.0"}, "tags": ["x", "y"]});
+	let ours = json!({"name": "app", "version": 2, "deps": {"a": "1.1", "b": "2.0"}, "tags": ["x", "y"]});
+	let theirs = json!({"name": "app", "version": 1, "deps": {"a": "1.0", "b": "2.0", "c": "3.0"}, "tags": ["x", "z"]});
+	let result = merge(base, ours, theirs);
+	assert!(result.is_clean());
+	assert_eq!(result.merged, json!({"name": "app", "version": 2, "deps": {"a": "1.1", "b": "2.0", "c": "3.0"}, "tags": ["x", "z"]}));
+
+	// The same change on both sides, and members deleted on one side only
+	let result = merge(json!({"a": 1, "b": 2, "c": 3}), json!({"a": 5, "c": 3}), json!({"a": 5, "b": 2}));
+	assert!(result.is_clean());
+	assert_eq!(result.merged, json!({"a": 5}));
+
+	// Arrays of different lengths are replaced whole by the side that changed them
+	let result = merge(json!([1, 2]), json!([1, 2, 3]), json!([1, 2]));
+	assert!(result.is_clean());
+	assert_eq!(result.merged, json!([1, 2, 3]));
+
+	// Numbers within the threshold are the same
+	let settings = DiffSetting { float_diff_threashold: 0.01 };
+	let result = JsonElement::merge3_with_setting(&json!({"x": 1.0}), &json!({"x": 1.001}), &json!({"x": 2.0}), settings);
+	assert!(result.is_clean());
+	assert_eq!(result.merged, json!({"x": 2.0}));
+}
+
+#[test]
+fn conflicts() {
+	let base = json!({"a": {"b": 1, "c": [1, 2]}, "d": "same"});
+	let ours = json!({"a": {"b": 2, "c": [1, 3]}, "d": "same"});
+	let theirs = json!({"a": {"b": 3, "c": [1, 4]}, "d": "same"});
+	let result = merge(base, ours, theirs);
+	assert!(!result.is_clean());
+	// Conflicting positions keep our value
+	assert_eq!(result.merged, json!({"a": {"b": 2, "c": [1, 3]}, "d": "same"}));
+	assert_eq!(result.conflicts, vec![
+		MergeConflict { base_path: "::a::b".to_string(), base: Some(json!(1)), ours: Some(json!(2)), theirs: Some(json!(3)) },
+		MergeConflict { base_path: "::a::c::1".to_string(), base: Some(json!(2)), ours: Some(json!(3)), theirs: Some(json!(4)) },
+	]);
+
+	// Members added on both sides with different values
+	let result = merge(json!({}), json!({"k": true}), json!({"k": false}));
+	assert_eq!(result.conflicts, vec![
+		MergeConflict { base_path: "::k".to_string(), base: None, ours: Some(json!(true)), theirs: Some(json!(false)) },
+	]);
+
+	// Different types at the root
+	let result = merge(json!(1), json!([1]), json!({"a": 1}));
+	assert_eq!(result.merged, json!([1]));
+	assert_eq!(result.conflicts.len(), 1);
+	assert_eq!(result.conflicts[0].base_path, "");
+}
+
+#[test]
+fn delete_against_modify() {
+	let base = json!({"keep": 1, "gone": {"x": 1}});
+	let result = merge(base.clone(), json!({"keep": 1}), json!({"keep": 1, "gone": {"x": 2}}));
+	assert_eq!(result.merged, json!({"keep": 1}));
+	assert_eq!(result.conflicts, vec![
+		MergeConflict { base_path: "::gone".to_string(), base: Some(json!({"x": 1})), ours: None, theirs: Some(json!({"x": 2})) },
+	]);
+
+	// Modified by us, deleted by them: our value stays
+	let result = merge(base, json!({"keep": 1, "gone": {"x": 3}}), json!({"keep": 1}));
+	assert_eq!(result.merged, json!({"keep": 1, "gone": {"x": 3}}));
+	assert_eq!(result.conflicts, vec![
+		MergeConflict { base_path: "::gone".to_string(), base: Some(json!({"x": 1})), ours: Some(json!({"x": 3})), theirs: None },
+	]);
+}
+
+#[test]
+fn report() {
+	let result = merge(json!({"a": 1, "b": 1}), json!({"a": 2, "b": 1, "c": 0}), json!({"a": 3}));
+	assert_eq!(result.report(), json!({
+		"merged": {"a": 2, "c": 0},
+		"conflicts": [{"path": "::a", "base": 1, "ours": 2, "theirs": 3}],
+	}));
+
+	// Sides without the value are left out of the conflict
+	let result = merge(json!({"a": 1}), json!({}), json!({"a": 2}));
+	assert_eq!(result.report(), json!({
+		"merged": {},
+		"conflicts": [{"path": "::a", "base": 1, "theirs": 2}],
+	}));
+
+	let result = merge(json!(null), json!(null), json!(null));
+	assert_eq!(result.report(), json!({"merged": null, "conflicts": []}));
+}
@@ -0,0 +1,133 @@
+use minjson::{json, JsonElement, ParallelSetting, ParseSetting};
+
+const THREADS: &[usize] = &[1, 2, 3, 4, 8];
+
+// Strings full of the bytes the splitter looks for, so that a split that doesn't track
+// strings and escapes cuts an element in the wrong place.
+const TRICKY: &[&str] = &[
+	r#""a,b""#,
+	r#""]""#,
+	r#""[[[""#,
+	r#""}{""#,
+	r#""\"""#,
+	r#""\\""#,
+	r#""\\\"],[""#,
+	r#""x\\,""#,
+	r#""\u005d\u002c""#,
+	r#""\n\t\/\b""#,
+	r#"{"k,]": "v\"]"}"#,
+	r#"["\\", ["]"], {"\"": ","}]"#,
+	r#""é,ü]""#,
+];
+
+// A small xorshift generator, so that failures reproduce.
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, n: usize) -> usize {
+		(self.next() % n as u64) as usize
+	}
+}
+
+fn element(rng: &mut Rng) -> String {
+	match rng.below(4) {
+		0 => TRICKY[rng.below(TRICKY.len())].to_string(),
+		1 => format!("[{}, {}]", TRICKY[rng.below(TRICKY.len())], rng.below(100)),
+		2 => format!("{{\"s\": {}}}", TRICKY[rng.below(TRICKY.len())]),
+		_ => rng.below(1000).to_string(),
+	}
+}
+
+fn serial(json: &str) -> Result<JsonElement, String> {
+	minjson::build_json_graph_with_setting(json, ParseSetting::default())
+}
+
+fn check_array(json: &str) {
+	let expected = serial(json);
+	for &jobs in THREADS {
+		let parallel = ParallelSetting { jobs, min_chunk_size: 0 };
+		let result = minjson::build_json_graph_parallel(json, ParseSetting::default(), parallel);
+		match &expected {
+			Ok(expected) => assert_eq!(result.as_ref(), Ok(expected), "{} threads: {}", jobs, json),
+			Err(_) => assert!(result.is_err(), "{} threads accepted {}", jobs, json),
+		}
+		let bytes = minjson::build_json_graph_parallel_bytes(json.as_bytes(), ParseSetting::default(), parallel);
+		assert_eq!(bytes, result, "{} threads, bytes: {}", jobs, json);
+	}
+}
+
+#[test]
+fn arrays_with_tricky_strings() {
+	for tricky in TRICKY {
+		check_array(&format!("[{}]", tricky));
+		check_array(&format!("[{0}, {0}, {0}]", tricky));
+		check_array(&format!(" [ 1 ,{}, 2 ] ", tricky));
+	}
+
+	let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+	for _ in 0..300 {
+		let elems: Vec<String> = (0..rng.below(40)).map(|_| element(&mut rng)).collect();
+		let json = format!("[{}]", elems.join(", "));
+		assert!(serial(&json).is_ok(), "{}", json);
+		check_array(&json);
+	}
+}
+
+#[test]
+fn invalid_arrays() {
+	for json in &[
+		"[1, 2", "[1, 2,]", "[,]", "[1 2]", r#"["a", "b]"#, r#"["\", 1]"#, r#"["a\"]"#,
+		"[[1, 2], [3]", "[1, 2]]", "[{]", "[}]", r#"["\q"]"#, "[1, \"x\" 2]",
+	] {
+		check_array(json);
+	}
+}
+
+#[test]
+fn ndjson_matches_serial() {
+	let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+	for _ in 0..200 {
+		let docs: Vec<String> = (0..rng.below(30)).map(|_| element(&mut rng)).collect();
+		let mut text = String::new();
+		for doc in &docs {
+			text.push_str(doc);
+			// Blank lines, CRLF and a missing final newline must not matter
+			text.push_str(["\n", "\r\n", "\n\n", " \n"][rng.below(4)]);
+		}
+		if rng.below(2) == 0 {
+			text.pop();
+		}
+
+		let expected: Vec<JsonElement> = docs.iter().map(|doc| serial(doc).unwrap()).collect();
+		for &jobs in THREADS {
+			let parallel = ParallelSetting { jobs, min_chunk_size: 0 };
+			assert_eq!(minjson::parse_ndjson(&text, ParseSetting::default(), parallel).as_ref(), Ok(&expected), "{} threads: {:?}", jobs, text);
+			assert_eq!(minjson::parse_ndjson_bytes(text.as_bytes(), ParseSetting::default(), parallel).as_ref(), Ok(&expected), "{} threads: {:?}", jobs, text);
+		}
+	}
+}
+
+#[test]
+fn ndjson_errors_name_the_line() {
+	let text = "1\n\"a\\\"\n\n[\"]\"]\n{\"a\": \"\\q\"}\n";
+	for &jobs in THREADS {
+		let parallel = ParallelSetting { jobs, min_chunk_size: 0 };
+		let e = minjson::parse_ndjson(text, ParseSetting::default(), parallel).unwrap_err();
+		assert!(e.starts_with("Line 2: "), "{} threads: {}", jobs, e);
+	}
+
+	// A document must not continue onto the next line
+	let e = minjson::parse_ndjson("[1,\n2]\n", ParseSetting::default(), ParallelSetting::default()).unwrap_err();
+	assert!(e.starts_with("Line 1: "), "{}", e);
+	assert_eq!(
+		minjson::parse_ndjson("[\"]\\n[\"]\n", ParseSetting::default(), ParallelSetting { jobs: 4, min_chunk_size: 0 }),
+		Ok(vec![json!(["]\n["])]),
+	);
+}
@@ -0,0 +1,143 @@
+use std::process::Command;
+
+use minjson::{json, JsonElement, JsonQuery};
+
+fn run(query: &str, input: &JsonElement) -> Vec<JsonElement> {
+	JsonQuery::new(query).unwrap().run(input).unwrap_or_else(|e| panic!("{}: {}", query, e))
+}
+
+fn run_err(query: &str, input: &JsonElement) -> String {
+	JsonQuery::new(query).unwrap().run(input).unwrap_err()
+}
+
+fn items() -> JsonElement {
+	json!({
+		"items": [
+			{"name": "pen", "price": 2, "tags": ["office"]},
+			{"name": "desk", "price": 120, "tags": ["office", "furniture"]},
+			{"name": "lamp", "price": 35, "tags": []},
+		],
+		"owner": null,
+	})
+}
+
+#[test]
+fn paths_and_iteration() {
+	let input = items();
+	assert_eq!(run(".items[0].name", &input), vec![json!("pen")]);
+	assert_eq!(run(".items[-1].price", &input), vec![json!(35)]);
+	assert_eq!(run(".items[].name", &input), vec![json!("pen"), json!("desk"), json!("lamp")]);
+	assert_eq!(run(".[\"owner\"]", &input), vec![json!(null)]);
+	assert_eq!(run(".missing.deeper", &input), vec![json!(null)]);
+	assert_eq!(run(".items | length", &input), vec![json!(3)]);
+	assert_eq!(run(".owner, .items[1].price", &input), vec![json!(null), json!(120)]);
+}
+
+#[test]
+fn select_and_map() {
+	let input = items();
+	assert_eq!(run(".items[] | select(.price > 10) | .name", &input), vec![json!("desk"), json!("lamp")]);
+	assert_eq!(run(".items | map(.price * 2)", &input), vec![json!([4, 240, 70])]);
+	assert_eq!(run(".items | map(select(.tags | length > 0) | .name)", &input), vec![json!(["pen", "desk"])]);
+	assert_eq!(run("[.items[] | .tags[]] | unique", &input), vec![json!(["furniture", "office"])]);
+	// select with a stream of conditions emits the input once per true one
+	assert_eq!(run("select(true, false, true)", &json!(1)), vec![json!(1), json!(1)]);
+}
+
+#[test]
+fn reduce_and_variables() {
+	let input = items();
+	assert_eq!(run("reduce .items[] as $item (0; . + $item.price)", &input), vec![json!(157)]);
+	assert_eq!(run("reduce range(5) as $i ([]; . + [$i * $i])", &json!(null)), vec![json!([0, 1, 4, 9, 16])]);
+	assert_eq!(run(".items[0] as $first | .items[] | select(.price > $first.price) | .name", &input), vec![json!("desk"), json!("lamp")]);
+	// Each binding of a stream runs the body once
+	assert_eq!(run("(1, 2) as $x | $x * 10", &json!(null)), vec![json!(10), json!(20)]);
+	assert!(JsonQuery::new("$undefined").and_then(|q| q.run(&json!(null))).is_err());
+}
+
+#[test]
+fn try_catch() {
+	assert_eq!(run("try error(\"boom\") catch .", &json!(null)), vec![json!("boom")]);
+	// Errors are strings; other values are passed to the handler as their JSON text
+	assert_eq!(run("try error({\"code\": 7}) catch (fromjson | .code)", &json!(null)), vec![json!(7)]);
+	assert_eq!(run("[.[] | try tonumber catch \"bad\"]", &json!(["1", "x", "2.5"])), vec![json!([1, "bad", 2.5])]);
+	// Without catch, errors are dropped, as with `?`
+	assert_eq!(run("try error(\"boom\")", &json!(null)), Vec::<JsonElement>::new());
+	assert_eq!(run("[.[] | .a?]", &json!([{"a": 1}, "s", {"a": 2}])), vec![json!([1, 2])]);
+}
+
+#[test]
+fn slices() {
+	let input = json!([0, 1, 2, 3, 4]);
+	assert_eq!(run(".[1:3]", &input), vec![json!([1, 2])]);
+	assert_eq!(run(".[:2]", &input), vec![json!([0, 1])]);
+	assert_eq!(run(".[-2:]", &input), vec![json!([3, 4])]);
+	assert_eq!(run(".[3:1]", &input), vec![json!([])]);
+	assert_eq!(run(".[10:]", &input), vec![json!([])]);
+	assert_eq!(run(".[1:3]", &json!("hello")), vec![json!("el")]);
+	assert_eq!(run(".[1:3]", &json!(null)), vec![json!(null)]);
+}
+
+#[test]
+fn string_interpolation() {
+	let input = items();
+	assert_eq!(run(".items[0] | \"\\(.name) costs \\(.price)\"", &input), vec![json!("pen costs 2")]);
+	assert_eq!(run("\"tags: \\(.items[1].tags)\"", &input), vec![json!("tags: [\"office\",\"furniture\"]")]);
+	assert_eq!(run("\"\\(1 + 2)\\(null)\"", &json!(null)), vec![json!("3null")]);
+	// Each combination of the interpolated streams makes a string
+	assert_eq!(run("\"\\(1, 2)\"", &json!(null)), vec![json!("1"), json!("2")]);
+}
+
+#[test]
+fn errors() {
+	assert_eq!(run_err(".[]", &json!(5)), "Cannot iterate over number (5)");
+	assert_eq!(run_err(".a", &json!([1])), "Cannot index array with \"a\"");
+	assert_eq!(run_err("1 + \"a\"", &json!(null)), "number (1) and string (\"a\") cannot be added");
+	assert_eq!(run_err("error(\"custom\")", &json!(null)), "custom");
+	assert_eq!(run_err(".[] | tonumber", &json!(["1", "x"])), "Cannot parse 'x' as a number");
+
+	assert!(JsonQuery::new(".a |").is_err());
+	assert!(JsonQuery::new("\"unterminated").is_err());
+	assert_eq!(JsonQuery::new("nosuch(1)").unwrap_err(), "nosuch/1 is not defined");
+	assert!(JsonQuery::new("reduce .[] as $x (0)").is_err());
+	assert!(JsonQuery::new("try").is_err());
+}
+
+fn transform(args: &[&str], input: &str) -> (i32, String, String) {
+	use std::io::Write;
+
+	let mut child = Command::new(env!("CARGO_BIN_EXE_minjson"))
+		.arg("transform")
+		.args(args)
+		.stdin(std::process::Stdio::piped())
+		.stdout(std::process::Stdio::piped())
+		.stderr(std::process::Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+	let output = child.wait_with_output().unwrap();
+	let text = |b: Vec<u8>| String::from_utf8(b).unwrap();
+	(output.status.code().unwrap(), text(output.stdout), text(output.stderr))
+}
+
+// Bad expressions and failing queries are invalid input: the CLI exits with 2 and writes
+// nothing but the error.
+#[test]
+fn cli_exits_with_2_on_errors() {
+	let (code, stdout, _) = transform(&[".a"], "{\"a\": [1]}");
+	assert_eq!((code, stdout.as_str()), (0, "[\n  1\n]\n"));
+
+	let (code, stdout, stderr) = transform(&[".a |"], "{}");
+	assert_eq!(code, 2);
+	assert!(stdout.is_empty());
+	assert!(stderr.starts_with("Invalid expression: "), "{}", stderr);
+
+	let (code, stdout, stderr) = transform(&[".[]"], "5");
+	assert_eq!((code, stdout.as_str(), stderr.as_str()), (2, "", "Cannot iterate over number (5)\n"));
+
+	let (code, stdout, _) = transform(&[".[] | error(\"x\")"], "[1, 2]");
+	assert_eq!((code, stdout.as_str()), (2, ""));
+
+	let (code, _, _) = transform(&["."], "{");
+	assert_eq!(code, 2);
+}
@@ -0,0 +1,170 @@
+use minjson::{json, JsonElement, JsonSchema};
+
+// The violations as (instance path, schema path, message), in the order they are reported.
+fn check(schema: JsonElement, instance: JsonElement) -> Vec<(String, String, String)> {
+	JsonSchema::new(schema).unwrap().validate(&instance).into_iter()
+		.map(|v| (v.instance_path, v.schema_path, v.message))
+		.collect()
+}
+
+fn violation(instance_path: &str, schema_path: &str, message: &str) -> (String, String, String) {
+	(instance_path.to_string(), schema_path.to_string(), message.to_string())
+}
+
+#[test]
+fn refs() {
+	let schema = json!({
+		"definitions": {
+			"node": {
+				"type": "object",
+				"properties": {"value": {"type": "integer"}, "next": {"$ref": "#/definitions/node"}},
+			},
+		},
+		"$ref": "#/definitions/node",
+	});
+	// A recursive schema is fine as long as each step moves into the instance
+	assert_eq!(check(schema.clone(), json!({"value": 1, "next": {"value": 2, "next": {"value": 3}}})), vec![]);
+	assert_eq!(check(schema, json!({"value": 1, "next": {"next": {"value": "x"}}})), vec![
+		violation("/next/next/value", "/$ref/properties/next/$ref/properties/next/$ref/properties/value/type", "expected integer, got string"),
+	]);
+
+	assert_eq!(check(json!({"$ref": "#/definitions/missing"}), json!(1)), vec![
+		violation("", "/$ref", "cannot resolve reference #/definitions/missing"),
+	]);
+}
+
+#[test]
+fn ref_cycles() {
+	assert_eq!(check(json!({"$ref": "#"}), json!(1)), vec![
+		violation("", "/$ref/$ref", "circular reference #"),
+	]);
+
+	let schema = json!({
+		"definitions": {"a": {"$ref": "#/definitions/b"}, "b": {"$ref": "#/definitions/a"}},
+		"$ref": "#/definitions/a",
+	});
+	assert_eq!(check(schema, json!(null)), vec![
+		violation("", "/$ref/$ref/$ref", "circular reference #/definitions/a"),
+	]);
+
+	// Cycles through oneOf end too: the repeated reference fails, and the levels above it are
+	// judged as usual
+	let schema = json!({"oneOf": [{"$ref": "#"}, {"type": "string"}]});
+	assert_eq!(check(schema, json!(1)), vec![
+		violation("", "/oneOf", "value matches 0 schemas in oneOf, expected exactly 1"),
+	]);
+}
+
+#[test]
+fn one_of() {
+	let schema = json!({"oneOf": [{"type": "integer"}, {"minimum": 10}, {"type": "string"}]});
+	assert_eq!(check(schema.clone(), json!(5)), vec![]);
+	assert_eq!(check(schema.clone(), json!(10.5)), vec![]);
+	assert_eq!(check(schema.clone(), json!(20)), vec![
+		violation("", "/oneOf", "value matches 2 schemas in oneOf, expected exactly 1"),
+	]);
+	assert_eq!(check(schema, json!(5.5)), vec![
+		violation("", "/oneOf", "value matches 0 schemas in oneOf, expected exactly 1"),
+	]);
+
+	let schema = json!({"properties": {"id": {"oneOf": [{"type": "integer"}, {"type": "number"}]}}});
+	assert_eq!(check(schema.clone(), json!({"id": 1.5})), vec![]);
+	assert_eq!(check(schema, json!({"id": 1})), vec![
+		violation("/id", "/properties/id/oneOf", "value matches 2 schemas in oneOf, expected exactly 1"),
+	]);
+}
+
+#[test]
+fn additional_properties() {
+	let schema = json!({"properties": {"a": {"type": "integer"}}, "additionalProperties": false});
+	assert_eq!(check(schema.clone(), json!({"a": 1})), vec![]);
+	assert_eq!(check(schema.clone(), json!({})), vec![]);
+	assert_eq!(check(schema, json!({"a": 1, "b": 2, "c/d": 3})), vec![
+		violation("/b", "/additionalProperties", "schema is false; no value is allowed"),
+		violation("/c~1d", "/additionalProperties", "schema is false; no value is allowed"),
+	]);
+
+	// A schema for additional properties applies to each of them
+	let schema = json!({"properties": {"a": true}, "additionalProperties": {"type": "string"}});
+	assert_eq!(check(schema.clone(), json!({"a": 1, "b": "x"})), vec![]);
+	assert_eq!(check(schema, json!({"a": 1, "b": 2})), vec![
+		violation("/b", "/additionalProperties/type", "expected string, got integer"),
+	]);
+}
+
+#[test]
+fn pattern() {
+	let schema = json!({"type": "string", "pattern": "^[a-z]+-[0-9]+$"});
+	assert_eq!(check(schema.clone(), json!("abc-123")), vec![]);
+	assert_eq!(check(schema.clone(), json!("abc-")), vec![
+		violation("", "/pattern", "string does not match pattern ^[a-z]+-[0-9]+$"),
+	]);
+	// Patterns are not anchored unless they say so
+	assert_eq!(check(json!({"pattern": "b+"}), json!("abbc")), vec![]);
+	// Only strings are checked
+	assert_eq!(check(schema.clone(), json!(5)), vec![violation("", "/type", "expected string, got integer")]);
+	assert_eq!(check(json!({"pattern": "^x"}), json!(5)), vec![]);
+
+	let schema = json!({"items": {"pattern": "^x"}});
+	assert_eq!(check(schema, json!(["xa", "ya", "xb"])), vec![
+		violation("/1", "/items/pattern", "string does not match pattern ^x"),
+	]);
+
+	let e = JsonSchema::new(json!({"properties": {"a": {"pattern": "("}}})).unwrap_err();
+	assert!(e.starts_with("invalid pattern at /properties/a/pattern: "), "{}", e);
+}
+
+#[test]
+fn keywords() {
+	let cases = vec![
+		(json!({"type": ["string", "null"]}), json!(null), json!(1), violation("", "/type", "expected string or null, got integer")),
+		(json!({"enum": [1, "a"]}), json!("a"), json!("b"), violation("", "/enum", "\"b\" is not one of the enumerated values")),
+		(json!({"const": {"a": 1}}), json!({"a": 1.0}), json!({"a": 2}), violation("", "/const", "expected {\"a\":1}, got {\"a\":2}")),
+		(json!({"minimum": 1}), json!(1), json!(0.5), violation("", "/minimum", "0.5 is less than 1")),
+		(json!({"maximum": 1}), json!(1), json!(2), violation("", "/maximum", "2 is greater than 1")),
+		(json!({"exclusiveMinimum": 1}), json!(1.5), json!(1), violation("", "/exclusiveMinimum", "1 is less than or equal to 1")),
+		(json!({"exclusiveMaximum": 1}), json!(0), json!(1), violation("", "/exclusiveMaximum", "1 is greater than or equal to 1")),
+		(json!({"minLength": 2}), json!("\u{e9}\u{e9}"), json!("a"), violation("", "/minLength", "string is shorter than 2 characters")),
+		(json!({"maxLength": 1}), json!("\u{e9}"), json!("ab"), violation("", "/maxLength", "string is longer than 1 characters")),
+		(json!({"minItems": 1}), json!([1]), json!([]), violation("", "/minItems", "array has fewer than 1 items")),
+		(json!({"maxItems": 1}), json!([]), json!([1, 2]), violation("", "/maxItems", "array has more than 1 items")),
+		(json!({"minProperties": 1}), json!({"a": 1}), json!({}), violation("", "/minProperties", "object has fewer than 1 properties")),
+		(json!({"maxProperties": 0}), json!({}), json!({"a": 1}), violation("", "/maxProperties", "object has more than 0 properties")),
+		(json!({"required": ["a"]}), json!({"a": null}), json!({"b": 1}), violation("", "/required", "missing required property \"a\"")),
+		(json!({"items": {"type": "integer"}}), json!([1, 2]), json!([1, "x"]), violation("/1", "/items/type", "expected integer, got string")),
+		(json!({"allOf": [true, {"type": "number"}]}), json!(1), json!("a"), violation("", "/allOf/1/type", "expected number, got string")),
+		(json!({"anyOf": [{"type": "null"}, {"type": "boolean"}]}), json!(true), json!(1), violation("", "/anyOf", "value does not match any schema in anyOf")),
+		(json!({"not": {"type": "null"}}), json!(1), json!(null), violation("", "/not", "value must not match the schema in not")),
+	];
+	for (schema, valid, invalid, expected) in cases {
+		assert_eq!(check(schema.clone(), valid), vec![], "{}", schema);
+		assert_eq!(check(schema.clone(), invalid), vec![expected], "{}", schema);
+	}
+
+	assert_eq!(check(json!(true), json!({"a": [1]})), vec![]);
+	assert_eq!(check(json!(false), json!(null)), vec![violation("", "", "schema is false; no value is allowed")]);
+}
+
+#[test]
+fn nested_paths() {
+	let schema = json!({
+		"type": "object",
+		"required": ["users"],
+		"properties": {
+			"users": {
+				"type": "array",
+				"items": {
+					"type": "object",
+					"required": ["name"],
+					"properties": {"name": {"type": "string"}, "tags": {"items": {"enum": ["a", "b"]}}},
+				},
+			},
+		},
+	});
+	let instance = json!({"users": [{"name": "x", "tags": ["a"]}, {"tags": ["a", "c"]}, {"name": 1}]});
+	assert_eq!(check(schema, instance), vec![
+		violation("/users/1", "/properties/users/items/required", "missing required property \"name\""),
+		violation("/users/1/tags/1", "/properties/users/items/properties/tags/items/enum", "\"c\" is not one of the enumerated values"),
+		violation("/users/2/name", "/properties/users/items/properties/name/type", "expected string, got integer"),
+	]);
+}
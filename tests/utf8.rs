@@ -0,0 +1,68 @@
+use minjson::{ParallelSetting, ParseError, ParseSetting};
+
+fn jsonc() -> ParseSetting {
+	ParseSetting { jsonc: true, ..Default::default() }
+}
+
+fn syntax_error(result: Result<&str, ParseError>) -> String {
+	match result {
+		Err(ParseError::Syntax(e)) => e,
+		other => panic!("expected a syntax error, got {:?}", other),
+	}
+}
+
+#[test]
+fn valid_input_is_returned_as_text() {
+	let json = "{\"caf\u{e9}\": [\"\u{4e2d}\u{1f600}\", 1]}".as_bytes();
+	assert_eq!(minjson::validate_json_bytes(json, ParseSetting::default()).unwrap(), std::str::from_utf8(json).unwrap());
+	let json = "// \u{e9}\n[1] /* \u{1f600} */".as_bytes();
+	assert_eq!(minjson::validate_json_bytes(json, jsonc()).unwrap(), std::str::from_utf8(json).unwrap());
+}
+
+#[test]
+fn invalid_bytes_are_reported_with_their_offset() {
+	let settings = ParseSetting::default();
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"[\"ab\xffcd\"]", settings)), "Invalid UTF-8 at byte 4");
+	// A truncated sequence at the end of a string
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"[\"\xc3\"]", settings)), "Invalid UTF-8 at byte 2");
+	// Outside strings, where the lexer expected a token
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"[1, \xe9]", settings)), "Invalid UTF-8 at byte 4");
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"[1]\xff", settings)), "Invalid UTF-8 at byte 3");
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"// \xff\n[1]", jsonc())), "Invalid UTF-8 at byte 3");
+	assert_eq!(syntax_error(minjson::validate_json_bytes(b"[1] /* \xc3( */", jsonc())), "Invalid UTF-8 at byte 7");
+}
+
+// The input is checked a block at a time, so sequences that straddle a block boundary, and
+// errors far from the start, must be handled like any other.
+#[test]
+fn long_input() {
+	for pad in 65530..65540 {
+		let mut json = format!("[\"{}", "a".repeat(pad)).into_bytes();
+		json.extend("\u{1f600}\"]".as_bytes());
+		assert!(minjson::validate_json_bytes(&json, ParseSetting::default()).is_ok(), "{}", pad);
+
+		let at = json.len() - 3;
+		json[at] = b'a';
+		assert_eq!(syntax_error(minjson::validate_json_bytes(&json, ParseSetting::default())), format!("Invalid UTF-8 at byte {}", pad + 2), "{}", pad);
+	}
+}
+
+#[test]
+fn byte_parsers() {
+	let parallel = ParallelSetting { jobs: 4, min_chunk_size: 0 };
+	let json = "[\"\u{e9}\", {\"\u{4e2d}\": [1, 2]}, \"x\"]";
+	assert_eq!(
+		minjson::build_json_graph_parallel_bytes(json.as_bytes(), ParseSetting::default(), parallel).unwrap(),
+		minjson::build_json_graph(json).unwrap(),
+	);
+	let e = minjson::build_json_graph_parallel_bytes(b"[1, \"\xff\", 3]", ParseSetting::default(), parallel).unwrap_err();
+	assert!(e.contains("Invalid UTF-8"), "{}", e);
+
+	let lines = "{\"a\": \"\u{e9}\"}\r\n\n[1]\n".as_bytes();
+	assert_eq!(
+		minjson::parse_ndjson_bytes(lines, ParseSetting::default(), parallel).unwrap(),
+		minjson::parse_ndjson(std::str::from_utf8(lines).unwrap(), ParseSetting::default(), parallel).unwrap(),
+	);
+	let e = minjson::parse_ndjson_bytes(b"1\n2\n\"\xff\"\n", ParseSetting::default(), parallel).unwrap_err();
+	assert_eq!(e, "Line 3: Invalid UTF-8 at byte 1");
+}